@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
-use tauri::{AppHandle, Manager};
+use tauri::{AppHandle, Emitter, Manager};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct FileItem {
@@ -16,8 +16,28 @@ pub struct FileItem {
     pub icon_color: Option<String>,
     pub modified: Option<u64>,
     pub size: Option<u64>,
+    /// When the canvas was last marked opened (Unix millis), from `.opened.json`.
+    #[serde(rename = "openedAt")]
+    pub opened_at: Option<u64>,
+    /// Length of the canvas's `elements` array; `None` for folders and unparseable files.
+    #[serde(rename = "elementCount")]
+    pub element_count: Option<usize>,
 }
 
+/// Just enough of a canvas to count its elements without building their values.
+#[derive(Deserialize)]
+struct ElementsOnly {
+    elements: Vec<serde::de::IgnoredAny>,
+}
+
+fn canvas_element_count(full_path: &Path) -> Option<usize> {
+    let content = read_canvas_bytes(full_path).ok()?;
+    let doc: ElementsOnly = serde_json::from_slice(&content).ok()?;
+    Some(doc.elements.len())
+}
+
+const DEFAULT_CANVAS_CONTENT: &str = r##"{"type":"excalidraw","version":2,"source":"excalidrauri","elements":[],"appState":{"gridSize":null,"viewBackgroundColor":"#ffffff"},"files":{}}"##;
+
 /// Resolve the base directory for canvas storage.
 /// Returns `~/.local/share/com.nnao45.excalidrauri/canvases` (or platform equivalent).
 pub fn resolve_base_dir(app: &AppHandle) -> Result<PathBuf, Box<dyn std::error::Error>> {
@@ -40,6 +60,10 @@ fn get_trash_dir(app: &AppHandle) -> Result<PathBuf, String> {
     resolve_trash_dir(app).map_err(|e| e.to_string())
 }
 
+fn get_app_data_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path().app_data_dir().map_err(|e| e.to_string())
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TrashItem {
     pub name: String,
@@ -51,9 +75,11 @@ pub struct TrashItem {
     pub is_folder: bool,
     #[serde(rename = "trashedAt")]
     pub trashed_at: u64,
+    /// Top-level vault folder whose sub-trash holds the item; `None` for the flat trash.
+    pub namespace: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 struct TrashMeta {
     original_path: String,
     trashed_at: u64,
@@ -76,7 +102,42 @@ fn safe_relative_path(relative: &str) -> Result<(), String> {
     Ok(())
 }
 
+/// Whether a relative path names the vault root itself (`""`, `"."`, `"./"` and the like).
+fn is_vault_root(relative: &str) -> bool {
+    !Path::new(relative)
+        .components()
+        .any(|c| matches!(c, std::path::Component::Normal(_)))
+}
+
+/// Convert Windows-style `\` separators to `/` and validate the result with
+/// `safe_relative_path`, so both separator styles resolve identically.
+fn normalize_relative_path(relative: &str) -> Result<String, String> {
+    let normalized = relative.replace('\\', "/");
+    safe_relative_path(&normalized)?;
+    Ok(normalized)
+}
+
+/// The sidebar listing in plain lexical order; the app itself always goes through `collect_items_sorted`.
+#[cfg(test)]
 fn collect_items(base: &PathBuf, dir: &PathBuf) -> Result<Vec<FileItem>, String> {
+    collect_items_sorted(base, dir, false)
+}
+
+/// `collect_items`, ordering names with `natural_cmp` instead of plain lexical order when `natural_sort` is set.
+fn collect_items_sorted(
+    base: &Path,
+    dir: &Path,
+    natural_sort: bool,
+) -> Result<Vec<FileItem>, String> {
+    collect_items_with(base, dir, natural_sort, &load_opened(base))
+}
+
+fn collect_items_with(
+    base: &Path,
+    dir: &Path,
+    natural_sort: bool,
+    opened: &OpenIndex,
+) -> Result<Vec<FileItem>, String> {
     let mut items = Vec::new();
 
     let entries = fs::read_dir(dir).map_err(|e| e.to_string())?;
@@ -97,10 +158,10 @@ fn collect_items(base: &PathBuf, dir: &PathBuf) -> Result<Vec<FileItem>, String>
             .unwrap_or_default();
 
         let is_folder = metadata.is_dir();
-        
+
         // Load icon from metadata file
         let (icon, icon_color) = load_item_icon(base, &relative_path);
-        
+
         // Get file metadata
         let modified = metadata.modified().ok()
             .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
@@ -108,7 +169,7 @@ fn collect_items(base: &PathBuf, dir: &PathBuf) -> Result<Vec<FileItem>, String>
         let size = if !is_folder { Some(metadata.len()) } else { None };
 
         if is_folder {
-            let children = collect_items(base, &entry_path)?;
+            let children = collect_items_with(base, &entry_path, natural_sort, opened)?;
             items.push(FileItem {
                 name,
                 path: relative_path,
@@ -118,8 +179,12 @@ fn collect_items(base: &PathBuf, dir: &PathBuf) -> Result<Vec<FileItem>, String>
                 icon_color,
                 modified,
                 size,
+                opened_at: None,
+                element_count: None,
             });
-        } else if name.ends_with(".excalidraw") {
+        } else if is_canvas_name(&name) {
+            let opened_at = opened.get(&relative_path).copied();
+            let element_count = canvas_element_count(&entry_path);
             items.push(FileItem {
                 name,
                 path: relative_path,
@@ -129,34 +194,41 @@ fn collect_items(base: &PathBuf, dir: &PathBuf) -> Result<Vec<FileItem>, String>
                 icon_color,
                 modified,
                 size,
+                opened_at,
+                element_count,
             });
         }
-        // Skip non-.excalidraw files silently
+        // Skip non-canvas files silently
     }
 
     // Sort: folders first, then files alphabetically
     items.sort_by(|a, b| match (a.is_folder, b.is_folder) {
         (true, false) => std::cmp::Ordering::Less,
         (false, true) => std::cmp::Ordering::Greater,
+        _ if natural_sort => natural_cmp(&a.name, &b.name),
         _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
     });
 
     Ok(items)
 }
 
-#[tauri::command]
+#[tauri::command(async)]
 pub fn get_base_directory(app: AppHandle) -> Result<String, String> {
     get_base_dir(&app).map(|p| p.to_string_lossy().to_string())
 }
 
-#[tauri::command]
-pub fn list_dir(app: AppHandle, path: String) -> Result<Vec<FileItem>, String> {
+#[tauri::command(async)]
+pub fn list_dir(
+    app: AppHandle,
+    path: String,
+    natural_sort: Option<bool>,
+) -> Result<Vec<FileItem>, String> {
     let base = get_base_dir(&app)?;
 
     let target = if path.is_empty() {
         base.clone()
     } else {
-        safe_relative_path(&path)?;
+        let path = normalize_relative_path(&path)?;
         base.join(&path)
     };
 
@@ -164,125 +236,412 @@ pub fn list_dir(app: AppHandle, path: String) -> Result<Vec<FileItem>, String> {
         return Ok(Vec::new());
     }
 
-    collect_items(&base, &target)
+    collect_items_sorted(&base, &target, natural_sort.unwrap_or(false))
 }
 
-#[tauri::command]
-pub fn create_folder(app: AppHandle, path: String) -> Result<(), String> {
-    safe_relative_path(&path)?;
-    let base = get_base_dir(&app)?;
-    let full_path = base.join(&path);
-    fs::create_dir_all(&full_path).map_err(|e| e.to_string())
+#[tauri::command(async)]
+pub fn create_folder(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    path: String,
+) -> Result<(), String> {
+    fs_ops.run(|| {
+        let path = normalize_relative_path(&path)?;
+        let base = get_base_dir(&app)?;
+        let full_path = base.join(&path);
+        fs::create_dir_all(&full_path).map_err(|e| e.to_string())?;
+        let _ = append_history(&base, "create", None, Some(&path));
+        Ok(())
+    })
 }
 
-#[tauri::command]
-pub fn create_canvas(app: AppHandle, path: String) -> Result<(), String> {
-    safe_relative_path(&path)?;
-    let base = get_base_dir(&app)?;
-    let full_path = base.join(&path);
+#[tauri::command(async)]
+pub fn create_canvas(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    path: String,
+) -> Result<(), String> {
+    fs_ops.run(|| {
+        let path = normalize_relative_path(&path)?;
+        let base = get_base_dir(&app)?;
+        let full_path = base.join(&path);
 
-    if let Some(parent) = full_path.parent() {
-        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
-    }
+        if let Some(parent) = full_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
 
-    let default_content = r##"{"type":"excalidraw","version":2,"source":"excalidrauri","elements":[],"appState":{"gridSize":null,"viewBackgroundColor":"#ffffff"},"files":{}}"##;
-    fs::write(&full_path, default_content).map_err(|e| e.to_string())
+        fs::write(&full_path, DEFAULT_CANVAS_CONTENT).map_err(|e| e.to_string())?;
+        let _ = append_history(&base, "create", None, Some(&path));
+        Ok(())
+    })
 }
 
-#[tauri::command]
-pub fn delete_item(app: AppHandle, path: String) -> Result<(), String> {
-    safe_relative_path(&path)?;
-    let base = get_base_dir(&app)?;
-    let full_path = base.join(&path);
+#[tauri::command(async)]
+pub fn delete_item(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    path: String,
+) -> Result<(), String> {
+    fs_ops.run(|| {
+        let path = normalize_relative_path(&path)?;
+        let base = get_base_dir(&app)?;
+        delete_entry(&base, &path)
+    })
+}
+
+fn delete_entry(base: &Path, path: &str) -> Result<(), String> {
+    let full_path = base.join(path);
 
     let metadata = fs::metadata(&full_path).map_err(|e| e.to_string())?;
+    let canvases = if metadata.is_dir() {
+        walk_canvases(base, &full_path)?
+    } else {
+        vec![path.to_string()]
+    };
     if metadata.is_dir() {
-        fs::remove_dir_all(&full_path).map_err(|e| e.to_string())
+        fs::remove_dir_all(&full_path).map_err(|e| e.to_string())?;
     } else {
-        fs::remove_file(&full_path).map_err(|e| e.to_string())
+        fs::remove_file(&full_path).map_err(|e| e.to_string())?;
     }
+    let _ = append_history(base, "delete", Some(path), None);
+    drop_versions_of_missing(base, &canvases);
+    forget_open_stats(base, &canvases);
+    Ok(())
 }
 
-#[tauri::command]
-pub fn rename_item(app: AppHandle, old_path: String, new_path: String) -> Result<(), String> {
-    safe_relative_path(&old_path)?;
-    safe_relative_path(&new_path)?;
-    let base = get_base_dir(&app)?;
-    let old_full = base.join(&old_path);
-    let new_full = base.join(&new_path);
+#[tauri::command(async)]
+pub fn rename_item(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    old_path: String,
+    new_path: String,
+) -> Result<(), String> {
+    fs_ops.run(|| {
+        let old_path = normalize_relative_path(&old_path)?;
+        let new_path = normalize_relative_path(&new_path)?;
+        let base = get_base_dir(&app)?;
+        let trash = get_trash_dir(&app)?;
+
+        rename_entry(&base, &old_path, &new_path)?;
+        let _ = retarget_trash_metas(&trash, &old_path, &new_path);
+        emit_folder_renamed(&app, &base, &old_path, &new_path);
+        Ok(())
+    })
+}
+
+fn rename_entry(base: &Path, old_path: &str, new_path: &str) -> Result<(), String> {
+    if old_path == new_path {
+        return Ok(());
+    }
+    let old_full = base.join(old_path);
+    let new_full = base.join(new_path);
 
     if let Some(parent) = new_full.parent() {
         fs::create_dir_all(parent).map_err(|e| e.to_string())?;
     }
 
-    fs::rename(&old_full, &new_full).map_err(|e| e.to_string())
+    rename_path(&old_full, &new_full)?;
+    move_sidecars(base, old_path, new_path);
+    let _ = append_history(base, "rename", Some(old_path), Some(new_path));
+    Ok(())
 }
 
-#[tauri::command]
+/// After `old_path` has been moved to `new_path`, emit `folder-renamed` with where each canvas
+/// inside went so open tabs can follow. Nothing is emitted for a canvas.
+fn emit_folder_renamed(app: &AppHandle, base: &Path, old_path: &str, new_path: &str) {
+    let new_full = base.join(new_path);
+    if old_path == new_path || !new_full.is_dir() {
+        return;
+    }
+    let prefix = format!("{}/", new_path);
+    let remapped = walk_canvases(base, &new_full)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|to| {
+            let rest = to.strip_prefix(&prefix)?.to_string();
+            Some(PathRemap {
+                from: format!("{}/{}", old_path, rest),
+                to,
+            })
+        })
+        .collect();
+    let payload = FolderRenamed {
+        from: old_path.to_string(),
+        to: new_path.to_string(),
+        remapped,
+    };
+    let _ = app.emit("folder-renamed", payload);
+}
+
+/// Carry everything kept beside the vault's items under their path (version history, icons,
+/// open statistics, manual order) from `old_path` to `new_path` after a move on disk.
+fn move_sidecars(base: &Path, old_path: &str, new_path: &str) {
+    move_versions(base, old_path, new_path);
+    move_icon_sidecars(base, old_path, new_path);
+    let _ = move_open_stats(base, old_path, new_path);
+    let _ = move_order_entry(base, old_path, new_path);
+}
+
+/// Whether `a` and `b` name the same file system entry.
+fn same_entry(a: &Path, b: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (a.symlink_metadata(), b.symlink_metadata()) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+            _ => false,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        match (a.canonicalize(), b.canonicalize()) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+/// Carry version history across a rename that has already happened on disk.
+/// For a folder, every canvas now under `new_path` takes its history along. Best-effort.
+fn move_versions(base: &Path, old_path: &str, new_path: &str) {
+    let new_full = base.join(new_path);
+    let moved = if new_full.is_dir() {
+        walk_canvases(base, &new_full).unwrap_or_default()
+    } else {
+        vec![new_path.to_string()]
+    };
+
+    for path in moved {
+        let Some(rest) = path.strip_prefix(new_path) else {
+            continue;
+        };
+        let from = get_versions_dir(base, &format!("{}{}", old_path, rest));
+        let to = get_versions_dir(base, &path);
+        if from.is_dir() && !to.exists() {
+            let _ = rename_path(&from, &to);
+        }
+    }
+}
+
+/// Carry the icon sidecars of a renamed item, and of every folder and canvas inside a renamed
+/// folder, to their new paths. Best-effort, like `move_versions`.
+fn move_icon_sidecars(base: &Path, old_path: &str, new_path: &str) {
+    let new_full = base.join(new_path);
+    let mut moved = vec![new_path.to_string()];
+    if new_full.is_dir() {
+        let _ = walk_discovered(base, &new_full, &mut |item| moved.push(item.path));
+    }
+
+    for path in moved {
+        let Some(rest) = path.strip_prefix(new_path) else {
+            continue;
+        };
+        let from = get_icon_meta_path(base, &format!("{}{}", old_path, rest));
+        if from.exists() {
+            let _ = fs::rename(&from, get_icon_meta_path(base, &path));
+        }
+    }
+}
+
+#[tauri::command(async)]
 pub fn read_canvas(app: AppHandle, path: String) -> Result<String, String> {
-    safe_relative_path(&path)?;
+    let path = normalize_relative_path(&path)?;
     let base = get_base_dir(&app)?;
     let full_path = base.join(&path);
-    fs::read_to_string(&full_path).map_err(|e| e.to_string())
+    read_canvas_text(&full_path)
 }
 
-#[tauri::command]
-pub fn save_canvas(app: AppHandle, path: String, content: String) -> Result<(), String> {
-    safe_relative_path(&path)?;
-    let base = get_base_dir(&app)?;
-    let full_path = base.join(&path);
+#[tauri::command(async)]
+pub fn save_canvas(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    cache: tauri::State<'_, VaultSizeCache>,
+    path: String,
+    content: String,
+    pretty: Option<bool>,
+) -> Result<(), SaveError> {
+    fs_ops.run(|| {
+        let path = normalize_relative_path(&path)?;
+        let base = get_base_dir(&app)?;
+        let settings = load_settings(&get_app_data_dir(&app)?);
+        save_canvas_within_quota(
+            &base,
+            &path,
+            &content,
+            pretty.unwrap_or(false),
+            settings.max_vault_bytes,
+            &cache,
+        )
+    })
+}
+
+/// Canvas content as sent by the frontend, or re-serialized as indented JSON
+/// when `pretty` is set so the file diffs well under version control.
+fn format_canvas_content(content: &str, pretty: bool) -> Result<std::borrow::Cow<'_, str>, String> {
+    if !pretty {
+        return Ok(content.into());
+    }
+    let doc: serde_json::Value = serde_json::from_str(content).map_err(|e| e.to_string())?;
+    let formatted = serde_json::to_string_pretty(&doc).map_err(|e| e.to_string())?;
+    Ok(formatted.into())
+}
 
+fn save_canvas_at(full_path: &Path, content: &str, pretty: bool) -> Result<(), String> {
     if let Some(parent) = full_path.parent() {
         fs::create_dir_all(parent).map_err(|e| e.to_string())?;
     }
+    let content = format_canvas_content(content, pretty)?;
+    write_canvas_bytes(full_path, content.as_bytes())
+}
+
+#[tauri::command(async)]
+pub fn copy_canvas(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    source_path: String,
+    dest_path: String,
+) -> Result<(), String> {
+    fs_ops.run(|| {
+        let source_path = normalize_relative_path(&source_path)?;
+        let dest_path = normalize_relative_path(&dest_path)?;
+        let base = get_base_dir(&app)?;
+        let source = base.join(&source_path);
+        let dest = base.join(&dest_path);
+
+        if !source.exists() {
+            return Err("Source file does not exist".to_string());
+        }
+
+        if dest.exists() {
+            return Err("Destination file already exists".to_string());
+        }
+
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+
+        fs::copy(&source, &dest).map_err(|e| e.to_string())?;
+
+        // Copy icon metadata if exists
+        let meta_dir = base.join(".meta");
+        let source_meta = get_icon_meta_path(&base, &source_path);
+        let dest_meta = get_icon_meta_path(&base, &dest_path);
+
+        if source_meta.exists() {
+            fs::create_dir_all(&meta_dir).map_err(|e| e.to_string())?;
+            let _ = fs::copy(&source_meta, &dest_meta);
+        }
 
-    fs::write(&full_path, content).map_err(|e| e.to_string())
+        Ok(())
+    })
 }
 
-#[tauri::command]
-pub fn copy_canvas(app: AppHandle, source_path: String, dest_path: String) -> Result<(), String> {
-    safe_relative_path(&source_path)?;
-    safe_relative_path(&dest_path)?;
-    let base = get_base_dir(&app)?;
-    let source = base.join(&source_path);
-    let dest = base.join(&dest_path);
+#[tauri::command(async)]
+pub fn trash_item(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    path: String,
+) -> Result<(), String> {
+    fs_ops.run(|| {
+        let path = normalize_relative_path(&path)?;
+        let base = get_base_dir(&app)?;
+        let trash = get_trash_dir(&app)?;
+        let settings = load_settings(&get_app_data_dir(&app)?);
+        move_to_trash(
+            &base,
+            &trash_for(&trash, &path, settings.namespaced_trash),
+            &path,
+        )?;
+        Ok(())
+    })
+}
 
-    if !source.exists() {
-        return Err("Source file does not exist".to_string());
-    }
+/// Consolidated metadata for every trashed item, keyed by its name inside the trash.
+/// Items trashed before the index existed keep a `<name>.meta` sidecar instead.
+const TRASH_INDEX: &str = "index.json";
 
-    if dest.exists() {
-        return Err("Destination file already exists".to_string());
+type TrashIndex = std::collections::BTreeMap<String, TrashMeta>;
+
+/// The trash index, empty when there is none. An index that fails to parse is moved aside to
+/// `index.json.bak` rather than being silently replaced by the next write.
+fn read_trash_index(trash: &Path) -> TrashIndex {
+    let path = trash.join(TRASH_INDEX);
+    let Ok(json) = fs::read_to_string(&path) else {
+        return TrashIndex::default();
+    };
+    serde_json::from_str(&json).unwrap_or_else(|_| {
+        let _ = fs::rename(
+            &path,
+            unique_child_path(trash, &format!("{}.bak", TRASH_INDEX)),
+        );
+        TrashIndex::default()
+    })
+}
+
+fn write_trash_index(trash: &Path, index: &TrashIndex) -> Result<(), String> {
+    let json = serde_json::to_string(index).map_err(|e| e.to_string())?;
+    write_atomic(&trash.join(TRASH_INDEX), &json)
+}
+
+/// Metadata of a trashed item from the index, falling back to its legacy `.meta` sidecar.
+fn trash_meta(index: &TrashIndex, trash: &Path, trash_name: &str) -> Result<TrashMeta, String> {
+    if let Some(meta) = index.get(trash_name) {
+        return Ok(meta.clone());
     }
+    let meta_json = fs::read_to_string(trash.join(format!("{}.meta", trash_name)))
+        .map_err(|e| e.to_string())?;
+    serde_json::from_str(&meta_json).map_err(|e| e.to_string())
+}
 
-    if let Some(parent) = dest.parent() {
-        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+/// Drop a trashed item's metadata from both the index and any legacy sidecar.
+fn forget_trash_meta(trash: &Path, trash_name: &str) -> Result<(), String> {
+    let mut index = read_trash_index(trash);
+    if index.remove(trash_name).is_some() {
+        write_trash_index(trash, &index)?;
     }
+    let _ = fs::remove_file(trash.join(format!("{}.meta", trash_name)));
+    Ok(())
+}
 
-    fs::copy(&source, &dest).map_err(|e| e.to_string())?;
-    
-    // Copy icon metadata if exists
-    let meta_dir = base.join(".meta");
-    let source_meta = get_icon_meta_path(&base, &source_path);
-    let dest_meta = get_icon_meta_path(&base, &dest_path);
-    
-    if source_meta.exists() {
-        fs::create_dir_all(&meta_dir).map_err(|e| e.to_string())?;
-        let _ = fs::copy(&source_meta, &dest_meta);
+/// The trash `path` goes to: `trash/<top-level folder>` when `namespaced`, so a large vault's
+/// trash stays navigable, else the flat trash. Items at the vault root always use the flat trash.
+fn trash_for(trash: &Path, path: &str, namespaced: bool) -> PathBuf {
+    match path.split_once('/') {
+        Some((root, _)) if namespaced => trash.join(root),
+        _ => trash.to_path_buf(),
     }
+}
 
-    Ok(())
+/// Sub-trashes created by `trash_for`, as `(namespace, dir)`. Unlike a trashed folder they
+/// have no metadata in the parent trash and keep an index of their own.
+fn trash_namespaces(trash: &Path, index: &TrashIndex) -> Result<Vec<(String, PathBuf)>, String> {
+    let mut namespaces = Vec::new();
+    for entry in fs::read_dir(trash).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let dir = entry.path();
+        if dir.join(TRASH_INDEX).is_file() && trash_meta(index, trash, &name).is_err() {
+            namespaces.push((name, dir));
+        }
+    }
+    namespaces.sort();
+    Ok(namespaces)
 }
 
-#[tauri::command]
-pub fn trash_item(app: AppHandle, path: String) -> Result<(), String> {
-    safe_relative_path(&path)?;
-    let base = get_base_dir(&app)?;
-    let trash = get_trash_dir(&app)?;
+/// The trash directory holding `trash_path` and the item's name within it.
+/// Namespaced items are addressed as `<namespace>/<name>`.
+fn locate_in_trash<'a>(trash: &Path, trash_path: &'a str) -> (PathBuf, &'a str) {
+    match trash_path.split_once('/') {
+        Some((namespace, name)) => (trash.join(namespace), name),
+        None => (trash.to_path_buf(), trash_path),
+    }
+}
 
-    fs::create_dir_all(&trash).map_err(|e| e.to_string())?;
+/// Move `path` into the trash and record it in the trash index, returning its name inside the trash.
+fn move_to_trash(base: &Path, trash: &Path, path: &str) -> Result<String, String> {
+    fs::create_dir_all(trash).map_err(|e| e.to_string())?;
 
-    let source = base.join(&path);
+    let source = base.join(path);
     // Validate source exists before attempting move
     fs::metadata(&source).map_err(|e| e.to_string())?;
 
@@ -297,50 +656,68 @@ pub fn trash_item(app: AppHandle, path: String) -> Result<(), String> {
         .unwrap_or_default()
         .as_millis() as u64;
 
-    let trash_name = format!("{}_{}", ts, original_name);
+    // Same-named items trashed within the same millisecond get a counter after the timestamp
+    let mut index = read_trash_index(trash);
+    let mut trash_name = format!("{}_{}", ts, original_name);
+    let mut n = 2;
+    while index.contains_key(&trash_name) || trash.join(&trash_name).symlink_metadata().is_ok() {
+        trash_name = format!("{}_{}_{}", ts, n, original_name);
+        n += 1;
+    }
     let dest = trash.join(&trash_name);
 
     fs::rename(&source, &dest).map_err(|e| e.to_string())?;
+    let _ = append_history(base, "trash", Some(path), None);
 
-    let meta = TrashMeta {
-        original_path: path,
-        trashed_at: ts,
-    };
-    let meta_json = serde_json::to_string(&meta).map_err(|e| e.to_string())?;
-    let meta_path = trash.join(format!("{}.meta", trash_name));
-    fs::write(&meta_path, meta_json).map_err(|e| e.to_string())?;
+    index.insert(
+        trash_name.clone(),
+        TrashMeta {
+            original_path: path.to_string(),
+            trashed_at: ts,
+        },
+    );
+    write_trash_index(trash, &index)?;
 
-    Ok(())
+    Ok(trash_name)
 }
 
-#[tauri::command]
+#[tauri::command(async)]
 pub fn list_trash(app: AppHandle) -> Result<Vec<TrashItem>, String> {
     let trash = get_trash_dir(&app)?;
+    read_trash_items(&trash)
+}
 
+fn read_trash_items(trash: &Path) -> Result<Vec<TrashItem>, String> {
     if !trash.exists() {
         return Ok(Vec::new());
     }
 
     let mut items = Vec::new();
-    let entries = fs::read_dir(&trash).map_err(|e| e.to_string())?;
+    let index = read_trash_index(trash);
+    let namespaces = trash_namespaces(trash, &index)?;
+    for (namespace, dir) in &namespaces {
+        for item in read_trash_items(dir)? {
+            items.push(TrashItem {
+                trash_path: format!("{}/{}", namespace, item.trash_path),
+                namespace: Some(namespace.clone()),
+                ..item
+            });
+        }
+    }
 
+    let entries = fs::read_dir(trash).map_err(|e| e.to_string())?;
     for entry in entries {
         let entry = entry.map_err(|e| e.to_string())?;
         let name = entry.file_name().to_string_lossy().to_string();
 
-        if name.ends_with(".meta") {
+        if name.ends_with(".meta") || name == TRASH_INDEX || name.starts_with('.') {
             continue;
         }
 
         let entry_meta = entry.metadata().map_err(|e| e.to_string())?;
         let is_folder = entry_meta.is_dir();
 
-        let meta_path = trash.join(format!("{}.meta", name));
-        let meta_json = match fs::read_to_string(&meta_path) {
-            Ok(s) => s,
-            Err(_) => continue,
-        };
-        let meta: TrashMeta = match serde_json::from_str(&meta_json) {
+        let meta = match trash_meta(&index, trash, &name) {
             Ok(m) => m,
             Err(_) => continue,
         };
@@ -350,8 +727,8 @@ pub fn list_trash(app: AppHandle) -> Result<Vec<TrashItem>, String> {
             .map(|n| n.to_string_lossy().to_string())
             .unwrap_or_else(|| name.clone());
 
-        let display_name = if !is_folder && original_name.ends_with(".excalidraw") {
-            original_name[..original_name.len() - ".excalidraw".len()].to_string()
+        let display_name = if !is_folder && is_canvas_name(&original_name) {
+            canvas_stem(&original_name).to_string()
         } else {
             original_name
         };
@@ -362,6 +739,7 @@ pub fn list_trash(app: AppHandle) -> Result<Vec<TrashItem>, String> {
             original_path: meta.original_path,
             is_folder,
             trashed_at: meta.trashed_at,
+            namespace: None,
         });
     }
 
@@ -370,78 +748,146 @@ pub fn list_trash(app: AppHandle) -> Result<Vec<TrashItem>, String> {
     Ok(items)
 }
 
-#[tauri::command]
-pub fn restore_item(app: AppHandle, trash_path: String) -> Result<(), String> {
+#[tauri::command(async)]
+pub fn list_trash_since(app: AppHandle, since_millis: u64) -> Result<Vec<TrashItem>, String> {
     let trash = get_trash_dir(&app)?;
-    let base = get_base_dir(&app)?;
+    trash_items_since(&trash, since_millis)
+}
+
+/// Items trashed strictly after `since_millis`, newest first.
+fn trash_items_since(trash: &Path, since_millis: u64) -> Result<Vec<TrashItem>, String> {
+    let mut items = read_trash_items(trash)?;
+    items.retain(|item| item.trashed_at > since_millis);
+    Ok(items)
+}
+
+#[tauri::command(async)]
+pub fn restore_item(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    trash_path: String,
+) -> Result<(), String> {
+    fs_ops.run(|| {
+        let trash = get_trash_dir(&app)?;
+        let base = get_base_dir(&app)?;
+        restore_from_trash(&base, &trash, &trash_path)
+    })
+}
 
-    let source = trash.join(&trash_path);
-    let meta_path = trash.join(format!("{}.meta", trash_path));
+/// Error unless `path`, once symlinks in its existing ancestors are resolved, lies inside `base`.
+fn ensure_within_base(base: &Path, path: &Path) -> Result<(), String> {
+    let canonical_base = base.canonicalize().map_err(|e| e.to_string())?;
+    let existing = path
+        .ancestors()
+        .find(|p| p.exists())
+        .ok_or("Invalid path")?;
+    let canonical = existing.canonicalize().map_err(|e| e.to_string())?;
+    if !canonical.starts_with(&canonical_base) {
+        return Err("復元先が保管庫の外を指しています".to_string());
+    }
+    Ok(())
+}
 
-    let meta_json = fs::read_to_string(&meta_path).map_err(|e| e.to_string())?;
-    let meta: TrashMeta = serde_json::from_str(&meta_json).map_err(|e| e.to_string())?;
+fn restore_from_trash(base: &Path, trash: &Path, trash_path: &str) -> Result<(), String> {
+    safe_relative_path(trash_path)?;
+    let (trash_dir, trash_path) = locate_in_trash(trash, trash_path);
+    let trash = trash_dir.as_path();
+    let source = trash.join(trash_path);
+    let meta = trash_meta(&read_trash_index(trash), trash, trash_path)?;
 
     safe_relative_path(&meta.original_path)?;
     let dest = base.join(&meta.original_path);
 
-    if let Some(parent) = dest.parent() {
-        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
-    }
+    // A tampered meta or a symlinked folder must not let the restore escape the vault
+    let parent = dest.parent().ok_or("Invalid path")?;
+    ensure_within_base(base, parent)?;
+    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    ensure_within_base(base, parent)?;
 
+    // After a rename the snapshot points at the live canvas; never restore over it
+    if dest.symlink_metadata().is_ok() {
+        return Err("復元先に同名のアイテムが既に存在します".to_string());
+    }
     fs::rename(&source, &dest).map_err(|e| e.to_string())?;
-    let _ = fs::remove_file(&meta_path);
+    let _ = forget_trash_meta(trash, trash_path);
 
     Ok(())
 }
 
-#[tauri::command]
-pub fn delete_permanently(app: AppHandle, trash_path: String) -> Result<(), String> {
-    let trash = get_trash_dir(&app)?;
+#[tauri::command(async)]
+pub fn delete_permanently(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    trash_path: String,
+) -> Result<(), String> {
+    fs_ops.run(|| {
+        let trash = get_trash_dir(&app)?;
+        let base = get_base_dir(&app)?;
+        delete_from_trash(&base, &trash, &trash_path)
+    })
+}
 
-    let target = trash.join(&trash_path);
-    let meta_path = trash.join(format!("{}.meta", trash_path));
+fn delete_from_trash(base: &Path, trash: &Path, trash_path: &str) -> Result<(), String> {
+    let (trash_dir, trash_path) = locate_in_trash(trash, trash_path);
+    let trash = trash_dir.as_path();
+    let target = trash.join(trash_path);
+    let meta = trash_meta(&read_trash_index(trash), trash, trash_path).ok();
 
     let target_meta = fs::metadata(&target).map_err(|e| e.to_string())?;
+    // Vault paths of the canvases going away, so their version history can follow
+    let canvases: Vec<String> = match &meta {
+        Some(meta) if target_meta.is_dir() => walk_canvases(&target, &target)?
+            .into_iter()
+            .map(|p| format!("{}/{}", meta.original_path, p))
+            .collect(),
+        Some(meta) => vec![meta.original_path.clone()],
+        None => Vec::new(),
+    };
+
     if target_meta.is_dir() {
         fs::remove_dir_all(&target).map_err(|e| e.to_string())?;
     } else {
         fs::remove_file(&target).map_err(|e| e.to_string())?;
     }
 
-    let _ = fs::remove_file(&meta_path);
+    let _ = forget_trash_meta(trash, trash_path);
+    drop_versions_of_missing(base, &canvases);
+    forget_open_stats(base, &canvases);
 
     Ok(())
 }
 
-#[tauri::command]
-pub fn empty_trash(app: AppHandle) -> Result<(), String> {
-    let trash = get_trash_dir(&app)?;
+#[tauri::command(async)]
+pub fn empty_trash(app: AppHandle, fs_ops: tauri::State<'_, FsSemaphore>) -> Result<(), String> {
+    fs_ops.run(|| {
+        let trash = get_trash_dir(&app)?;
 
-    if !trash.exists() {
-        return Ok(());
-    }
+        if !trash.exists() {
+            return Ok(());
+        }
 
-    let entries = fs::read_dir(&trash).map_err(|e| e.to_string())?;
-    for entry in entries {
-        let entry = entry.map_err(|e| e.to_string())?;
-        let m = entry.metadata().map_err(|e| e.to_string())?;
-        if m.is_dir() {
-            fs::remove_dir_all(entry.path()).map_err(|e| e.to_string())?;
-        } else {
-            fs::remove_file(entry.path()).map_err(|e| e.to_string())?;
+        let entries = fs::read_dir(&trash).map_err(|e| e.to_string())?;
+        for entry in entries {
+            let entry = entry.map_err(|e| e.to_string())?;
+            let m = entry.metadata().map_err(|e| e.to_string())?;
+            if m.is_dir() {
+                fs::remove_dir_all(entry.path()).map_err(|e| e.to_string())?;
+            } else {
+                fs::remove_file(entry.path()).map_err(|e| e.to_string())?;
+            }
         }
-    }
 
-    Ok(())
+        Ok(())
+    })
 }
 
-fn get_icon_meta_path(base: &PathBuf, item_path: &str) -> PathBuf {
+fn get_icon_meta_path(base: &Path, item_path: &str) -> PathBuf {
     let meta_dir = base.join(".meta");
     let safe_path = item_path.replace('/', "_").replace('\\', "_");
     meta_dir.join(format!("{}.icon", safe_path))
 }
 
-fn load_item_icon(base: &PathBuf, item_path: &str) -> (Option<String>, Option<String>) {
+fn load_item_icon(base: &Path, item_path: &str) -> (Option<String>, Option<String>) {
     let meta_path = get_icon_meta_path(base, item_path);
     if let Ok(content) = fs::read_to_string(meta_path) {
         // Format: "iconName:color" or just "iconName"
@@ -456,259 +902,10727 @@ fn load_item_icon(base: &PathBuf, item_path: &str) -> (Option<String>, Option<St
     }
 }
 
-#[tauri::command]
-pub fn set_item_icon(app: AppHandle, path: String, icon: String, color: Option<String>) -> Result<(), String> {
-    safe_relative_path(&path)?;
+#[tauri::command(async)]
+pub fn set_item_icon(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    path: String,
+    icon: String,
+    color: Option<String>,
+) -> Result<(), String> {
+    fs_ops.run(|| {
+        let path = normalize_relative_path(&path)?;
+        let base = get_base_dir(&app)?;
+        let meta_dir = base.join(".meta");
+
+        fs::create_dir_all(&meta_dir).map_err(|e| e.to_string())?;
+
+        let meta_path = get_icon_meta_path(&base, &path);
+        let content = if let Some(c) = color {
+            format!("{}:{}", icon, c)
+        } else {
+            icon
+        };
+        fs::write(meta_path, content).map_err(|e| e.to_string())
+    })
+}
+
+const CANVAS_TYPE: &str = "excalidraw";
+const CANVAS_VERSION: u64 = 2;
+const CANVAS_SOURCE: &str = "excalidrauri";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FormatInfo {
+    #[serde(rename = "type")]
+    pub format_type: Option<String>,
+    pub version: Option<u64>,
+    pub source: Option<String>,
+    #[serde(rename = "isExpectedType")]
+    pub is_expected_type: bool,
+    #[serde(rename = "isExpectedVersion")]
+    pub is_expected_version: bool,
+    #[serde(rename = "isNativeSource")]
+    pub is_native_source: bool,
+}
+
+fn read_canvas_json(path: &Path) -> Result<serde_json::Value, String> {
+    let content = read_canvas_text(path)?;
+    serde_json::from_str(&content).map_err(|e| e.to_string())
+}
+
+/// Extract the `type` / `version` / `source` header of a parsed canvas.
+/// Older files may lack `source`, in which case it is reported as `None`.
+fn format_info(doc: &serde_json::Value) -> FormatInfo {
+    let format_type = doc.get("type").and_then(|v| v.as_str()).map(str::to_string);
+    let version = doc.get("version").and_then(|v| v.as_u64());
+    let source = doc
+        .get("source")
+        .and_then(|v| v.as_str())
+        .map(str::to_string);
+
+    FormatInfo {
+        is_expected_type: format_type.as_deref() == Some(CANVAS_TYPE),
+        is_expected_version: version == Some(CANVAS_VERSION),
+        is_native_source: source.as_deref() == Some(CANVAS_SOURCE),
+        format_type,
+        version,
+        source,
+    }
+}
+
+#[tauri::command(async)]
+pub fn canvas_format_info(app: AppHandle, path: String) -> Result<FormatInfo, String> {
+    let path = normalize_relative_path(&path)?;
     let base = get_base_dir(&app)?;
-    let meta_dir = base.join(".meta");
-    
-    fs::create_dir_all(&meta_dir).map_err(|e| e.to_string())?;
-    
-    let meta_path = get_icon_meta_path(&base, &path);
-    let content = if let Some(c) = color {
-        format!("{}:{}", icon, c)
-    } else {
-        icon
+    let doc = read_canvas_json(&base.join(&path))?;
+    Ok(format_info(&doc))
+}
+
+const HISTORY_FILE: &str = ".history.jsonl";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HistoryEntry {
+    pub timestamp: u64,
+    pub action: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Append one entry to the `.history.jsonl` audit log in the base directory.
+fn append_history(
+    base: &Path,
+    action: &str,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<(), String> {
+    use std::io::Write;
+
+    let entry = HistoryEntry {
+        timestamp: now_millis(),
+        action: action.to_string(),
+        from: from.map(str::to_string),
+        to: to.map(str::to_string),
     };
-    fs::write(meta_path, content).map_err(|e| e.to_string())
+    let line = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(base.join(HISTORY_FILE))
+        .map_err(|e| e.to_string())?;
+    writeln!(file, "{}", line).map_err(|e| e.to_string())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::TempDir;
+/// Read up to `limit` history entries, most recent first. Malformed lines are skipped.
+fn read_history(base: &Path, limit: usize) -> Result<Vec<HistoryEntry>, String> {
+    let content = match fs::read_to_string(base.join(HISTORY_FILE)) {
+        Ok(c) => c,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.to_string()),
+    };
 
-    // ──────────────────────────────────────────────
-    // safe_relative_path のテスト
-    // ──────────────────────────────────────────────
+    Ok(content
+        .lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .take(limit)
+        .collect())
+}
 
-    #[test]
-    fn safe_relative_path_空文字は許可する() {
-        assert!(safe_relative_path("").is_ok());
-    }
+#[tauri::command(async)]
+pub fn get_history(app: AppHandle, limit: usize) -> Result<Vec<HistoryEntry>, String> {
+    let base = get_base_dir(&app)?;
+    read_history(&base, limit)
+}
 
-    #[test]
-    fn safe_relative_path_通常のファイル名は許可する() {
-        assert!(safe_relative_path("test.excalidraw").is_ok());
+/// Validate a single path segment as a portable file/folder name.
+fn validate_name(name: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("名前を入力してください".to_string());
     }
-
-    #[test]
-    fn safe_relative_path_ネストパスは許可する() {
-        assert!(safe_relative_path("folder/test.excalidraw").is_ok());
+    if name
+        .chars()
+        .any(|c| c.is_control() || matches!(c, '<' | '>' | ':' | '"' | '|' | '?' | '*'))
+    {
+        return Err("使用できない文字が含まれています".to_string());
     }
-
-    #[test]
-    fn safe_relative_path_深くネストされたパスは許可する() {
-        assert!(safe_relative_path("a/b/c/d/test.excalidraw").is_ok());
+    if name.ends_with('.') || name.ends_with(' ') {
+        return Err("名前の末尾にピリオドや空白は使用できません".to_string());
     }
 
-    #[test]
-    fn safe_relative_path_親ディレクトリ参照を拒否する() {
-        let err = safe_relative_path("../secret").unwrap_err();
-        assert!(err.contains("パストラバーサル"), "expected traversal error, got: {err}");
+    // Windows reserved device names, with or without an extension
+    let stem = name.split('.').next().unwrap_or(name).to_ascii_uppercase();
+    let reserved = matches!(stem.as_str(), "CON" | "PRN" | "AUX" | "NUL")
+        || ((stem.starts_with("COM") || stem.starts_with("LPT"))
+            && stem.len() == 4
+            && matches!(stem.as_bytes()[3], b'1'..=b'9'));
+    if reserved {
+        return Err("予約された名前は使用できません".to_string());
     }
+    Ok(())
+}
 
-    #[test]
-    fn safe_relative_path_中間の親ディレクトリ参照を拒否する() {
-        let err = safe_relative_path("folder/../etc/passwd").unwrap_err();
-        assert!(err.contains("パストラバーサル"), "expected traversal error, got: {err}");
+/// Full validation of a user-supplied relative path: traversal checks plus per-segment name rules.
+fn validate_relative_path(path: &str) -> Result<(), String> {
+    let path = normalize_relative_path(path)?;
+    if path.is_empty() {
+        return Err("名前を入力してください".to_string());
     }
-
-    #[test]
-    fn safe_relative_path_絶対パスを拒否する_ルートスラッシュ() {
-        // Unix: /etc/passwd
-        let result = safe_relative_path("/etc/passwd");
-        assert!(result.is_err(), "absolute path should be rejected");
+    for segment in path.split('/') {
+        validate_name(segment)?;
     }
+    Ok(())
+}
 
-    #[test]
-    fn safe_relative_path_複数の親ディレクトリ参照を拒否する() {
-        let err = safe_relative_path("../../etc/shadow").unwrap_err();
-        assert!(err.contains("パストラバーサル"), "expected traversal error, got: {err}");
-    }
+#[tauri::command]
+pub fn validate_path(path: String) -> Result<(), String> {
+    validate_relative_path(&path)
+}
 
-    // ──────────────────────────────────────────────
+const ORDER_FILE: &str = ".order.json";
+
+/// Load the manual order of a folder's direct children from its `.order.json`.
+/// Entries that no longer exist are dropped and new ones are appended in the default sort order.
+fn load_order(dir: &Path) -> Result<Vec<String>, String> {
+    // Direct children in the default sidebar order: folders first, then case-insensitive by name
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+        let is_folder = entry.file_type().map_err(|e| e.to_string())?.is_dir();
+        if is_folder || is_canvas_name(&name) {
+            entries.push((is_folder, name));
+        }
+    }
+    entries.sort_by(|a, b| {
+        b.0.cmp(&a.0)
+            .then_with(|| a.1.to_lowercase().cmp(&b.1.to_lowercase()))
+    });
+    let current: Vec<String> = entries.into_iter().map(|(_, name)| name).collect();
+
+    let mut order: Vec<String> = read_stored_order(dir)
+        .into_iter()
+        .filter(|name| current.contains(name))
+        .collect();
+    for name in current {
+        if !order.contains(&name) {
+            order.push(name);
+        }
+    }
+    Ok(order)
+}
+
+/// The folder's `.order.json` as written, without reconciling it against the folder.
+fn read_stored_order(dir: &Path) -> Vec<String> {
+    fs::read_to_string(dir.join(ORDER_FILE))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn write_order(dir: &Path, order: &[String]) -> Result<(), String> {
+    let json = serde_json::to_string(order).map_err(|e| e.to_string())?;
+    write_atomic(&dir.join(ORDER_FILE), &json)
+}
+
+/// Keep manual ordering across a move: renamed in place, an item keeps its slot; moved to
+/// another folder, it leaves the old folder's order and, like any new item, comes last in
+/// the new one's.
+fn move_order_entry(base: &Path, old_path: &str, new_path: &str) -> Result<(), String> {
+    let split = |path: &str| -> (String, String) {
+        match path.rsplit_once('/') {
+            Some((parent, name)) => (parent.to_string(), name.to_string()),
+            None => (String::new(), path.to_string()),
+        }
+    };
+    let (old_parent, old_name) = split(old_path);
+    let (new_parent, new_name) = split(new_path);
+
+    let old_dir = base.join(&old_parent);
+    let mut order = read_stored_order(&old_dir);
+    let Some(slot) = order.iter().position(|n| *n == old_name) else {
+        return Ok(());
+    };
+    if old_parent == new_parent {
+        order[slot] = new_name;
+    } else {
+        order.remove(slot);
+    }
+    write_order(&old_dir, &order)
+}
+
+fn reorder_entry(
+    base: &Path,
+    folder_path: &str,
+    name: &str,
+    direction: &str,
+) -> Result<Vec<String>, String> {
+    let dir = base.join(folder_path);
+    let mut order = load_order(&dir)?;
+
+    let index = order
+        .iter()
+        .position(|n| n == name)
+        .ok_or_else(|| format!("Item not found: {}", name))?;
+    let neighbor = match direction {
+        "up" if index > 0 => index - 1,
+        "down" if index + 1 < order.len() => index + 1,
+        "up" | "down" => return Ok(order),
+        _ => return Err(format!("Invalid direction: {}", direction)),
+    };
+    order.swap(index, neighbor);
+
+    write_order(&dir, &order)?;
+    Ok(order)
+}
+
+#[tauri::command(async)]
+pub fn reorder_item(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    folder_path: String,
+    name: String,
+    direction: String,
+) -> Result<Vec<String>, String> {
+    fs_ops.run(|| {
+        let folder_path = normalize_relative_path(&folder_path)?;
+        validate_name(&name)?;
+        let base = get_base_dir(&app)?;
+        reorder_entry(&base, &folder_path, &name, &direction)
+    })
+}
+
+/// Recursively add `dir` to the archive, with entry names relative to `root`.
+/// File contents are streamed so large vaults are never held in memory.
+fn add_dir_to_zip<W: std::io::Write + std::io::Seek>(
+    zip: &mut zip::ZipWriter<W>,
+    root: &Path,
+    dir: &Path,
+    include_hidden: bool,
+) -> Result<(), String> {
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+
+    let mut entries: Vec<_> = fs::read_dir(dir)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    entries.sort_by_key(|e| e.file_name());
+
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !include_hidden && name.starts_with('.') {
+            continue;
+        }
+
+        let entry_path = entry.path();
+        let relative = entry_path
+            .strip_prefix(root)
+            .map(|p| p.to_string_lossy().replace('\\', "/"))
+            .map_err(|e| e.to_string())?;
+
+        let file_type = entry.file_type().map_err(|e| e.to_string())?;
+        // Collection entries link back into the vault, so following them would only duplicate
+        // canvases (or pull in whatever a link outside the vault points at)
+        if file_type.is_symlink() {
+            continue;
+        }
+        if file_type.is_dir() {
+            zip.add_directory(relative.as_str(), options)
+                .map_err(|e| e.to_string())?;
+            add_dir_to_zip(zip, root, &entry_path, include_hidden)?;
+        } else {
+            zip.start_file(relative.as_str(), options)
+                .map_err(|e| e.to_string())?;
+            let mut file = fs::File::open(&entry_path).map_err(|e| e.to_string())?;
+            std::io::copy(&mut file, zip).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// Write the whole vault under `base` into a zip archive at `archive_path`.
+fn write_vault_archive(
+    base: &Path,
+    archive_path: &Path,
+    include_hidden: bool,
+) -> Result<(), String> {
+    let file = fs::File::create(archive_path).map_err(|e| e.to_string())?;
+    let mut zip = zip::ZipWriter::new(std::io::BufWriter::new(file));
+    add_dir_to_zip(&mut zip, base, base, include_hidden)?;
+    zip.finish().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command(async)]
+pub fn backup_vault(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    dest_dir_absolute: String,
+    include_hidden: bool,
+) -> Result<String, String> {
+    fs_ops.run(|| {
+        let base = get_base_dir(&app)?;
+        let dest_dir = PathBuf::from(&dest_dir_absolute);
+
+        if !dest_dir.is_absolute() {
+            return Err("Backup destination must be an absolute path".to_string());
+        }
+        if dest_dir.starts_with(&base) {
+            return Err("Backup destination must be outside the vault".to_string());
+        }
+
+        fs::create_dir_all(&dest_dir).map_err(|e| e.to_string())?;
+        let archive_path = dest_dir.join(format!("vault-{}.zip", now_millis()));
+        write_vault_archive(&base, &archive_path, include_hidden)?;
+
+        Ok(archive_path.to_string_lossy().to_string())
+    })
+}
+
+/// Extract a vault archive into `base`, returning the number of files written.
+/// Refuses a non-empty `base` unless `force` is set, and rejects the whole archive
+/// up front if any entry would land outside `base` (zip-slip).
+fn extract_vault_archive(base: &Path, archive_path: &Path, force: bool) -> Result<usize, String> {
+    fs::create_dir_all(base).map_err(|e| e.to_string())?;
+    let is_empty = fs::read_dir(base)
+        .map_err(|e| e.to_string())?
+        .next()
+        .is_none();
+    if !is_empty && !force {
+        return Err("Vault is not empty; use force to restore anyway".to_string());
+    }
+
+    let file = fs::File::open(archive_path).map_err(|e| e.to_string())?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| e.to_string())?;
+
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i).map_err(|e| e.to_string())?;
+        if entry.enclosed_name().is_none() {
+            return Err(format!("Unsafe entry in archive: {}", entry.name()));
+        }
+    }
+
+    let mut count = 0;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).map_err(|e| e.to_string())?;
+        let relative = entry
+            .enclosed_name()
+            .ok_or_else(|| format!("Unsafe entry in archive: {}", entry.name()))?;
+        let dest = base.join(relative);
+
+        if entry.is_dir() {
+            fs::create_dir_all(&dest).map_err(|e| e.to_string())?;
+            continue;
+        }
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let mut out = fs::File::create(&dest).map_err(|e| e.to_string())?;
+        std::io::copy(&mut entry, &mut out).map_err(|e| e.to_string())?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[tauri::command(async)]
+pub fn restore_vault(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    archive_absolute_path: String,
+    force: bool,
+) -> Result<usize, String> {
+    fs_ops.run(|| {
+        let archive_path = PathBuf::from(&archive_absolute_path);
+        if !archive_path.is_absolute() {
+            return Err("Archive path must be an absolute path".to_string());
+        }
+        let base = get_base_dir(&app)?;
+        extract_vault_archive(&base, &archive_path, force)
+    })
+}
+
+/// Iterate over the elements of a parsed canvas that are not marked `isDeleted`.
+fn live_elements(doc: &serde_json::Value) -> impl Iterator<Item = &serde_json::Value> {
+    doc.get("elements")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter(|el| {
+            !el.get("isDeleted")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false)
+        })
+}
+
+fn count_live_elements(doc: &serde_json::Value) -> u64 {
+    live_elements(doc).count() as u64
+}
+
+/// Element counts for the given canvases; canvases that cannot be read or parsed count as 0.
+fn element_counts_in(base: &Path, paths: &[String]) -> Result<Vec<(String, u64)>, String> {
+    let mut counts = Vec::with_capacity(paths.len());
+    for path in paths {
+        let normalized = normalize_relative_path(path)?;
+        let count = read_canvas_json(&base.join(normalized))
+            .map(|doc| count_live_elements(&doc))
+            .unwrap_or(0);
+        counts.push((path.clone(), count));
+    }
+    Ok(counts)
+}
+
+#[tauri::command(async)]
+pub fn element_counts(app: AppHandle, paths: Vec<String>) -> Result<Vec<(String, u64)>, String> {
+    let base = get_base_dir(&app)?;
+    element_counts_in(&base, &paths)
+}
+
+/// Split a file name into stem and extension (`"a.excalidraw"` → `("a", ".excalidraw")`).
+/// A compressed canvas keeps its whole `.excalidraw.gz` suffix as the extension.
+fn split_name(name: &str) -> (&str, &str) {
+    if let Some(stem) = name.strip_suffix(COMPRESSED_CANVAS_SUFFIX) {
+        if !stem.is_empty() {
+            return (stem, COMPRESSED_CANVAS_SUFFIX);
+        }
+    }
+    match name.rfind('.') {
+        Some(i) if i > 0 => (&name[..i], &name[i..]),
+        _ => (name, ""),
+    }
+}
+
+/// First free path among `dir/<name>`, `dir/<stem> 2<ext>`, `dir/<stem> 3<ext>`, ...
+fn unique_child_path(dir: &Path, name: &str) -> PathBuf {
+    let candidate = dir.join(name);
+    if !candidate.exists() {
+        return candidate;
+    }
+    let (stem, ext) = split_name(name);
+    (2..)
+        .map(|n| dir.join(format!("{} {}{}", stem, n, ext)))
+        .find(|p| !p.exists())
+        .expect("unbounded range always yields a free name")
+}
+
+fn relative_to(base: &Path, path: &Path) -> String {
+    path.strip_prefix(base)
+        .map(|p| p.to_string_lossy().replace('\\', "/"))
+        .unwrap_or_default()
+}
+
+/// `YYYY/MM` folder for a modification time (UTC).
+fn dated_folder(modified: SystemTime) -> String {
+    let secs = modified
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let date =
+        time::OffsetDateTime::from_unix_timestamp(secs).unwrap_or(time::OffsetDateTime::UNIX_EPOCH);
+    format!("{:04}/{:02}", date.year(), u8::from(date.month()))
+}
+
+/// Plan (and unless `dry_run`, perform) moving loose canvases in `subpath` into `YYYY/MM` folders.
+fn organize_by_date_in(
+    base: &Path,
+    subpath: &str,
+    dry_run: bool,
+) -> Result<Vec<(String, String)>, String> {
+    let dir = base.join(subpath);
+    let mut names: Vec<(String, SystemTime)> = Vec::new();
+    for entry in fs::read_dir(&dir).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let metadata = entry.metadata().map_err(|e| e.to_string())?;
+        if name.starts_with('.') || !metadata.is_file() || !is_canvas_name(&name) {
+            continue;
+        }
+        let modified = metadata.modified().map_err(|e| e.to_string())?;
+        names.push((name, modified));
+    }
+    names.sort();
+
+    let mut moves = Vec::new();
+    for (name, modified) in names {
+        let target_dir = dir.join(dated_folder(modified));
+        let target = unique_child_path(&target_dir, &name);
+        let from = relative_to(base, &dir.join(&name));
+        let to = relative_to(base, &target);
+
+        if !dry_run {
+            rename_entry(base, &from, &to)?;
+        }
+        moves.push((from, to));
+    }
+    Ok(moves)
+}
+
+#[tauri::command(async)]
+pub fn organize_by_date(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    subpath: String,
+    dry_run: bool,
+) -> Result<Vec<(String, String)>, String> {
+    fs_ops.run(|| {
+        let subpath = normalize_relative_path(&subpath)?;
+        let base = get_base_dir(&app)?;
+        organize_by_date_in(&base, &subpath, dry_run)
+    })
+}
+
+/// Relative paths of every canvas under `dir`, plain or compressed, skipping hidden entries, sorted.
+fn walk_canvases(base: &Path, dir: &Path) -> Result<Vec<String>, String> {
+    let mut paths = walk_files_with_suffix(base, dir, ".excalidraw")?;
+    paths.extend(walk_files_with_suffix(base, dir, COMPRESSED_CANVAS_SUFFIX)?);
+    paths.sort();
+    Ok(paths)
+}
+
+/// Relative paths of every file under `dir` whose name ends with `suffix`, skipping hidden entries, sorted.
+fn walk_files_with_suffix(base: &Path, dir: &Path, suffix: &str) -> Result<Vec<String>, String> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+
+        let entry_path = entry.path();
+        if entry.file_type().map_err(|e| e.to_string())?.is_dir() {
+            paths.extend(walk_files_with_suffix(base, &entry_path, suffix)?);
+        } else if name.ends_with(suffix) {
+            paths.push(relative_to(base, &entry_path));
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+fn find_canvases_with_element_type_in(
+    base: &Path,
+    element_type: &str,
+) -> Result<Vec<String>, String> {
+    let mut matches = Vec::new();
+    for path in walk_canvases(base, base)? {
+        let Ok(doc) = read_canvas_json(&base.join(&path)) else {
+            continue;
+        };
+        if live_elements(&doc)
+            .any(|el| el.get("type").and_then(|v| v.as_str()) == Some(element_type))
+        {
+            matches.push(path);
+        }
+    }
+    Ok(matches)
+}
+
+#[tauri::command(async)]
+pub fn find_canvases_with_element_type(
+    app: AppHandle,
+    element_type: String,
+) -> Result<Vec<String>, String> {
+    let base = get_base_dir(&app)?;
+    find_canvases_with_element_type_in(&base, &element_type)
+}
+
+/// Point trashed snapshots of `old_path` (or of anything under it, for folders) at `new_path`,
+/// so restoring them follows the rename. Returns the number of metas rewritten.
+fn retarget_trash_metas(trash: &Path, old_path: &str, new_path: &str) -> Result<usize, String> {
+    if !trash.exists() {
+        return Ok(0);
+    }
+
+    let folder_prefix = format!("{}/", old_path);
+    let retarget = |meta: &mut TrashMeta| {
+        if meta.original_path == old_path {
+            meta.original_path = new_path.to_string();
+        } else if let Some(rest) = meta.original_path.strip_prefix(&folder_prefix) {
+            meta.original_path = format!("{}/{}", new_path, rest);
+        } else {
+            return false;
+        }
+        true
+    };
+
+    let mut updated = 0;
+    let mut index = read_trash_index(trash);
+    for meta in index.values_mut() {
+        if retarget(meta) {
+            updated += 1;
+        }
+    }
+    if updated > 0 {
+        write_trash_index(trash, &index)?;
+    }
+
+    for entry in fs::read_dir(trash).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let meta_path = entry.path();
+        if !entry.file_name().to_string_lossy().ends_with(".meta") {
+            continue;
+        }
+        let Ok(meta_json) = fs::read_to_string(&meta_path) else {
+            continue;
+        };
+        let Ok(mut meta) = serde_json::from_str::<TrashMeta>(&meta_json) else {
+            continue;
+        };
+        if !retarget(&mut meta) {
+            continue;
+        }
+
+        let meta_json = serde_json::to_string(&meta).map_err(|e| e.to_string())?;
+        fs::write(&meta_path, meta_json).map_err(|e| e.to_string())?;
+        updated += 1;
+    }
+
+    for (_, dir) in trash_namespaces(trash, &index)? {
+        updated += retarget_trash_metas(&dir, old_path, new_path)?;
+    }
+    Ok(updated)
+}
+
+/// Fold legacy per-item `.meta` sidecars into the trash index, returning how many were migrated.
+fn migrate_trash_index_in(trash: &Path) -> Result<usize, String> {
+    if !trash.exists() {
+        return Ok(0);
+    }
+
+    let mut index = read_trash_index(trash);
+    let mut legacy = Vec::new();
+    for entry in fs::read_dir(trash).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Some(trash_name) = name.strip_suffix(".meta") else {
+            continue;
+        };
+        let Ok(meta_json) = fs::read_to_string(entry.path()) else {
+            continue;
+        };
+        let Ok(meta) = serde_json::from_str::<TrashMeta>(&meta_json) else {
+            continue;
+        };
+        index.entry(trash_name.to_string()).or_insert(meta);
+        legacy.push(entry.path());
+    }
+
+    if legacy.is_empty() {
+        return Ok(0);
+    }
+    // Persist the index before dropping the sidecars so a crash never loses metadata
+    write_trash_index(trash, &index)?;
+    for meta_path in &legacy {
+        fs::remove_file(meta_path).map_err(|e| e.to_string())?;
+    }
+    Ok(legacy.len())
+}
+
+#[tauri::command(async)]
+pub fn migrate_trash_index(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+) -> Result<usize, String> {
+    fs_ops.run(|| {
+        let trash = get_trash_dir(&app)?;
+        migrate_trash_index_in(&trash)
+    })
+}
+
+#[tauri::command(async)]
+pub fn list_canvas_snapshots(app: AppHandle, path: String) -> Result<Vec<TrashItem>, String> {
+    let path = normalize_relative_path(&path)?;
+    let trash = get_trash_dir(&app)?;
+    Ok(read_trash_items(&trash)?
+        .into_iter()
+        .filter(|item| item.original_path == path)
+        .collect())
+}
+
+/// 64-bit FNV-1a. Used instead of `DefaultHasher`, whose output may change between Rust releases.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn hsl_to_hex(hue: f64, saturation: f64, lightness: f64) -> String {
+    let c = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let h = hue / 60.0;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = lightness - c / 2.0;
+    let to_byte = |v: f64| ((v + m) * 255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", to_byte(r), to_byte(g), to_byte(b))
+}
+
+/// Deterministic pastel-ish color for a folder path, stable across runs and platforms.
+#[tauri::command]
+pub fn folder_color(folder_path: String) -> String {
+    let hash = fnv1a(folder_path.as_bytes());
+    let hue = (hash % 360) as f64;
+    let saturation = 0.55 + ((hash >> 16) % 20) as f64 / 100.0;
+    let lightness = 0.5 + ((hash >> 32) % 15) as f64 / 100.0;
+    hsl_to_hex(hue, saturation, lightness)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", content = "value", rename_all = "camelCase")]
+pub enum TrashFilter {
+    EmptyCanvases,
+    OlderThanDays(u32),
+}
+
+fn matches_trash_filter(full_path: &Path, filter: &TrashFilter, now: SystemTime) -> bool {
+    match filter {
+        TrashFilter::EmptyCanvases => read_canvas_json(full_path)
+            .map(|doc| count_live_elements(&doc) == 0)
+            .unwrap_or(false),
+        TrashFilter::OlderThanDays(days) => {
+            // A cutoff before the earliest representable time matches nothing
+            let Some(cutoff) = now.checked_sub(std::time::Duration::from_secs(
+                u64::from(*days) * 24 * 60 * 60,
+            )) else {
+                return false;
+            };
+            fs::metadata(full_path)
+                .and_then(|m| m.modified())
+                .map(|modified| modified < cutoff)
+                .unwrap_or(false)
+        }
+    }
+}
+
+/// Trash every canvas in the vault matching `filter`, returning the trashed paths.
+/// Like `trash_item`, each goes to its top-level folder's sub-trash when `namespaced`.
+fn trash_matching_in(
+    base: &Path,
+    trash: &Path,
+    filter: &TrashFilter,
+    now: SystemTime,
+    namespaced: bool,
+) -> Result<Vec<String>, String> {
+    let mut trashed = Vec::new();
+    for path in walk_canvases(base, base)? {
+        if matches_trash_filter(&base.join(&path), filter, now) {
+            move_to_trash(base, &trash_for(trash, &path, namespaced), &path)?;
+            trashed.push(path);
+        }
+    }
+    Ok(trashed)
+}
+
+#[tauri::command(async)]
+pub fn trash_matching(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    predicate: TrashFilter,
+) -> Result<Vec<String>, String> {
+    fs_ops.run(|| {
+        let base = get_base_dir(&app)?;
+        let trash = get_trash_dir(&app)?;
+        let settings = load_settings(&get_app_data_dir(&app)?);
+        trash_matching_in(
+            &base,
+            &trash,
+            &predicate,
+            SystemTime::now(),
+            settings.namespaced_trash,
+        )
+    })
+}
+
+/// A full canvas document with the default `appState`, as written by `create_canvas`.
+fn canvas_document(
+    elements: Vec<serde_json::Value>,
+    files: serde_json::Value,
+) -> serde_json::Value {
+    serde_json::json!({
+        "type": CANVAS_TYPE,
+        "version": CANVAS_VERSION,
+        "source": CANVAS_SOURCE,
+        "elements": elements,
+        "appState": { "gridSize": null, "viewBackgroundColor": "#ffffff" },
+        "files": files,
+    })
+}
+
+/// Common Excalidraw element properties shared by every generated element.
+fn base_element(
+    id: &str,
+    element_type: &str,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+) -> serde_json::Value {
+    let seed = (fnv1a(id.as_bytes()) % i32::MAX as u64) as i64;
+    serde_json::json!({
+        "id": id,
+        "type": element_type,
+        "x": x,
+        "y": y,
+        "width": width,
+        "height": height,
+        "angle": 0,
+        "strokeColor": "#1e1e1e",
+        "backgroundColor": "transparent",
+        "fillStyle": "solid",
+        "strokeWidth": 2,
+        "strokeStyle": "solid",
+        "roughness": 1,
+        "opacity": 100,
+        "groupIds": [],
+        "frameId": null,
+        "roundness": null,
+        "seed": seed,
+        "version": 1,
+        "versionNonce": seed,
+        "isDeleted": false,
+        "boundElements": null,
+        "updated": now_millis(),
+        "link": null,
+        "locked": false,
+    })
+}
+
+fn text_element(id: &str, x: f64, y: f64, text: &str) -> serde_json::Value {
+    const FONT_SIZE: f64 = 20.0;
+    let longest_line = text.lines().map(|l| l.chars().count()).max().unwrap_or(0);
+    let line_count = text.lines().count().max(1);
+
+    let mut element = base_element(
+        id,
+        "text",
+        x,
+        y,
+        longest_line as f64 * FONT_SIZE * 0.6,
+        line_count as f64 * FONT_SIZE * 1.25,
+    );
+    let fields = element.as_object_mut().expect("base_element is an object");
+    fields.insert("text".into(), text.into());
+    fields.insert("originalText".into(), text.into());
+    fields.insert("fontSize".into(), FONT_SIZE.into());
+    fields.insert("fontFamily".into(), 1.into());
+    fields.insert("textAlign".into(), "left".into());
+    fields.insert("verticalAlign".into(), "top".into());
+    fields.insert("containerId".into(), serde_json::Value::Null);
+    fields.insert("lineHeight".into(), 1.25.into());
+    element
+}
+
+fn canvas_stem(path: &str) -> &str {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let name = name.strip_suffix(".gz").unwrap_or(name);
+    name.strip_suffix(".excalidraw").unwrap_or(name)
+}
+
+/// Build an overview canvas with one text label per canvas in the vault, laid out in a grid.
+fn build_index_canvas(base: &Path, dest_path: &str) -> Result<serde_json::Value, String> {
+    const CELL_WIDTH: f64 = 320.0;
+    const CELL_HEIGHT: f64 = 80.0;
+
+    let paths: Vec<String> = walk_canvases(base, base)?
+        .into_iter()
+        .filter(|p| p != dest_path)
+        .collect();
+    let columns = (paths.len() as f64).sqrt().ceil().max(1.0) as usize;
+
+    let elements = paths
+        .iter()
+        .enumerate()
+        .map(|(i, path)| {
+            let x = (i % columns) as f64 * CELL_WIDTH;
+            let y = (i / columns) as f64 * CELL_HEIGHT;
+            text_element(&format!("index-{}", i), x, y, canvas_stem(path))
+        })
+        .collect();
+    Ok(canvas_document(elements, serde_json::json!({})))
+}
+
+/// Write the index canvas at `dest_path`, or at the next free numbered name when that is taken,
+/// returning the path written.
+fn generate_index_canvas_in(base: &Path, dest_path: &str) -> Result<String, String> {
+    if is_vault_root(dest_path) {
+        return Err("保存先のファイル名を指定してください".to_string());
+    }
+    let requested = base.join(dest_path);
+    let parent = requested.parent().ok_or("Invalid path")?;
+    let name = requested
+        .file_name()
+        .ok_or("Invalid path")?
+        .to_string_lossy()
+        .to_string();
+    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+
+    let full_path = unique_child_path(parent, &name);
+    let written = relative_to(base, &full_path);
+    let doc = build_index_canvas(base, &written)?;
+    write_canvas_json(&full_path, &doc)?;
+    Ok(written)
+}
+
+#[tauri::command(async)]
+pub fn generate_index_canvas(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    dest_path: String,
+) -> Result<String, String> {
+    fs_ops.run(|| {
+        let dest_path = normalize_relative_path(&dest_path)?;
+        let base = get_base_dir(&app)?;
+        generate_index_canvas_in(&base, &dest_path)
+    })
+}
+
+/// Write `content` to a hidden sibling temp file and rename it over `path`,
+/// so readers never observe a partially written canvas.
+fn write_atomic(path: &Path, content: impl AsRef<[u8]>) -> Result<(), String> {
+    let file_name = path
+        .file_name()
+        .ok_or("Invalid path")?
+        .to_string_lossy()
+        .to_string();
+    let tmp_path = path.with_file_name(format!(".{}.tmp", file_name));
+
+    fs::write(&tmp_path, content).map_err(|e| e.to_string())?;
+    fs::rename(&tmp_path, path).map_err(|e| {
+        let _ = fs::remove_file(&tmp_path);
+        e.to_string()
+    })
+}
+
+fn write_canvas_json(path: &Path, doc: &serde_json::Value) -> Result<(), String> {
+    let content = serde_json::to_string(doc).map_err(|e| e.to_string())?;
+    write_canvas_bytes(path, content.as_bytes())
+}
+
+/// Insert an empty `files` object into every canvas that lacks one. Returns the number fixed.
+fn fix_missing_files_key_in(base: &Path) -> Result<usize, String> {
+    let mut fixed = 0;
+    for path in walk_canvases(base, base)? {
+        let full_path = base.join(&path);
+        let Ok(mut doc) = read_canvas_json(&full_path) else {
+            continue;
+        };
+        let Some(fields) = doc.as_object_mut() else {
+            continue;
+        };
+        if fields.get("files").is_some_and(|f| f.is_object()) {
+            continue;
+        }
+
+        fields.insert("files".into(), serde_json::json!({}));
+        write_canvas_json(&full_path, &doc)?;
+        fixed += 1;
+    }
+    Ok(fixed)
+}
+
+#[tauri::command(async)]
+pub fn fix_missing_files_key(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+) -> Result<usize, String> {
+    fs_ops.run(|| {
+        let base = get_base_dir(&app)?;
+        fix_missing_files_key_in(&base)
+    })
+}
+
+/// Build a flat `FileItem` for a single canvas, as `collect_items` would list it. `opened` is
+/// the vault's `.opened.json`, loaded once by the caller for the whole listing.
+fn canvas_file_item(base: &Path, path: &str, opened: &OpenIndex) -> Result<FileItem, String> {
+    let metadata = fs::metadata(base.join(path)).map_err(|e| e.to_string())?;
+    let (icon, icon_color) = load_item_icon(base, path);
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs());
+
+    Ok(FileItem {
+        name: path.rsplit('/').next().unwrap_or(path).to_string(),
+        path: path.to_string(),
+        is_folder: false,
+        children: None,
+        icon,
+        icon_color,
+        modified,
+        size: Some(metadata.len()),
+        opened_at: opened.get(path).copied(),
+        element_count: canvas_element_count(&base.join(path)),
+    })
+}
+
+/// Group every canvas by its first path segment; canvases at the root go under `""`.
+fn group_by_root_folder_in(base: &Path) -> Result<Vec<(String, Vec<FileItem>)>, String> {
+    let mut groups: std::collections::BTreeMap<String, Vec<FileItem>> = Default::default();
+    let opened = load_opened(base);
+    for path in walk_canvases(base, base)? {
+        let key = match path.split_once('/') {
+            Some((root, _)) => root.to_string(),
+            None => String::new(),
+        };
+        groups
+            .entry(key)
+            .or_default()
+            .push(canvas_file_item(base, &path, &opened)?);
+    }
+    Ok(groups.into_iter().collect())
+}
+
+#[tauri::command(async)]
+pub fn group_by_root_folder(app: AppHandle) -> Result<Vec<(String, Vec<FileItem>)>, String> {
+    let base = get_base_dir(&app)?;
+    group_by_root_folder_in(&base)
+}
+
+/// Rewrite every zero-byte canvas with the default template, returning the repaired paths.
+fn repair_zero_byte_canvases_in(base: &Path) -> Result<Vec<String>, String> {
+    let mut repaired = Vec::new();
+    for path in walk_canvases(base, base)? {
+        let full_path = base.join(&path);
+        let metadata = fs::metadata(&full_path).map_err(|e| e.to_string())?;
+        if metadata.len() == 0 {
+            write_canvas_bytes(&full_path, DEFAULT_CANVAS_CONTENT.as_bytes())?;
+            repaired.push(path);
+        }
+    }
+    Ok(repaired)
+}
+
+#[tauri::command(async)]
+pub fn repair_zero_byte_canvases(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+) -> Result<Vec<String>, String> {
+    fs_ops.run(|| {
+        let base = get_base_dir(&app)?;
+        repair_zero_byte_canvases_in(&base)
+    })
+}
+
+const RECENT_FILE: &str = ".recent.json";
+
+/// Paths of opened canvases, most recently opened first.
+fn load_recent(base: &Path) -> Vec<String> {
+    fs::read_to_string(base.join(RECENT_FILE))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn save_recent(base: &Path, recent: &[String]) -> Result<(), String> {
+    let json = serde_json::to_string(recent).map_err(|e| e.to_string())?;
+    write_atomic(&base.join(RECENT_FILE), &json)
+}
+
+fn record_open_in(base: &Path, path: &str) -> Result<(), String> {
+    let mut recent = load_recent(base);
+    recent.retain(|p| p != path);
+    recent.insert(0, path.to_string());
+    save_recent(base, &recent)?;
+
+    let mut counts = load_open_counts(base);
+    *counts.entry(path.to_string()).or_default() += 1;
+    save_open_index(base, OPEN_COUNTS_FILE, &counts)
+}
+
+#[tauri::command(async)]
+pub fn record_open(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    path: String,
+) -> Result<(), String> {
+    fs_ops.run(|| {
+        let path = normalize_relative_path(&path)?;
+        let base = get_base_dir(&app)?;
+        record_open_in(&base, &path)
+    })
+}
+
+fn find_never_opened_in(base: &Path) -> Result<Vec<String>, String> {
+    let opened: std::collections::HashSet<String> = load_recent(base).into_iter().collect();
+    Ok(walk_canvases(base, base)?
+        .into_iter()
+        .filter(|p| !opened.contains(p))
+        .collect())
+}
+
+#[tauri::command(async)]
+pub fn find_never_opened(app: AppHandle) -> Result<Vec<String>, String> {
+    let base = get_base_dir(&app)?;
+    find_never_opened_in(&base)
+}
+
+/// Canonicalize, deduplicate and drop missing entries from the recent list.
+/// Returns the pruned list and whether it differs from what was stored.
+fn prune_recent(base: &Path, recent: &[String]) -> (Vec<String>, bool) {
+    let mut pruned: Vec<String> = Vec::with_capacity(recent.len());
+    for path in recent {
+        let canonical = path.replace('\\', "/");
+        let canonical = canonical.trim_start_matches("./").trim_end_matches('/');
+        if safe_relative_path(canonical).is_err()
+            || canonical.is_empty()
+            || !base.join(canonical).is_file()
+            || pruned.iter().any(|p| p == canonical)
+        {
+            continue;
+        }
+        pruned.push(canonical.to_string());
+    }
+    let changed = pruned.as_slice() != recent;
+    (pruned, changed)
+}
+
+fn list_recent_in(base: &Path) -> Result<Vec<String>, String> {
+    let (pruned, changed) = prune_recent(base, &load_recent(base));
+    if changed {
+        save_recent(base, &pruned)?;
+    }
+    Ok(pruned)
+}
+
+#[tauri::command(async)]
+pub fn list_recent(app: AppHandle) -> Result<Vec<String>, String> {
+    let base = get_base_dir(&app)?;
+    list_recent_in(&base)
+}
+
+/// Outcome of one item in a batch operation. `error` is set on failure;
+/// `new_path` is set when the operation moved the item.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BatchResult {
+    pub path: String,
+    #[serde(rename = "newPath")]
+    pub new_path: Option<String>,
+    pub error: Option<String>,
+}
+
+impl BatchResult {
+    fn from_result(path: &str, result: Result<Option<String>, String>) -> Self {
+        match result {
+            Ok(new_path) => BatchResult {
+                path: path.to_string(),
+                new_path,
+                error: None,
+            },
+            Err(e) => BatchResult {
+                path: path.to_string(),
+                new_path: None,
+                error: Some(e),
+            },
+        }
+    }
+}
+
+fn affix_name(base: &Path, path: &str, prefix: &str, suffix: &str) -> Result<String, String> {
+    let path = normalize_relative_path(path)?;
+    let full_path = base.join(&path);
+    if !full_path.is_file() {
+        return Err(format!("Canvas not found: {}", path));
+    }
+
+    let name = full_path
+        .file_name()
+        .ok_or("Invalid path")?
+        .to_string_lossy()
+        .to_string();
+    let (stem, ext) = split_name(&name);
+    let new_name = format!("{}{}{}{}", prefix, stem, suffix, ext);
+    validate_name(&new_name)?;
+
+    let parent = full_path.parent().ok_or("Invalid path")?;
+    let target = unique_child_path(parent, &new_name);
+    let new_path = relative_to(base, &target);
+    rename_entry(base, &path, &new_path)?;
+    Ok(new_path)
+}
+
+/// Rename each canvas to `<prefix><stem><suffix><ext>`, numbering on collision.
+fn affix_names_in(base: &Path, paths: &[String], prefix: &str, suffix: &str) -> Vec<BatchResult> {
+    paths
+        .iter()
+        .map(|path| {
+            BatchResult::from_result(path, affix_name(base, path, prefix, suffix).map(Some))
+        })
+        .collect()
+}
+
+#[tauri::command(async)]
+pub fn affix_names(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    paths: Vec<String>,
+    prefix: String,
+    suffix: String,
+) -> Result<Vec<BatchResult>, String> {
+    fs_ops.run(|| {
+        let base = get_base_dir(&app)?;
+        Ok(affix_names_in(&base, &paths, &prefix, &suffix))
+    })
+}
+
+/// Distinct stroke/background colors of a canvas, most frequently used first.
+fn canvas_palette_of(doc: &serde_json::Value) -> Vec<String> {
+    let mut counts: std::collections::HashMap<&str, usize> = std::collections::HashMap::new();
+    for element in live_elements(doc) {
+        for key in ["strokeColor", "backgroundColor"] {
+            if let Some(color) = element.get(key).and_then(|v| v.as_str()) {
+                if color != "transparent" && !color.is_empty() {
+                    *counts.entry(color).or_default() += 1;
+                }
+            }
+        }
+    }
+
+    let mut palette: Vec<(&str, usize)> = counts.into_iter().collect();
+    palette.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    palette.into_iter().map(|(c, _)| c.to_string()).collect()
+}
+
+#[tauri::command(async)]
+pub fn canvas_palette(app: AppHandle, path: String) -> Result<Vec<String>, String> {
+    let path = normalize_relative_path(&path)?;
+    let base = get_base_dir(&app)?;
+    let doc = read_canvas_json(&base.join(&path))?;
+    Ok(canvas_palette_of(&doc))
+}
+
+/// `fileId`s referenced by live elements but missing from the canvas's `files` map.
+fn broken_file_refs(doc: &serde_json::Value) -> Vec<String> {
+    let files = doc.get("files").and_then(|f| f.as_object());
+    let mut missing: Vec<String> = live_elements(doc)
+        .filter_map(|el| el.get("fileId").and_then(|v| v.as_str()))
+        .filter(|id| !files.is_some_and(|f| f.contains_key(*id)))
+        .map(str::to_string)
+        .collect();
+    missing.sort();
+    missing.dedup();
+    missing
+}
+
+fn find_broken_image_refs_in(base: &Path) -> Result<Vec<(String, Vec<String>)>, String> {
+    let mut broken = Vec::new();
+    for path in walk_canvases(base, base)? {
+        let Ok(doc) = read_canvas_json(&base.join(&path)) else {
+            continue;
+        };
+        let missing = broken_file_refs(&doc);
+        if !missing.is_empty() {
+            broken.push((path, missing));
+        }
+    }
+    Ok(broken)
+}
+
+#[tauri::command(async)]
+pub fn find_broken_image_refs(app: AppHandle) -> Result<Vec<(String, Vec<String>)>, String> {
+    let base = get_base_dir(&app)?;
+    find_broken_image_refs_in(&base)
+}
+
+/// Copy the given `files` entries from `donor` into `target`. Entries the target already
+/// has are left untouched. Returns the number of entries added.
+fn import_files_between(target: &Path, donor: &Path, file_ids: &[String]) -> Result<usize, String> {
+    let donor_doc = read_canvas_json(donor)?;
+    let donor_files = donor_doc
+        .get("files")
+        .and_then(|f| f.as_object())
+        .ok_or("Donor canvas has no files")?;
+
+    let missing: Vec<&str> = file_ids
+        .iter()
+        .filter(|id| !donor_files.contains_key(id.as_str()))
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("Files not found in donor: {}", missing.join(", ")));
+    }
+
+    let mut target_doc = read_canvas_json(target)?;
+    let fields = target_doc.as_object_mut().ok_or("Invalid canvas")?;
+    let target_files = fields
+        .entry("files")
+        .or_insert_with(|| serde_json::json!({}));
+    if !target_files.is_object() {
+        *target_files = serde_json::json!({});
+    }
+    let target_files = target_files
+        .as_object_mut()
+        .expect("files was just made an object");
+
+    let mut added = 0;
+    for id in file_ids {
+        if !target_files.contains_key(id) {
+            target_files.insert(id.clone(), donor_files[id].clone());
+            added += 1;
+        }
+    }
+
+    if added > 0 {
+        write_canvas_json(target, &target_doc)?;
+    }
+    Ok(added)
+}
+
+#[tauri::command(async)]
+pub fn import_files_from(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    target_path: String,
+    donor_path: String,
+    file_ids: Vec<String>,
+) -> Result<usize, String> {
+    fs_ops.run(|| {
+        let target_path = normalize_relative_path(&target_path)?;
+        let donor_path = normalize_relative_path(&donor_path)?;
+        let base = get_base_dir(&app)?;
+        import_files_between(&base.join(&target_path), &base.join(&donor_path), &file_ids)
+    })
+}
+
+/// Whether `dir` contains a visible `.excalidraw` file at any depth; stops at the first hit.
+fn contains_canvas(dir: &Path) -> Result<bool, String> {
+    let mut subdirs = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+        if entry.file_type().map_err(|e| e.to_string())?.is_dir() {
+            subdirs.push(entry.path());
+        } else if is_canvas_name(&name) {
+            return Ok(true);
+        }
+    }
+
+    for subdir in subdirs {
+        if contains_canvas(&subdir)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+#[tauri::command(async)]
+pub fn folder_has_canvases(app: AppHandle, folder_path: String) -> Result<bool, String> {
+    let folder_path = normalize_relative_path(&folder_path)?;
+    let base = get_base_dir(&app)?;
+    contains_canvas(&base.join(&folder_path))
+}
+
+const GRID_SIZE_RANGE: std::ops::RangeInclusive<u32> = 1..=100;
+
+fn set_grid_size_at(full_path: &Path, grid_size: Option<u32>) -> Result<(), String> {
+    if let Some(size) = grid_size {
+        if !GRID_SIZE_RANGE.contains(&size) {
+            return Err(format!(
+                "Grid size must be between {} and {}",
+                GRID_SIZE_RANGE.start(),
+                GRID_SIZE_RANGE.end()
+            ));
+        }
+    }
+
+    let mut doc = read_canvas_json(full_path)?;
+    let fields = doc.as_object_mut().ok_or("Invalid canvas")?;
+    let app_state = fields
+        .entry("appState")
+        .or_insert_with(|| serde_json::json!({}));
+    if !app_state.is_object() {
+        *app_state = serde_json::json!({});
+    }
+    app_state["gridSize"] = grid_size.into();
+
+    write_canvas_json(full_path, &doc)
+}
+
+#[tauri::command(async)]
+pub fn set_grid_size(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    path: String,
+    grid_size: Option<u32>,
+) -> Result<(), String> {
+    fs_ops.run(|| {
+        let path = normalize_relative_path(&path)?;
+        let base = get_base_dir(&app)?;
+        set_grid_size_at(&base.join(&path), grid_size)
+    })
+}
+
+const CLIPBOARD_TYPE: &str = "excalidraw/clipboard";
+
+/// Excalidraw clipboard payload with the live elements and the files they reference.
+fn clipboard_payload(doc: &serde_json::Value) -> serde_json::Value {
+    let elements: Vec<serde_json::Value> = live_elements(doc).cloned().collect();
+    let referenced: std::collections::HashSet<&str> = elements
+        .iter()
+        .filter_map(|el| el.get("fileId").and_then(|v| v.as_str()))
+        .collect();
+    let files: serde_json::Map<String, serde_json::Value> = doc
+        .get("files")
+        .and_then(|f| f.as_object())
+        .into_iter()
+        .flatten()
+        .filter(|(id, _)| referenced.contains(id.as_str()))
+        .map(|(id, file)| (id.clone(), file.clone()))
+        .collect();
+
+    serde_json::json!({
+        "type": CLIPBOARD_TYPE,
+        "elements": elements,
+        "files": files,
+    })
+}
+
+#[tauri::command(async)]
+pub fn export_clipboard(app: AppHandle, path: String) -> Result<String, String> {
+    let path = normalize_relative_path(&path)?;
+    let base = get_base_dir(&app)?;
+    let doc = read_canvas_json(&base.join(&path))?;
+    serde_json::to_string(&clipboard_payload(&doc)).map_err(|e| e.to_string())
+}
+
+/// Wrap an Excalidraw clipboard payload into a full canvas document.
+fn canvas_from_clipboard(clipboard_json: &str) -> Result<serde_json::Value, String> {
+    let payload: serde_json::Value =
+        serde_json::from_str(clipboard_json).map_err(|e| e.to_string())?;
+    if payload.get("type").and_then(|v| v.as_str()) != Some(CLIPBOARD_TYPE) {
+        return Err("Not Excalidraw clipboard data".to_string());
+    }
+
+    let elements = payload
+        .get("elements")
+        .and_then(|v| v.as_array())
+        .cloned()
+        .unwrap_or_default();
+    let files = payload
+        .get("files")
+        .filter(|f| f.is_object())
+        .cloned()
+        .unwrap_or_else(|| serde_json::json!({}));
+    Ok(canvas_document(elements, files))
+}
+
+fn import_clipboard_in(
+    base: &Path,
+    clipboard_json: &str,
+    dest_path: &str,
+) -> Result<String, String> {
+    if is_vault_root(dest_path) {
+        return Err("保存先のファイル名を指定してください".to_string());
+    }
+    let doc = canvas_from_clipboard(clipboard_json)?;
+
+    let requested = base.join(dest_path);
+    let parent = requested.parent().ok_or("Invalid path")?;
+    let name = requested
+        .file_name()
+        .ok_or("Invalid path")?
+        .to_string_lossy()
+        .to_string();
+    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+
+    let target = unique_child_path(parent, &name);
+    write_canvas_json(&target, &doc)?;
+    Ok(relative_to(base, &target))
+}
+
+#[tauri::command(async)]
+pub fn import_clipboard(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    clipboard_json: String,
+    dest_path: String,
+) -> Result<String, String> {
+    fs_ops.run(|| {
+        let dest_path = normalize_relative_path(&dest_path)?;
+        let base = get_base_dir(&app)?;
+        import_clipboard_in(&base, &clipboard_json, &dest_path)
+    })
+}
+
+/// Canvases whose `source` is set to something other than this app, with that source.
+/// Files without a `source` field are not reported.
+fn find_foreign_source_in(base: &Path) -> Result<Vec<(String, String)>, String> {
+    let mut foreign = Vec::new();
+    for path in walk_canvases(base, base)? {
+        let Ok(doc) = read_canvas_json(&base.join(&path)) else {
+            continue;
+        };
+        if let Some(source) = format_info(&doc).source {
+            if source != CANVAS_SOURCE {
+                foreign.push((path, source));
+            }
+        }
+    }
+    Ok(foreign)
+}
+
+#[tauri::command(async)]
+pub fn find_foreign_source(app: AppHandle) -> Result<Vec<(String, String)>, String> {
+    let base = get_base_dir(&app)?;
+    find_foreign_source_in(&base)
+}
+
+fn set_source_at(full_path: &Path) -> Result<(), String> {
+    let mut doc = read_canvas_json(full_path)?;
+    let fields = doc.as_object_mut().ok_or("Invalid canvas")?;
+    if fields.get("source").and_then(|v| v.as_str()) == Some(CANVAS_SOURCE) {
+        return Ok(());
+    }
+    fields.insert("source".into(), CANVAS_SOURCE.into());
+    write_canvas_json(full_path, &doc)
+}
+
+fn set_canvas_source_in(base: &Path, paths: &[String]) -> Vec<BatchResult> {
+    paths
+        .iter()
+        .map(|path| {
+            let result = normalize_relative_path(path).and_then(|p| set_source_at(&base.join(p)));
+            BatchResult::from_result(path, result.map(|_| None))
+        })
+        .collect()
+}
+
+#[tauri::command(async)]
+pub fn set_canvas_source(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    paths: Vec<String>,
+) -> Result<Vec<BatchResult>, String> {
+    fs_ops.run(|| {
+        let base = get_base_dir(&app)?;
+        Ok(set_canvas_source_in(&base, &paths))
+    })
+}
+
+/// Permanently delete a canvas only if it has no live elements.
+fn delete_if_empty_in(base: &Path, path: &str) -> Result<(), String> {
+    let full_path = base.join(path);
+    let doc = read_canvas_json(&full_path)?;
+    if count_live_elements(&doc) > 0 {
+        return Err("Canvas is not empty; move it to the trash instead".to_string());
+    }
+
+    fs::remove_file(&full_path).map_err(|e| e.to_string())?;
+    let _ = append_history(base, "delete", Some(path), None);
+    Ok(())
+}
+
+#[tauri::command(async)]
+pub fn delete_if_empty(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    path: String,
+) -> Result<(), String> {
+    fs_ops.run(|| {
+        let path = normalize_relative_path(&path)?;
+        let base = get_base_dir(&app)?;
+        delete_if_empty_in(&base, &path)
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ImageStats {
+    #[serde(rename = "imageCount")]
+    pub image_count: u64,
+    #[serde(rename = "totalBytes")]
+    pub total_bytes: u64,
+}
+
+/// Decoded size of a base64 `data:` URL, computed from its length without decoding.
+fn data_url_decoded_len(data_url: &str) -> u64 {
+    let Some((_, payload)) = data_url.split_once(',') else {
+        return 0;
+    };
+    if !data_url[..data_url.len() - payload.len()].contains(";base64") {
+        return payload.len() as u64;
+    }
+    let payload = payload.trim_end();
+    let padding = payload.bytes().rev().take_while(|b| *b == b'=').count() as u64;
+    (payload.len() as u64 / 4 * 3).saturating_sub(padding)
+}
+
+fn embedded_image_stats_in(base: &Path) -> Result<ImageStats, String> {
+    let mut stats = ImageStats::default();
+    for path in walk_canvases(base, base)? {
+        let Ok(doc) = read_canvas_json(&base.join(&path)) else {
+            continue;
+        };
+        for file in doc
+            .get("files")
+            .and_then(|f| f.as_object())
+            .into_iter()
+            .flat_map(|f| f.values())
+        {
+            stats.image_count += 1;
+            if let Some(data_url) = file.get("dataURL").and_then(|v| v.as_str()) {
+                stats.total_bytes += data_url_decoded_len(data_url);
+            }
+        }
+    }
+    Ok(stats)
+}
+
+#[tauri::command(async)]
+pub fn embedded_image_stats(app: AppHandle) -> Result<ImageStats, String> {
+    let base = get_base_dir(&app)?;
+    embedded_image_stats_in(&base)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PathRemap {
+    pub from: String,
+    pub to: String,
+}
+
+/// Payload of the `folder-renamed` event emitted by `rename_item`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FolderRenamed {
+    pub from: String,
+    pub to: String,
+    pub remapped: Vec<PathRemap>,
+}
+
+/// Old → new paths of every canvas under `old_folder` once it is renamed to `new_folder`.
+fn descendant_remappings(
+    base: &Path,
+    old_folder: &str,
+    new_folder: &str,
+) -> Result<Vec<PathRemap>, String> {
+    let prefix = format!("{}/", old_folder);
+    Ok(walk_canvases(base, &base.join(old_folder))?
+        .into_iter()
+        .filter_map(|from| {
+            let rest = from.strip_prefix(&prefix)?.to_string();
+            Some(PathRemap {
+                to: format!("{}/{}", new_folder, rest),
+                from,
+            })
+        })
+        .collect())
+}
+
+const VERSIONS_DIR: &str = ".versions";
+
+/// Name of a canvas's history folder: its relative path with `%` and `/` percent-escaped,
+/// so two different paths never share one.
+fn versions_key(item_path: &str) -> String {
+    item_path
+        .replace('\\', "/")
+        .replace('%', "%25")
+        .replace('/', "%2F")
+}
+
+/// Directory holding the backups of one canvas.
+fn get_versions_dir(base: &Path, item_path: &str) -> PathBuf {
+    base.join(VERSIONS_DIR).join(versions_key(item_path))
+}
+
+/// Copy the current content of a canvas into its version history, returning the backup's timestamp.
+fn create_version_in(base: &Path, path: &str) -> Result<u64, String> {
+    let source = base.join(path);
+    if !source.is_file() {
+        return Err(format!("Canvas not found: {}", path));
+    }
+
+    let dir = get_versions_dir(base, path);
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+    let mut ts = now_millis();
+    while dir.join(format!("{}.excalidraw", ts)).exists() {
+        ts += 1;
+    }
+    // Backups are always stored plain, whether or not the canvas is compressed
+    let content = read_canvas_bytes(&source).map_err(|e| e.to_string())?;
+    fs::write(dir.join(format!("{}.excalidraw", ts)), content).map_err(|e| e.to_string())?;
+    Ok(ts)
+}
+
+/// Timestamps of a canvas's backups, newest first.
+fn list_versions_in(base: &Path, path: &str) -> Result<Vec<u64>, String> {
+    let dir = get_versions_dir(base, path);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut versions: Vec<u64> = fs::read_dir(&dir)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            name.strip_suffix(".excalidraw")?.parse().ok()
+        })
+        .collect();
+    versions.sort_unstable_by(|a, b| b.cmp(a));
+    Ok(versions)
+}
+
+fn clear_versions_in(base: &Path, path: &str) -> Result<usize, String> {
+    let removed = list_versions_in(base, path)?.len();
+    let dir = get_versions_dir(base, path);
+    if dir.exists() {
+        fs::remove_dir_all(&dir).map_err(|e| e.to_string())?;
+    }
+    Ok(removed)
+}
+
+#[tauri::command(async)]
+pub fn create_version(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    path: String,
+) -> Result<u64, String> {
+    fs_ops.run(|| {
+        let path = normalize_relative_path(&path)?;
+        let base = get_base_dir(&app)?;
+        create_version_in(&base, &path)
+    })
+}
+
+#[tauri::command(async)]
+pub fn list_versions(app: AppHandle, path: String) -> Result<Vec<u64>, String> {
+    let path = normalize_relative_path(&path)?;
+    let base = get_base_dir(&app)?;
+    list_versions_in(&base, &path)
+}
+
+#[tauri::command(async)]
+pub fn clear_versions(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    path: String,
+) -> Result<usize, String> {
+    fs_ops.run(|| {
+        let path = normalize_relative_path(&path)?;
+        let base = get_base_dir(&app)?;
+        clear_versions_in(&base, &path)
+    })
+}
+
+fn folder_has_match(dir: &Path, matcher: &globset::GlobMatcher) -> Result<bool, String> {
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+        if entry.file_type().map_err(|e| e.to_string())?.is_dir() {
+            if folder_has_match(&entry.path(), matcher)? {
+                return Ok(true);
+            }
+        } else if is_canvas_name(&name) && matcher.is_match(&name) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Shallow listing of `path` keeping canvases whose name matches `glob`
+/// and folders that contain a match at any depth.
+fn list_dir_filtered_in(base: &Path, path: &str, glob: &str) -> Result<Vec<FileItem>, String> {
+    let matcher = globset::Glob::new(glob)
+        .map_err(|e| format!("Invalid glob pattern: {}", e))?
+        .compile_matcher();
+
+    let dir = base.join(path);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    // Only the direct children are listed; subfolders are searched just until a first match
+    let opened = load_opened(base);
+    let mut items = Vec::new();
+    for entry in fs::read_dir(&dir).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+        let entry_path = entry.path();
+        let relative_path = relative_to(base, &entry_path);
+
+        if entry.file_type().map_err(|e| e.to_string())?.is_dir() {
+            if !folder_has_match(&entry_path, &matcher)? {
+                continue;
+            }
+            let (icon, icon_color) = load_item_icon(base, &relative_path);
+            let modified = entry
+                .metadata()
+                .ok()
+                .and_then(|m| m.modified().ok())
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs());
+            items.push(FileItem {
+                name,
+                path: relative_path,
+                is_folder: true,
+                children: None,
+                icon,
+                icon_color,
+                modified,
+                size: None,
+                opened_at: None,
+                element_count: None,
+            });
+        } else if is_canvas_name(&name) && matcher.is_match(&name) {
+            items.push(canvas_file_item(base, &relative_path, &opened)?);
+        }
+    }
+
+    items.sort_by(|a, b| match (a.is_folder, b.is_folder) {
+        (true, false) => std::cmp::Ordering::Less,
+        (false, true) => std::cmp::Ordering::Greater,
+        _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+    });
+    Ok(items)
+}
+
+#[tauri::command(async)]
+pub fn list_dir_filtered(
+    app: AppHandle,
+    path: String,
+    glob: String,
+) -> Result<Vec<FileItem>, String> {
+    let path = normalize_relative_path(&path)?;
+    let base = get_base_dir(&app)?;
+    list_dir_filtered_in(&base, &path, &glob)
+}
+
+/// Hex SHA-256 digest used to detect external modifications between open and save.
+fn content_hash(bytes: &[u8]) -> String {
+    use sha2::Digest;
+
+    sha2::Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Error returned by `save_canvas_guarded`. A conflict carries the on-disk content
+/// so the frontend can offer a merge.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum SaveError {
+    Conflict {
+        current: Option<String>,
+        #[serde(rename = "currentHash")]
+        current_hash: Option<String>,
+    },
+    Io {
+        message: String,
+    },
+    /// The write would take the vault past `maxVaultBytes`.
+    QuotaExceeded {
+        limit: u64,
+        projected: u64,
+    },
+}
+
+impl From<String> for SaveError {
+    fn from(message: String) -> Self {
+        SaveError::Io { message }
+    }
+}
+
+fn save_canvas_guarded_at(
+    full_path: &Path,
+    content: &str,
+    baseline_hash: &str,
+) -> Result<(), SaveError> {
+    match read_canvas_bytes(full_path) {
+        Ok(current) => {
+            let current_hash = content_hash(&current);
+            if current_hash != baseline_hash {
+                return Err(SaveError::Conflict {
+                    current: Some(String::from_utf8_lossy(&current).to_string()),
+                    current_hash: Some(current_hash),
+                });
+            }
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(SaveError::Conflict {
+                current: None,
+                current_hash: None,
+            });
+        }
+        Err(e) => return Err(e.to_string().into()),
+    }
+
+    write_canvas_bytes(full_path, content.as_bytes())?;
+    Ok(())
+}
+
+#[tauri::command(async)]
+pub fn canvas_hash(app: AppHandle, path: String) -> Result<String, String> {
+    let path = normalize_relative_path(&path)?;
+    let base = get_base_dir(&app)?;
+    let content = read_canvas_bytes(&base.join(&path)).map_err(|e| e.to_string())?;
+    Ok(content_hash(&content))
+}
+
+#[tauri::command(async)]
+pub fn save_canvas_guarded(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    path: String,
+    content: String,
+    baseline_hash: String,
+) -> Result<(), SaveError> {
+    fs_ops.run(|| {
+        let path = normalize_relative_path(&path)?;
+        let base = get_base_dir(&app)?;
+        save_canvas_guarded_at(&base.join(&path), &content, &baseline_hash)
+    })
+}
+
+/// Next free `Untitled.excalidraw` / `Untitled N.excalidraw` in a folder, optionally created.
+fn new_untitled_in(base: &Path, folder_path: &str, create: bool) -> Result<String, String> {
+    let dir = base.join(folder_path);
+    let target = unique_child_path(&dir, "Untitled.excalidraw");
+    let path = relative_to(base, &target);
+
+    if create {
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        fs::write(&target, DEFAULT_CANVAS_CONTENT).map_err(|e| e.to_string())?;
+        let _ = append_history(base, "create", None, Some(&path));
+    }
+    Ok(path)
+}
+
+#[tauri::command(async)]
+pub fn new_untitled(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    folder_path: String,
+    create: bool,
+) -> Result<String, String> {
+    fs_ops.run(|| {
+        let folder_path = normalize_relative_path(&folder_path)?;
+        let base = get_base_dir(&app)?;
+        new_untitled_in(&base, &folder_path, create)
+    })
+}
+
+/// Creation time in epoch millis, or `None` where the platform/filesystem does not record it.
+fn created_millis(full_path: &Path) -> Result<Option<u64>, String> {
+    let metadata = fs::metadata(full_path).map_err(|e| e.to_string())?;
+    Ok(metadata
+        .created()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64))
+}
+
+#[tauri::command(async)]
+pub fn canvas_created_time(app: AppHandle, path: String) -> Result<Option<u64>, String> {
+    let path = normalize_relative_path(&path)?;
+    let base = get_base_dir(&app)?;
+    created_millis(&base.join(&path))
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct HistoryStats {
+    #[serde(rename = "entryCount")]
+    pub entry_count: u64,
+    #[serde(rename = "byteSize")]
+    pub byte_size: u64,
+}
+
+fn history_stats_in(base: &Path) -> Result<HistoryStats, String> {
+    let content = match fs::read_to_string(base.join(HISTORY_FILE)) {
+        Ok(c) => c,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HistoryStats::default()),
+        Err(e) => return Err(e.to_string()),
+    };
+    Ok(HistoryStats {
+        entry_count: content.lines().filter(|l| !l.trim().is_empty()).count() as u64,
+        byte_size: content.len() as u64,
+    })
+}
+
+fn clear_history_in(base: &Path) -> Result<(), String> {
+    let path = base.join(HISTORY_FILE);
+    if path.exists() {
+        fs::write(&path, "").map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+#[tauri::command(async)]
+pub fn history_stats(app: AppHandle) -> Result<HistoryStats, String> {
+    let base = get_base_dir(&app)?;
+    history_stats_in(&base)
+}
+
+#[tauri::command(async)]
+pub fn clear_history(app: AppHandle, fs_ops: tauri::State<'_, FsSemaphore>) -> Result<(), String> {
+    fs_ops.run(|| {
+        let base = get_base_dir(&app)?;
+        clear_history_in(&base)
+    })
+}
+
+/// Quote a CSV cell when it contains a separator, quote or line break.
+fn csv_escape(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+/// Render the live elements of `doc` as CSV rows of id, type, x, y, width, height, text.
+fn elements_csv(doc: &serde_json::Value) -> String {
+    const COLUMNS: [&str; 7] = ["id", "type", "x", "y", "width", "height", "text"];
+
+    let mut csv = COLUMNS.join(",");
+    csv.push('\n');
+    for el in live_elements(doc) {
+        let row: Vec<String> = COLUMNS
+            .iter()
+            .map(|key| match el.get(*key) {
+                Some(serde_json::Value::String(s)) => csv_escape(s),
+                Some(serde_json::Value::Number(n)) => n.to_string(),
+                _ => String::new(),
+            })
+            .collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+#[tauri::command(async)]
+pub fn export_elements_csv(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    path: String,
+    dest_absolute_path: String,
+) -> Result<(), String> {
+    fs_ops.run(|| {
+        let path = normalize_relative_path(&path)?;
+        let base = get_base_dir(&app)?;
+        let dest = PathBuf::from(&dest_absolute_path);
+
+        if !dest.is_absolute() {
+            return Err("CSV destination must be an absolute path".to_string());
+        }
+
+        let doc = read_canvas_json(&base.join(&path))?;
+        fs::write(&dest, elements_csv(&doc)).map_err(|e| e.to_string())
+    })
+}
+
+/// Bounding box `(min_x, min_y, max_x, max_y)` of an element, accounting for
+/// negative sizes and the relative `points` of lines and arrows.
+fn element_bounds(el: &serde_json::Value) -> Option<(f64, f64, f64, f64)> {
+    let num = |key: &str| el.get(key).and_then(|v| v.as_f64());
+    let x = num("x")?;
+    let y = num("y")?;
+    let width = num("width").unwrap_or(0.0);
+    let height = num("height").unwrap_or(0.0);
+
+    let mut bounds = (
+        x.min(x + width),
+        y.min(y + height),
+        x.max(x + width),
+        y.max(y + height),
+    );
+    let points = el.get("points").and_then(|v| v.as_array());
+    for point in points.into_iter().flatten() {
+        let (Some(px), Some(py)) = (
+            point.get(0).and_then(|v| v.as_f64()),
+            point.get(1).and_then(|v| v.as_f64()),
+        ) else {
+            continue;
+        };
+        bounds.0 = bounds.0.min(x + px);
+        bounds.1 = bounds.1.min(y + py);
+        bounds.2 = bounds.2.max(x + px);
+        bounds.3 = bounds.3.max(y + py);
+    }
+    Some(bounds)
+}
+
+/// Bounding box of every live element in `doc`, or `None` for an empty canvas.
+fn content_bounds(doc: &serde_json::Value) -> Option<(f64, f64, f64, f64)> {
+    live_elements(doc)
+        .filter_map(element_bounds)
+        .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)))
+}
+
+/// Canvases whose content reaches into negative x or y coordinates.
+fn find_offscreen_canvases_in(base: &Path) -> Result<Vec<String>, String> {
+    let mut offscreen = Vec::new();
+    for path in walk_canvases(base, base)? {
+        let Ok(doc) = read_canvas_json(&base.join(&path)) else {
+            continue;
+        };
+        if let Some((min_x, min_y, _, _)) = content_bounds(&doc) {
+            if min_x < 0.0 || min_y < 0.0 {
+                offscreen.push(path);
+            }
+        }
+    }
+    Ok(offscreen)
+}
+
+#[tauri::command(async)]
+pub fn find_offscreen_canvases(app: AppHandle) -> Result<Vec<String>, String> {
+    let base = get_base_dir(&app)?;
+    find_offscreen_canvases_in(&base)
+}
+
+/// Text of the live text elements in z-order, joined by spaces and cut to `max_chars`
+/// characters with a trailing ellipsis when anything was dropped.
+fn text_preview(doc: &serde_json::Value, max_chars: usize) -> String {
+    let text = live_elements(doc)
+        .filter(|el| el.get("type").and_then(|v| v.as_str()) == Some("text"))
+        .filter_map(|el| el.get("text").and_then(|v| v.as_str()))
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if text.chars().count() <= max_chars {
+        return text;
+    }
+    let cut: String = text.chars().take(max_chars).collect();
+    format!("{}…", cut.trim_end())
+}
+
+#[tauri::command(async)]
+pub fn canvas_text_preview(
+    app: AppHandle,
+    path: String,
+    max_chars: usize,
+) -> Result<String, String> {
+    let path = normalize_relative_path(&path)?;
+    let base = get_base_dir(&app)?;
+    let doc = read_canvas_json(&base.join(&path))?;
+    Ok(text_preview(&doc, max_chars))
+}
+
+const COLLECTIONS_DIR: &str = ".collections";
+
+/// Entry name to vault path, for platforms where collection entries are copies rather than symlinks.
+#[cfg(not(unix))]
+const COLLECTION_INDEX: &str = ".index.json";
+
+fn collection_dir(base: &Path, name: &str) -> Result<PathBuf, String> {
+    validate_name(name)?;
+    if name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err("使用できない文字が含まれています".to_string());
+    }
+    Ok(base.join(COLLECTIONS_DIR).join(name))
+}
+
+/// Each entry in a collection with the vault path it stands for. Links are relative to the
+/// collection folder, so they keep resolving after the vault is moved.
+#[cfg(unix)]
+fn collection_entries(base: &Path, dir: &Path) -> Result<Vec<(PathBuf, String)>, String> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let Ok(target) = fs::read_link(entry.path()) else {
+            continue;
+        };
+        let mut resolved = PathBuf::new();
+        for component in dir.join(target).components() {
+            match component {
+                std::path::Component::ParentDir => {
+                    resolved.pop();
+                }
+                std::path::Component::CurDir => {}
+                other => resolved.push(other),
+            }
+        }
+        let relative = relative_to(base, &resolved);
+        if !relative.is_empty() {
+            entries.push((entry.path(), relative));
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(not(unix))]
+fn read_collection_index(dir: &Path) -> std::collections::BTreeMap<String, String> {
+    fs::read_to_string(dir.join(COLLECTION_INDEX))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(not(unix))]
+fn collection_entries(_base: &Path, dir: &Path) -> Result<Vec<(PathBuf, String)>, String> {
+    Ok(read_collection_index(dir)
+        .into_iter()
+        .map(|(entry, target)| (dir.join(entry), target))
+        .collect())
+}
+
+fn add_to_collection_in(base: &Path, path: &str, collection_name: &str) -> Result<(), String> {
+    let source = base.join(path);
+    if !source.is_file() {
+        return Err("Canvas not found".to_string());
+    }
+    let dir = collection_dir(base, collection_name)?;
+    if collection_entries(base, &dir)?
+        .iter()
+        .any(|(_, target)| target == path)
+    {
+        return Ok(());
+    }
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+    let file_name = source
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or("Invalid path")?;
+    let entry = unique_child_path(&dir, &file_name);
+
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(Path::new("../..").join(path), &entry).map_err(|e| e.to_string())?;
+
+    #[cfg(not(unix))]
+    {
+        fs::copy(&source, &entry).map_err(|e| e.to_string())?;
+        let mut index = read_collection_index(&dir);
+        index.insert(relative_to(&dir, &entry), path.to_string());
+        let json = serde_json::to_string(&index).map_err(|e| e.to_string())?;
+        write_atomic(&dir.join(COLLECTION_INDEX), &json)?;
+    }
+
+    Ok(())
+}
+
+fn remove_from_collection_in(base: &Path, path: &str, collection_name: &str) -> Result<(), String> {
+    let dir = collection_dir(base, collection_name)?;
+    let matching: Vec<PathBuf> = collection_entries(base, &dir)?
+        .into_iter()
+        .filter(|(_, target)| target == path)
+        .map(|(entry, _)| entry)
+        .collect();
+    if matching.is_empty() {
+        return Err("Canvas is not in this collection".to_string());
+    }
+
+    for entry in &matching {
+        fs::remove_file(entry).map_err(|e| e.to_string())?;
+    }
+
+    #[cfg(not(unix))]
+    {
+        let mut index = read_collection_index(&dir);
+        index.retain(|_, target| target.as_str() != path);
+        let json = serde_json::to_string(&index).map_err(|e| e.to_string())?;
+        write_atomic(&dir.join(COLLECTION_INDEX), &json)?;
+    }
+
+    Ok(())
+}
+
+/// Vault paths of the canvases in a collection, sorted, skipping ones that no longer exist.
+fn list_collection_in(base: &Path, collection_name: &str) -> Result<Vec<String>, String> {
+    let dir = collection_dir(base, collection_name)?;
+    let mut paths: Vec<String> = collection_entries(base, &dir)?
+        .into_iter()
+        .map(|(_, target)| target)
+        .filter(|target| base.join(target).is_file())
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+#[tauri::command(async)]
+pub fn add_to_collection(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    path: String,
+    collection_name: String,
+) -> Result<(), String> {
+    fs_ops.run(|| {
+        let path = normalize_relative_path(&path)?;
+        let base = get_base_dir(&app)?;
+        add_to_collection_in(&base, &path, &collection_name)
+    })
+}
+
+#[tauri::command(async)]
+pub fn remove_from_collection(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    path: String,
+    collection_name: String,
+) -> Result<(), String> {
+    fs_ops.run(|| {
+        let path = normalize_relative_path(&path)?;
+        let base = get_base_dir(&app)?;
+        remove_from_collection_in(&base, &path, &collection_name)
+    })
+}
+
+#[tauri::command(async)]
+pub fn list_collection(app: AppHandle, collection_name: String) -> Result<Vec<String>, String> {
+    let base = get_base_dir(&app)?;
+    list_collection_in(&base, &collection_name)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct CanvasDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<String>,
+}
+
+/// Compare the live elements of two canvases by id. Each list is sorted.
+fn diff_elements(before: &serde_json::Value, after: &serde_json::Value) -> CanvasDiff {
+    let by_id = |doc| -> std::collections::BTreeMap<String, &serde_json::Value> {
+        live_elements(doc)
+            .filter_map(|el| Some((el.get("id")?.as_str()?.to_string(), el)))
+            .collect()
+    };
+    let before = by_id(before);
+    let after = by_id(after);
+
+    let mut diff = CanvasDiff::default();
+    for (id, el) in &after {
+        match before.get(id) {
+            None => diff.added.push(id.clone()),
+            Some(old) if old != el => diff.modified.push(id.clone()),
+            Some(_) => {}
+        }
+    }
+    diff.removed = before
+        .keys()
+        .filter(|id| !after.contains_key(*id))
+        .cloned()
+        .collect();
+    diff
+}
+
+/// Diff the backup taken at `ts_a` against the one taken at `ts_b`.
+fn diff_versions_in(base: &Path, path: &str, ts_a: u64, ts_b: u64) -> Result<CanvasDiff, String> {
+    let dir = get_versions_dir(base, path);
+    let load = |ts: u64| {
+        let version = dir.join(format!("{}.excalidraw", ts));
+        if !version.is_file() {
+            return Err(format!("Version not found: {}", ts));
+        }
+        read_canvas_json(&version)
+    };
+    Ok(diff_elements(&load(ts_a)?, &load(ts_b)?))
+}
+
+#[tauri::command(async)]
+pub fn diff_versions(
+    app: AppHandle,
+    path: String,
+    ts_a: u64,
+    ts_b: u64,
+) -> Result<CanvasDiff, String> {
+    let path = normalize_relative_path(&path)?;
+    let base = get_base_dir(&app)?;
+    diff_versions_in(&base, &path, ts_a, ts_b)
+}
+
+/// Pairs of canvases whose live elements overlap by at least `threshold`, scored by the
+/// Jaccard index of their `(id, type)` sets and sorted most similar first.
+fn find_similar_canvases_in(
+    base: &Path,
+    threshold: f64,
+) -> Result<Vec<(String, String, f64)>, String> {
+    let mut canvases: Vec<(String, std::collections::HashSet<(String, String)>)> = Vec::new();
+    for path in walk_canvases(base, base)? {
+        let Ok(doc) = read_canvas_json(&base.join(&path)) else {
+            continue;
+        };
+        let keys: std::collections::HashSet<(String, String)> = live_elements(&doc)
+            .filter_map(|el| {
+                let id = el.get("id")?.as_str()?.to_string();
+                let kind = el.get("type")?.as_str().unwrap_or_default().to_string();
+                Some((id, kind))
+            })
+            .collect();
+        if !keys.is_empty() {
+            canvases.push((path, keys));
+        }
+    }
+
+    let mut pairs = Vec::new();
+    for (i, (path_a, a)) in canvases.iter().enumerate() {
+        for (path_b, b) in &canvases[i + 1..] {
+            // The score can never exceed the ratio of the two sizes
+            let (small, large) = (a.len().min(b.len()), a.len().max(b.len()));
+            if (small as f64) / (large as f64) < threshold {
+                continue;
+            }
+            let shared = a.intersection(b).count();
+            let score = shared as f64 / (a.len() + b.len() - shared) as f64;
+            if score >= threshold {
+                pairs.push((path_a.clone(), path_b.clone(), score));
+            }
+        }
+    }
+    pairs.sort_by(|x, y| y.2.total_cmp(&x.2));
+    Ok(pairs)
+}
+
+#[tauri::command(async)]
+pub fn find_similar_canvases(
+    app: AppHandle,
+    threshold: f64,
+) -> Result<Vec<(String, String, f64)>, String> {
+    let base = get_base_dir(&app)?;
+    find_similar_canvases_in(&base, threshold)
+}
+
+const PINNED_FOLDERS_FILE: &str = ".pinned_folders.json";
+
+/// Pinned folder paths in the order they were pinned.
+fn load_pinned_folders(base: &Path) -> Vec<String> {
+    fs::read_to_string(base.join(PINNED_FOLDERS_FILE))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn save_pinned_folders(base: &Path, pinned: &[String]) -> Result<(), String> {
+    let json = serde_json::to_string(pinned).map_err(|e| e.to_string())?;
+    write_atomic(&base.join(PINNED_FOLDERS_FILE), &json)
+}
+
+fn pin_folder_in(base: &Path, folder_path: &str) -> Result<(), String> {
+    if folder_path.is_empty() || !base.join(folder_path).is_dir() {
+        return Err(format!("Not a folder: {}", folder_path));
+    }
+    let mut pinned = load_pinned_folders(base);
+    if !pinned.iter().any(|p| p == folder_path) {
+        pinned.push(folder_path.to_string());
+        save_pinned_folders(base, &pinned)?;
+    }
+    Ok(())
+}
+
+fn unpin_folder_in(base: &Path, folder_path: &str) -> Result<(), String> {
+    let mut pinned = load_pinned_folders(base);
+    pinned.retain(|p| p != folder_path);
+    save_pinned_folders(base, &pinned)
+}
+
+/// Pinned folders that still exist, in pin order.
+fn list_pinned_folders_in(base: &Path) -> Vec<String> {
+    load_pinned_folders(base)
+        .into_iter()
+        .filter(|p| base.join(p).is_dir())
+        .collect()
+}
+
+#[tauri::command(async)]
+pub fn pin_folder(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    folder_path: String,
+) -> Result<(), String> {
+    fs_ops.run(|| {
+        let folder_path = normalize_relative_path(&folder_path)?;
+        let base = get_base_dir(&app)?;
+        pin_folder_in(&base, &folder_path)
+    })
+}
+
+#[tauri::command(async)]
+pub fn unpin_folder(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    folder_path: String,
+) -> Result<(), String> {
+    fs_ops.run(|| {
+        let folder_path = normalize_relative_path(&folder_path)?;
+        let base = get_base_dir(&app)?;
+        unpin_folder_in(&base, &folder_path)
+    })
+}
+
+#[tauri::command(async)]
+pub fn list_pinned_folders(app: AppHandle) -> Result<Vec<String>, String> {
+    let base = get_base_dir(&app)?;
+    Ok(list_pinned_folders_in(&base))
+}
+
+/// Undo already-applied moves, newest first. Best-effort: a failing step is skipped.
+fn rollback_renames(applied: &[(PathBuf, PathBuf)]) {
+    for (from, to) in applied.iter().rev() {
+        let _ = fs::rename(to, from);
+    }
+}
+
+/// The steps of a rename, each recorded in `applied` once it has taken effect.
+fn apply_rename_steps(
+    base: &Path,
+    trash: &Path,
+    old_path: &str,
+    new_path: &str,
+    applied: &mut Vec<(PathBuf, PathBuf)>,
+) -> Result<(), String> {
+    // Icon sidecars to carry along: the item itself plus, for folders, every canvas inside it
+    let mut sidecars = vec![PathRemap {
+        from: old_path.to_string(),
+        to: new_path.to_string(),
+    }];
+    if base.join(old_path).is_dir() {
+        sidecars.extend(descendant_remappings(base, old_path, new_path)?);
+    }
+
+    let old_full = base.join(old_path);
+    let new_full = base.join(new_path);
+    if let Some(parent) = new_full.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    rename_path(&old_full, &new_full)?;
+    applied.push((old_full, new_full));
+
+    for remap in &sidecars {
+        let from = get_icon_meta_path(base, &remap.from);
+        if !from.exists() {
+            continue;
+        }
+        let to = get_icon_meta_path(base, &remap.to);
+        fs::rename(&from, &to).map_err(|e| e.to_string())?;
+        applied.push((from, to));
+    }
+
+    retarget_trash_metas(trash, old_path, new_path)?;
+    Ok(())
+}
+
+/// Rename an item together with its icon sidecars and trash metas, putting everything
+/// back where it was if any step fails. Version history follows once the rename has stuck.
+fn rename_transactional_in(
+    base: &Path,
+    trash: &Path,
+    old_path: &str,
+    new_path: &str,
+) -> Result<(), String> {
+    let mut applied = Vec::new();
+    if let Err(e) = apply_rename_steps(base, trash, old_path, new_path, &mut applied) {
+        rollback_renames(&applied);
+        return Err(e);
+    }
+    move_versions(base, old_path, new_path);
+    let _ = append_history(base, "rename", Some(old_path), Some(new_path));
+    Ok(())
+}
+
+#[tauri::command(async)]
+pub fn rename_item_transactional(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    old_path: String,
+    new_path: String,
+) -> Result<(), String> {
+    fs_ops.run(|| {
+        let old_path = normalize_relative_path(&old_path)?;
+        let new_path = normalize_relative_path(&new_path)?;
+        let base = get_base_dir(&app)?;
+        let trash = get_trash_dir(&app)?;
+
+        let remapped = if base.join(&old_path).is_dir() {
+            Some(descendant_remappings(&base, &old_path, &new_path)?)
+        } else {
+            None
+        };
+
+        rename_transactional_in(&base, &trash, &old_path, &new_path)?;
+
+        if let Some(remapped) = remapped {
+            let payload = FolderRenamed {
+                from: old_path,
+                to: new_path,
+                remapped,
+            };
+            let _ = app.emit("folder-renamed", payload);
+        }
+        Ok(())
+    })
+}
+
+/// Number of distinct `groupIds` used across the live elements of `doc`.
+fn group_count(doc: &serde_json::Value) -> u64 {
+    live_elements(doc)
+        .filter_map(|el| el.get("groupIds").and_then(|v| v.as_array()))
+        .flatten()
+        .filter_map(|id| id.as_str())
+        .collect::<std::collections::HashSet<_>>()
+        .len() as u64
+}
+
+#[tauri::command(async)]
+pub fn canvas_group_count(app: AppHandle, path: String) -> Result<u64, String> {
+    let path = normalize_relative_path(&path)?;
+    let base = get_base_dir(&app)?;
+    let doc = read_canvas_json(&base.join(&path))?;
+    Ok(group_count(&doc))
+}
+
+/// Canvases last modified more than `older_than_days` before `now`, oldest first.
+fn find_stale_canvases_in(
+    base: &Path,
+    older_than_days: u32,
+    now: SystemTime,
+) -> Result<Vec<FileItem>, String> {
+    // A cutoff before the clock's range means nothing can be that old
+    let Some(cutoff) = now.checked_sub(std::time::Duration::from_secs(
+        u64::from(older_than_days) * 24 * 60 * 60,
+    )) else {
+        return Ok(Vec::new());
+    };
+    let cutoff_secs = cutoff
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let opened = load_opened(base);
+    let mut stale: Vec<FileItem> = walk_canvases(base, base)?
+        .iter()
+        .filter_map(|path| canvas_file_item(base, path, &opened).ok())
+        .filter(|item| item.modified.is_some_and(|m| m < cutoff_secs))
+        .collect();
+    stale.sort_by_key(|item| item.modified);
+    Ok(stale)
+}
+
+#[tauri::command(async)]
+pub fn find_stale_canvases(app: AppHandle, older_than_days: u32) -> Result<Vec<FileItem>, String> {
+    let base = get_base_dir(&app)?;
+    find_stale_canvases_in(&base, older_than_days, SystemTime::now())
+}
+
+/// A problem found by `lint_folder`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum LintIssue {
+    ZeroByte,
+    Unparseable {
+        message: String,
+    },
+    MissingFilesKey,
+    Offscreen,
+    BrokenImageRefs {
+        #[serde(rename = "fileIds")]
+        file_ids: Vec<String>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LintResult {
+    pub path: String,
+    pub issues: Vec<LintIssue>,
+}
+
+/// Every issue found in one canvas file.
+fn lint_canvas(full_path: &Path) -> Result<Vec<LintIssue>, String> {
+    let content = read_canvas_text(full_path)?;
+    if content.is_empty() {
+        return Ok(vec![LintIssue::ZeroByte]);
+    }
+    let doc: serde_json::Value = match serde_json::from_str(&content) {
+        Ok(doc) => doc,
+        Err(e) => {
+            return Ok(vec![LintIssue::Unparseable {
+                message: e.to_string(),
+            }])
+        }
+    };
+
+    let mut issues = Vec::new();
+    if !doc.get("files").is_some_and(|f| f.is_object()) {
+        issues.push(LintIssue::MissingFilesKey);
+    }
+    if content_bounds(&doc).is_some_and(|(min_x, min_y, _, _)| min_x < 0.0 || min_y < 0.0) {
+        issues.push(LintIssue::Offscreen);
+    }
+    let file_ids = broken_file_refs(&doc);
+    if !file_ids.is_empty() {
+        issues.push(LintIssue::BrokenImageRefs { file_ids });
+    }
+    Ok(issues)
+}
+
+/// Read-only diagnostics for every canvas under `folder_path`; clean canvases are omitted.
+fn lint_folder_in(base: &Path, folder_path: &str) -> Result<Vec<LintResult>, String> {
+    let dir = base.join(folder_path);
+    if !dir.is_dir() {
+        return Err(format!("Not a folder: {}", folder_path));
+    }
+
+    let mut results = Vec::new();
+    for path in walk_canvases(base, &dir)? {
+        let issues = lint_canvas(&base.join(&path))?;
+        if !issues.is_empty() {
+            results.push(LintResult { path, issues });
+        }
+    }
+    Ok(results)
+}
+
+#[tauri::command(async)]
+pub fn lint_folder(app: AppHandle, folder_path: String) -> Result<Vec<LintResult>, String> {
+    let folder_path = normalize_relative_path(&folder_path)?;
+    let base = get_base_dir(&app)?;
+    lint_folder_in(&base, &folder_path)
+}
+
+/// Translate every element so the live content starts at non-negative coordinates.
+/// Returns whether anything moved.
+fn shift_to_origin(doc: &mut serde_json::Value) -> bool {
+    let Some((min_x, min_y, _, _)) = content_bounds(doc) else {
+        return false;
+    };
+    let dx = if min_x < 0.0 { -min_x } else { 0.0 };
+    let dy = if min_y < 0.0 { -min_y } else { 0.0 };
+    if dx == 0.0 && dy == 0.0 {
+        return false;
+    }
+
+    let elements = doc.get_mut("elements").and_then(|v| v.as_array_mut());
+    for el in elements.into_iter().flatten() {
+        for (key, delta) in [("x", dx), ("y", dy)] {
+            if let Some(v) = el.get(key).and_then(|v| v.as_f64()) {
+                el[key] = serde_json::json!(v + delta);
+            }
+        }
+    }
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FixResult {
+    pub path: String,
+    /// Issues that were repaired.
+    pub fixed: Vec<LintIssue>,
+    /// Issues left alone because they cannot be fixed safely.
+    pub remaining: Vec<LintIssue>,
+}
+
+/// Apply the safe fixes for one canvas's lint issues, writing it back only if something changed.
+fn autofix_canvas(base: &Path, path: String, issues: Vec<LintIssue>) -> Result<FixResult, String> {
+    let full_path = base.join(&path);
+    let mut fixed = Vec::new();
+    let mut remaining = Vec::new();
+
+    if issues.contains(&LintIssue::ZeroByte) {
+        fs::write(&full_path, DEFAULT_CANVAS_CONTENT).map_err(|e| e.to_string())?;
+        fixed.push(LintIssue::ZeroByte);
+    } else if issues
+        .iter()
+        .any(|i| matches!(i, LintIssue::Unparseable { .. }))
+    {
+        remaining = issues;
+    } else {
+        let mut doc = read_canvas_json(&full_path)?;
+        for issue in issues {
+            match issue {
+                LintIssue::MissingFilesKey => {
+                    if let Some(fields) = doc.as_object_mut() {
+                        fields.insert("files".into(), serde_json::json!({}));
+                        fixed.push(issue);
+                    } else {
+                        remaining.push(issue);
+                    }
+                }
+                LintIssue::Offscreen if shift_to_origin(&mut doc) => fixed.push(issue),
+                _ => remaining.push(issue),
+            }
+        }
+        if !fixed.is_empty() {
+            write_canvas_json(&full_path, &doc)?;
+        }
+    }
+
+    Ok(FixResult {
+        path,
+        fixed,
+        remaining,
+    })
+}
+
+/// Fix what `lint_folder` would report under `folder_path`, one result per canvas with issues.
+fn autofix_folder_in(base: &Path, folder_path: &str) -> Result<Vec<FixResult>, String> {
+    lint_folder_in(base, folder_path)?
+        .into_iter()
+        .map(|lint| autofix_canvas(base, lint.path, lint.issues))
+        .collect()
+}
+
+#[tauri::command(async)]
+pub fn autofix_folder(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    folder_path: String,
+) -> Result<Vec<FixResult>, String> {
+    fs_ops.run(|| {
+        let folder_path = normalize_relative_path(&folder_path)?;
+        let base = get_base_dir(&app)?;
+        autofix_folder_in(&base, &folder_path)
+    })
+}
+
+/// Payload of the `vault-item-discovered` event emitted by `list_dir_streaming`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DiscoveredItem {
+    pub path: String,
+    #[serde(rename = "isFolder")]
+    pub is_folder: bool,
+    /// Relative path of the containing folder, `""` for the vault root.
+    pub parent: String,
+}
+
+/// Walk `dir` depth-first in sidebar order (folders first, then case-insensitive by name),
+/// reporting each folder before its contents. Returns the number of items reported.
+fn walk_discovered<F: FnMut(DiscoveredItem)>(
+    base: &Path,
+    dir: &Path,
+    on_item: &mut F,
+) -> Result<usize, String> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+        let is_folder = entry.file_type().map_err(|e| e.to_string())?.is_dir();
+        if is_folder || is_canvas_name(&name) {
+            entries.push((is_folder, name, entry.path()));
+        }
+    }
+    entries.sort_by(|a, b| {
+        b.0.cmp(&a.0)
+            .then_with(|| a.1.to_lowercase().cmp(&b.1.to_lowercase()))
+    });
+
+    let parent = relative_to(base, dir);
+    let mut count = 0;
+    for (is_folder, _, entry_path) in entries {
+        on_item(DiscoveredItem {
+            path: relative_to(base, &entry_path),
+            is_folder,
+            parent: parent.clone(),
+        });
+        count += 1;
+        if is_folder {
+            count += walk_discovered(base, &entry_path, on_item)?;
+        }
+    }
+    Ok(count)
+}
+
+#[tauri::command(async)]
+pub fn list_dir_streaming(app: AppHandle, path: String) -> Result<(), String> {
+    let path = normalize_relative_path(&path)?;
+    let base = get_base_dir(&app)?;
+    let target = base.join(&path);
+
+    let count = if target.is_dir() {
+        walk_discovered(&base, &target, &mut |item| {
+            let _ = app.emit("vault-item-discovered", item);
+        })?
+    } else {
+        0
+    };
+    let _ = app.emit("vault-listing-complete", count);
+    Ok(())
+}
+
+/// Longest icon accepted by `set_canvas_icon`, in chars; enough for multi-codepoint emoji.
+const MAX_ICON_CHARS: usize = 16;
+
+/// Set or clear a canvas's icon in its `.meta` sidecar, keeping any existing color.
+fn set_canvas_icon_in(base: &Path, path: &str, icon: Option<&str>) -> Result<(), String> {
+    if !base.join(path).is_file() {
+        return Err(format!("Canvas not found: {}", path));
+    }
+    let meta_path = get_icon_meta_path(base, path);
+
+    let Some(icon) = icon else {
+        if meta_path.exists() {
+            fs::remove_file(&meta_path).map_err(|e| e.to_string())?;
+        }
+        return Ok(());
+    };
+
+    let icon = icon.trim();
+    if icon.is_empty() || icon.chars().count() > MAX_ICON_CHARS {
+        return Err(format!("Icon must be 1 to {} characters", MAX_ICON_CHARS));
+    }
+    // ':' separates the icon from its color in the sidecar
+    if icon.chars().any(|c| c.is_control() || c == ':') {
+        return Err("Icon contains invalid characters".to_string());
+    }
+
+    let content = match load_item_icon(base, path).1 {
+        Some(color) => format!("{}:{}", icon, color),
+        None => icon.to_string(),
+    };
+    fs::create_dir_all(base.join(".meta")).map_err(|e| e.to_string())?;
+    fs::write(meta_path, content).map_err(|e| e.to_string())
+}
+
+#[tauri::command(async)]
+pub fn set_canvas_icon(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    path: String,
+    icon: Option<String>,
+) -> Result<(), String> {
+    fs_ops.run(|| {
+        let path = normalize_relative_path(&path)?;
+        let base = get_base_dir(&app)?;
+        set_canvas_icon_in(&base, &path, icon.as_deref())
+    })
+}
+
+#[tauri::command(async)]
+pub fn get_canvas_icon(app: AppHandle, path: String) -> Result<Option<String>, String> {
+    let path = normalize_relative_path(&path)?;
+    let base = get_base_dir(&app)?;
+    Ok(load_item_icon(&base, &path).0)
+}
+
+/// HTTP ETag for a canvas: a weak `W/"<size>-<mtime>"` tag from metadata alone, or a strong
+/// tag over the SHA-256 of the (decompressed) content when `strong` is set.
+fn canvas_etag_at(full_path: &Path, strong: bool) -> Result<String, String> {
+    if strong {
+        let bytes = read_canvas_bytes(full_path).map_err(|e| e.to_string())?;
+        return Ok(format!("\"{}\"", content_hash(&bytes)));
+    }
+
+    let metadata = fs::metadata(full_path).map_err(|e| e.to_string())?;
+    let mtime = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default()
+        .as_nanos();
+    Ok(format!("W/\"{:x}-{:x}\"", metadata.len(), mtime))
+}
+
+#[tauri::command(async)]
+pub fn canvas_etag(app: AppHandle, path: String, strong: Option<bool>) -> Result<String, String> {
+    let path = normalize_relative_path(&path)?;
+    let base = get_base_dir(&app)?;
+    canvas_etag_at(&base.join(&path), strong.unwrap_or(false))
+}
+
+/// Canvases with more than `min_elements` live elements, largest first.
+fn find_heavy_canvases_in(base: &Path, min_elements: u64) -> Result<Vec<(String, u64)>, String> {
+    let mut heavy = Vec::new();
+    for path in walk_canvases(base, base)? {
+        let Ok(doc) = read_canvas_json(&base.join(&path)) else {
+            continue;
+        };
+        let count = count_live_elements(&doc);
+        if count > min_elements {
+            heavy.push((path, count));
+        }
+    }
+    heavy.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    Ok(heavy)
+}
+
+#[tauri::command(async)]
+pub fn find_heavy_canvases(
+    app: AppHandle,
+    min_elements: u64,
+) -> Result<Vec<(String, u64)>, String> {
+    let base = get_base_dir(&app)?;
+    find_heavy_canvases_in(&base, min_elements)
+}
+
+/// Filesystem renames needed to move `from` to `to`. When only the letter case differs,
+/// the move goes through a hidden temporary sibling so case-insensitive filesystems
+/// (macOS, Windows) actually apply the new case instead of treating it as a no-op.
+fn rename_plan(from: &Path, to: &Path) -> Vec<(PathBuf, PathBuf)> {
+    let from_str = from.to_string_lossy();
+    let to_str = to.to_string_lossy();
+    if from_str == to_str || from_str.to_lowercase() != to_str.to_lowercase() {
+        return vec![(from.to_path_buf(), to.to_path_buf())];
+    }
+
+    let name = from
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let tmp = from.with_file_name(format!(".{}.case-rename.tmp", name));
+    vec![(from.to_path_buf(), tmp.clone()), (tmp, to.to_path_buf())]
+}
+
+/// Rename following `rename_plan`, undoing the first step if the second fails.
+fn rename_path(from: &Path, to: &Path) -> Result<(), String> {
+    // fs::rename would silently replace an existing file (or empty folder) at the destination.
+    // A hit on the source itself is a case-only rename on a case-insensitive file system.
+    if to.symlink_metadata().is_ok() && !same_entry(from, to) {
+        return Err("移動先に同名のアイテムが既に存在します".to_string());
+    }
+
+    let plan = rename_plan(from, to);
+    for (i, (step_from, step_to)) in plan.iter().enumerate() {
+        if let Err(e) = fs::rename(step_from, step_to) {
+            rollback_renames(&plan[..i]);
+            return Err(e.to_string());
+        }
+    }
+    Ok(())
+}
+
+/// A4 paper size in inches.
+const A4_INCHES: (f64, f64) = (210.0 / 25.4, 297.0 / 25.4);
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SceneSize {
+    pub width: f64,
+    pub height: f64,
+    /// A4 pages needed to tile the scene at the requested DPI, portrait orientation.
+    #[serde(rename = "a4PagesPortrait")]
+    pub a4_pages_portrait: u64,
+    /// Same, landscape orientation.
+    #[serde(rename = "a4PagesLandscape")]
+    pub a4_pages_landscape: u64,
+}
+
+fn scene_size(doc: &serde_json::Value, dpi: f64) -> SceneSize {
+    let Some((min_x, min_y, max_x, max_y)) = content_bounds(doc) else {
+        return SceneSize {
+            width: 0.0,
+            height: 0.0,
+            a4_pages_portrait: 0,
+            a4_pages_landscape: 0,
+        };
+    };
+    let width = max_x - min_x;
+    let height = max_y - min_y;
+
+    let (page_w, page_h) = (A4_INCHES.0 * dpi, A4_INCHES.1 * dpi);
+    let pages = |pw: f64, ph: f64| {
+        let across = (width / pw).ceil().max(1.0);
+        let down = (height / ph).ceil().max(1.0);
+        (across * down) as u64
+    };
+
+    SceneSize {
+        width,
+        height,
+        a4_pages_portrait: pages(page_w, page_h),
+        a4_pages_landscape: pages(page_h, page_w),
+    }
+}
+
+#[tauri::command(async)]
+pub fn canvas_scene_size(
+    app: AppHandle,
+    path: String,
+    dpi: Option<f64>,
+) -> Result<SceneSize, String> {
+    let path = normalize_relative_path(&path)?;
+    let dpi = dpi.unwrap_or(96.0);
+    if !(dpi > 0.0 && dpi.is_finite()) {
+        return Err("DPI must be a positive number".to_string());
+    }
+    let base = get_base_dir(&app)?;
+    let doc = read_canvas_json(&base.join(&path))?;
+    Ok(scene_size(&doc, dpi))
+}
+
+/// Hex SHA-1 of a base64 `data:` URL's decoded bytes, the id Excalidraw gives embedded files.
+fn data_url_sha1(data_url: &str) -> Option<String> {
+    use base64::Engine;
+    use sha1::Digest;
+
+    let (header, payload) = data_url.split_once(',')?;
+    if !header.ends_with(";base64") {
+        return None;
+    }
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(payload)
+        .ok()?;
+    Some(
+        sha1::Sha1::digest(&bytes)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect(),
+    )
+}
+
+/// Re-key `files` by content hash and point element `fileId`s at the new keys.
+/// Entries whose content matches an existing key are merged into it. Returns how many were re-keyed.
+fn rekey_embedded_files_in(doc: &mut serde_json::Value) -> usize {
+    let Some(files) = doc.get_mut("files").and_then(|f| f.as_object_mut()) else {
+        return 0;
+    };
+
+    let mut renames = std::collections::HashMap::new();
+    for (id, entry) in files.iter() {
+        let Some(hash) = entry
+            .get("dataURL")
+            .and_then(|v| v.as_str())
+            .and_then(data_url_sha1)
+        else {
+            continue;
+        };
+        if *id != hash {
+            renames.insert(id.clone(), hash);
+        }
+    }
+
+    for (old_id, new_id) in &renames {
+        let Some(mut entry) = files.remove(old_id) else {
+            continue;
+        };
+        entry["id"] = serde_json::json!(new_id);
+        files.entry(new_id.clone()).or_insert(entry);
+    }
+
+    let elements = doc.get_mut("elements").and_then(|v| v.as_array_mut());
+    for el in elements.into_iter().flatten() {
+        let Some(new_id) = el
+            .get("fileId")
+            .and_then(|v| v.as_str())
+            .and_then(|id| renames.get(id))
+        else {
+            continue;
+        };
+        el["fileId"] = serde_json::json!(new_id);
+    }
+    renames.len()
+}
+
+#[tauri::command(async)]
+pub fn rekey_embedded_files(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    path: String,
+) -> Result<usize, String> {
+    fs_ops.run(|| {
+        let path = normalize_relative_path(&path)?;
+        let base = get_base_dir(&app)?;
+        let full_path = base.join(&path);
+
+        let mut doc = read_canvas_json(&full_path)?;
+        let rekeyed = rekey_embedded_files_in(&mut doc);
+        if rekeyed > 0 {
+            write_canvas_json(&full_path, &doc)?;
+        }
+        Ok(rekeyed)
+    })
+}
+
+const SUBTREE_MANIFEST: &str = "manifest.json";
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SubtreeManifest {
+    /// Vault path of the exported folder.
+    pub folder: String,
+    #[serde(rename = "exportedAt")]
+    pub exported_at: u64,
+    /// Canvas paths inside the archive, relative to the exported folder.
+    pub canvases: Vec<String>,
+}
+
+/// Zip the canvases under `folder_path` (paths relative to that folder) plus a manifest.
+fn write_subtree_archive(
+    base: &Path,
+    folder_path: &str,
+    archive_path: &Path,
+) -> Result<SubtreeManifest, String> {
+    let dir = base.join(folder_path);
+    if folder_path.is_empty() || !dir.is_dir() {
+        return Err(format!("Not a folder: {}", folder_path));
+    }
+
+    let prefix = format!("{}/", folder_path);
+    let canvases: Vec<String> = walk_canvases(base, &dir)?
+        .into_iter()
+        .filter_map(|p| p.strip_prefix(&prefix).map(str::to_string))
+        .collect();
+    let manifest = SubtreeManifest {
+        folder: folder_path.to_string(),
+        exported_at: now_millis(),
+        canvases,
+    };
+
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+    let file = fs::File::create(archive_path).map_err(|e| e.to_string())?;
+    let mut zip = zip::ZipWriter::new(std::io::BufWriter::new(file));
+
+    for relative in &manifest.canvases {
+        zip.start_file(relative.as_str(), options)
+            .map_err(|e| e.to_string())?;
+        let mut canvas = fs::File::open(dir.join(relative)).map_err(|e| e.to_string())?;
+        std::io::copy(&mut canvas, &mut zip).map_err(|e| e.to_string())?;
+    }
+
+    let manifest_json = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+    zip.start_file(SUBTREE_MANIFEST, options)
+        .map_err(|e| e.to_string())?;
+    std::io::Write::write_all(&mut zip, manifest_json.as_bytes()).map_err(|e| e.to_string())?;
+    zip.finish().map_err(|e| e.to_string())?;
+
+    Ok(manifest)
+}
+
+#[tauri::command(async)]
+pub fn export_subtree(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    folder_path: String,
+    dest_absolute_path: String,
+) -> Result<SubtreeManifest, String> {
+    fs_ops.run(|| {
+        let folder_path = normalize_relative_path(&folder_path)?;
+        let base = get_base_dir(&app)?;
+        let archive_path = PathBuf::from(&dest_absolute_path);
+
+        if !archive_path.is_absolute() {
+            return Err("Archive path must be an absolute path".to_string());
+        }
+        if archive_path.starts_with(base.join(&folder_path)) {
+            return Err("Archive path must be outside the exported folder".to_string());
+        }
+
+        write_subtree_archive(&base, &folder_path, &archive_path)
+    })
+}
+
+/// Pairs of canvases whose live elements share ids, with the shared ids.
+/// Pairs and ids are sorted.
+fn find_id_collisions_in(base: &Path) -> Result<Vec<(String, String, Vec<String>)>, String> {
+    // element id -> canvases (in walk order) that use it
+    let mut owners: std::collections::BTreeMap<String, Vec<String>> = Default::default();
+    for path in walk_canvases(base, base)? {
+        let Ok(doc) = read_canvas_json(&base.join(&path)) else {
+            continue;
+        };
+        let ids: std::collections::BTreeSet<&str> = live_elements(&doc)
+            .filter_map(|el| el.get("id").and_then(|v| v.as_str()))
+            .collect();
+        for id in ids {
+            owners.entry(id.to_string()).or_default().push(path.clone());
+        }
+    }
+
+    let mut pairs: std::collections::BTreeMap<(String, String), Vec<String>> = Default::default();
+    for (id, paths) in owners {
+        for (i, a) in paths.iter().enumerate() {
+            for b in &paths[i + 1..] {
+                pairs
+                    .entry((a.clone(), b.clone()))
+                    .or_default()
+                    .push(id.clone());
+            }
+        }
+    }
+    Ok(pairs.into_iter().map(|((a, b), ids)| (a, b, ids)).collect())
+}
+
+#[tauri::command(async)]
+pub fn find_id_collisions(app: AppHandle) -> Result<Vec<(String, String, Vec<String>)>, String> {
+    let base = get_base_dir(&app)?;
+    find_id_collisions_in(&base)
+}
+
+/// Case-insensitive comparison that orders runs of digits by numeric value,
+/// so `canvas2` sorts before `canvas10`.
+fn natural_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+
+    fn chunks(s: &str) -> Vec<(bool, String)> {
+        let mut out: Vec<(bool, String)> = Vec::new();
+        for c in s.chars() {
+            let is_digit = c.is_ascii_digit();
+            match out.last_mut() {
+                Some((digits, chunk)) if *digits == is_digit => chunk.push(c),
+                _ => out.push((is_digit, c.to_string())),
+            }
+        }
+        out
+    }
+
+    let (left, right) = (chunks(a), chunks(b));
+    for ((l_digits, l), (r_digits, r)) in left.iter().zip(&right) {
+        let ord = if *l_digits && *r_digits {
+            let (l_trim, r_trim) = (l.trim_start_matches('0'), r.trim_start_matches('0'));
+            l_trim
+                .len()
+                .cmp(&r_trim.len())
+                .then_with(|| l_trim.cmp(r_trim))
+                .then_with(|| l.len().cmp(&r.len()))
+        } else {
+            l.to_lowercase().cmp(&r.to_lowercase())
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    left.len()
+        .cmp(&right.len())
+        .then_with(|| a.to_lowercase().cmp(&b.to_lowercase()))
+}
+
+/// Sidebar state, kept in app data next to the vault rather than inside it.
+const UI_STATE_FILE: &str = ".uistate.json";
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct UiState {
+    #[serde(rename = "expandedPaths", default)]
+    expanded_paths: Vec<String>,
+}
+
+fn save_ui_state_in(app_data: &Path, expanded_paths: Vec<String>) -> Result<(), String> {
+    fs::create_dir_all(app_data).map_err(|e| e.to_string())?;
+    let state = UiState { expanded_paths };
+    let json = serde_json::to_string(&state).map_err(|e| e.to_string())?;
+    write_atomic(&app_data.join(UI_STATE_FILE), &json)
+}
+
+/// Expanded folder paths from the last session, dropping ones that no longer exist in `base`.
+fn load_ui_state_in(app_data: &Path, base: &Path) -> Vec<String> {
+    let state: UiState = fs::read_to_string(app_data.join(UI_STATE_FILE))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+    state
+        .expanded_paths
+        .into_iter()
+        .filter(|p| safe_relative_path(p).is_ok() && !p.is_empty() && base.join(p).is_dir())
+        .collect()
+}
+
+#[tauri::command(async)]
+pub fn save_ui_state(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    expanded_paths: Vec<String>,
+) -> Result<(), String> {
+    fs_ops.run(|| {
+        let app_data = get_app_data_dir(&app)?;
+        save_ui_state_in(&app_data, expanded_paths)
+    })
+}
+
+#[tauri::command(async)]
+pub fn load_ui_state(app: AppHandle) -> Result<Vec<String>, String> {
+    let app_data = get_app_data_dir(&app)?;
+    let base = get_base_dir(&app)?;
+    Ok(load_ui_state_in(&app_data, &base))
+}
+
+/// Move one item into `dest_dir` with `rename_entry`, returning its new path. A name already
+/// taken there is numbered when `number_on_conflict` is set, and an error otherwise.
+fn move_item_into(
+    base: &Path,
+    path: &str,
+    dest_dir: &str,
+    number_on_conflict: bool,
+) -> Result<String, String> {
+    let path = normalize_relative_path(path)?;
+    let source = base.join(&path);
+    if path.is_empty() || !source.exists() {
+        return Err(format!("Item not found: {}", path));
+    }
+    if dest_dir == path || dest_dir.starts_with(&format!("{}/", path)) {
+        return Err("フォルダをそれ自身の中に移動することはできません".to_string());
+    }
+
+    let dest = base.join(dest_dir);
+    if source.parent() == Some(dest.as_path()) {
+        return Ok(path);
+    }
+    let name = source
+        .file_name()
+        .ok_or("Invalid path")?
+        .to_string_lossy()
+        .to_string();
+    let new_full = if number_on_conflict {
+        unique_child_path(&dest, &name)
+    } else {
+        dest.join(&name)
+    };
+    let new_path = relative_to(base, &new_full);
+    rename_entry(base, &path, &new_path)?;
+    Ok(new_path)
+}
+
+/// Move each item into `dest_dir`, reporting success or failure per item.
+fn move_items_in(
+    base: &Path,
+    paths: &[String],
+    dest_dir: &str,
+) -> Result<Vec<BatchResult>, String> {
+    if !base.join(dest_dir).is_dir() {
+        return Err(format!("Not a folder: {}", dest_dir));
+    }
+    Ok(paths
+        .iter()
+        .map(|path| {
+            BatchResult::from_result(path, move_item_into(base, path, dest_dir, true).map(Some))
+        })
+        .collect())
+}
+
+#[tauri::command(async)]
+pub fn move_items(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    paths: Vec<String>,
+    dest_dir: String,
+) -> Result<Vec<BatchResult>, String> {
+    fs_ops.run(|| {
+        let dest_dir = normalize_relative_path(&dest_dir)?;
+        let base = get_base_dir(&app)?;
+        let trash = get_trash_dir(&app)?;
+
+        let results = move_items_in(&base, &paths, &dest_dir)?;
+        for result in &results {
+            if let (Ok(old_path), Some(new_path)) =
+                (normalize_relative_path(&result.path), &result.new_path)
+            {
+                let _ = retarget_trash_metas(&trash, &old_path, new_path);
+                emit_folder_renamed(&app, &base, &old_path, new_path);
+            }
+        }
+        Ok(results)
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct SnapshotDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+/// Content hash of every canvas entry in a vault archive, keyed by path.
+fn archive_canvas_hashes(
+    archive_path: &Path,
+) -> Result<std::collections::BTreeMap<String, String>, String> {
+    let file = fs::File::open(archive_path).map_err(|e| e.to_string())?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| e.to_string())?;
+
+    let mut hashes = std::collections::BTreeMap::new();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).map_err(|e| e.to_string())?;
+        if entry.is_dir() || !entry.name().ends_with(".excalidraw") {
+            continue;
+        }
+        let name = entry.name().to_string();
+        let mut bytes = Vec::new();
+        std::io::Read::read_to_end(&mut entry, &mut bytes).map_err(|e| e.to_string())?;
+        hashes.insert(name, content_hash(&bytes));
+    }
+    Ok(hashes)
+}
+
+/// Canvases added, removed or changed going from archive `a` to archive `b`.
+fn compare_snapshots_at(archive_a: &Path, archive_b: &Path) -> Result<SnapshotDiff, String> {
+    let before = archive_canvas_hashes(archive_a)?;
+    let after = archive_canvas_hashes(archive_b)?;
+
+    let mut diff = SnapshotDiff::default();
+    for (path, hash) in &after {
+        match before.get(path) {
+            None => diff.added.push(path.clone()),
+            Some(old) if old != hash => diff.changed.push(path.clone()),
+            Some(_) => {}
+        }
+    }
+    diff.removed = before
+        .keys()
+        .filter(|path| !after.contains_key(*path))
+        .cloned()
+        .collect();
+    Ok(diff)
+}
+
+#[tauri::command(async)]
+pub fn compare_snapshots(archive_a: String, archive_b: String) -> Result<SnapshotDiff, String> {
+    let (archive_a, archive_b) = (PathBuf::from(archive_a), PathBuf::from(archive_b));
+    if !archive_a.is_absolute() || !archive_b.is_absolute() {
+        return Err("Archive path must be an absolute path".to_string());
+    }
+    compare_snapshots_at(&archive_a, &archive_b)
+}
+
+/// User preferences, kept in app data next to the vault.
+const SETTINGS_FILE: &str = ".settings.json";
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct Settings {
+    /// Canvas saves from the editor (`save_canvas`) that would grow the vault past this many
+    /// bytes are refused. Other commands that write to the vault are not limited.
+    #[serde(rename = "maxVaultBytes")]
+    pub max_vault_bytes: Option<u64>,
+    /// How many mutating filesystem operations may run at once.
+    #[serde(rename = "maxConcurrentOps")]
+    pub max_concurrent_ops: Option<usize>,
+    /// Trash items into a sub-trash per top-level folder instead of one flat trash.
+    #[serde(rename = "namespacedTrash")]
+    pub namespaced_trash: bool,
+}
+
+fn load_settings(app_data: &Path) -> Settings {
+    fs::read_to_string(app_data.join(SETTINGS_FILE))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn save_settings_in(app_data: &Path, settings: &Settings) -> Result<(), String> {
+    fs::create_dir_all(app_data).map_err(|e| e.to_string())?;
+    let json = serde_json::to_string(settings).map_err(|e| e.to_string())?;
+    write_atomic(&app_data.join(SETTINGS_FILE), &json)
+}
+
+#[tauri::command(async)]
+pub fn get_settings(app: AppHandle) -> Result<Settings, String> {
+    Ok(load_settings(&get_app_data_dir(&app)?))
+}
+
+#[tauri::command(async)]
+pub fn save_settings(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    settings: Settings,
+) -> Result<(), String> {
+    save_settings_in(&get_app_data_dir(&app)?, &settings)?;
+    fs_ops.set_limit(
+        settings
+            .max_concurrent_ops
+            .unwrap_or(DEFAULT_MAX_CONCURRENT_OPS),
+    );
+    Ok(())
+}
+
+/// Total size in bytes of every file under `dir`, hidden sidecars included.
+fn dir_size(dir: &Path) -> Result<u64, String> {
+    let mut total = 0;
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let metadata = entry.metadata().map_err(|e| e.to_string())?;
+        total += if metadata.is_dir() {
+            dir_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(total)
+}
+
+/// How long a measured vault size is trusted before walking the tree again. Saves keep the
+/// cached figure current; other commands that change the vault are picked up on expiry.
+const VAULT_SIZE_TTL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Last measured vault size, managed as Tauri state so quota checks on save stay cheap.
+#[derive(Default)]
+pub struct VaultSizeCache(std::sync::Mutex<Option<(u64, std::time::Instant)>>);
+
+impl VaultSizeCache {
+    #[cfg(test)]
+    fn total(&self, base: &Path) -> Result<u64, String> {
+        self.update(base, Ok::<u64, String>)
+    }
+
+    /// Run `f` on the current vault size and store the total it returns, holding the lock
+    /// throughout so a concurrent save cannot check against a figure this one is changing.
+    fn update<E: From<String>>(
+        &self,
+        base: &Path,
+        f: impl FnOnce(u64) -> Result<u64, E>,
+    ) -> Result<u64, E> {
+        let mut cached = self.0.lock().map_err(|e| e.to_string())?;
+        let total = match *cached {
+            Some((total, measured_at)) if measured_at.elapsed() < VAULT_SIZE_TTL => total,
+            _ => dir_size(base)?,
+        };
+        let total = f(total)?;
+        *cached = Some((total, std::time::Instant::now()));
+        Ok(total)
+    }
+}
+
+/// Save a canvas, refusing with `QuotaExceeded` when the vault would grow past `limit`. The
+/// projection counts the bytes that land on disk, so a compressed canvas is measured after
+/// gzip. Only `save_canvas` goes through this check; the vault's other writers change the
+/// size without it, and the cache picks their changes up when it expires.
+fn save_canvas_within_quota(
+    base: &Path,
+    path: &str,
+    content: &str,
+    pretty: bool,
+    limit: Option<u64>,
+    cache: &VaultSizeCache,
+) -> Result<(), SaveError> {
+    let full_path = base.join(path);
+    let Some(limit) = limit else {
+        return Ok(save_canvas_at(&full_path, content, pretty)?);
+    };
+
+    let content = format_canvas_content(content, pretty)?;
+    let bytes = encode_canvas(&full_path, content.as_bytes())?;
+    cache.update(base, |total| {
+        let current = fs::metadata(&full_path).map(|m| m.len()).unwrap_or(0);
+        let projected = total.saturating_sub(current) + bytes.len() as u64;
+        if projected > limit {
+            return Err(SaveError::QuotaExceeded { limit, projected });
+        }
+        if let Some(parent) = full_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        write_atomic(&full_path, &bytes)?;
+        Ok(projected)
+    })?;
+    Ok(())
+}
+
+/// Leftovers of interrupted operations under `dir`: `*.tmp` files anywhere (including the
+/// hidden ones `write_atomic` and case-only renames use), plus zero-byte files and empty
+/// folders outside hidden sidecar areas.
+fn collect_remnants(
+    base: &Path,
+    dir: &Path,
+    in_hidden: bool,
+    out: &mut Vec<String>,
+) -> Result<(), String> {
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let hidden = in_hidden || name.starts_with('.');
+        let entry_path = entry.path();
+        let metadata = entry.metadata().map_err(|e| e.to_string())?;
+
+        if metadata.is_dir() {
+            let is_empty = fs::read_dir(&entry_path)
+                .map_err(|e| e.to_string())?
+                .next()
+                .is_none();
+            if is_empty && !hidden {
+                out.push(relative_to(base, &entry_path));
+            } else {
+                collect_remnants(base, &entry_path, hidden, out)?;
+            }
+        } else if name.ends_with(".tmp") || (!hidden && metadata.len() == 0) {
+            out.push(relative_to(base, &entry_path));
+        }
+    }
+    Ok(())
+}
+
+fn find_remnants_in(base: &Path) -> Result<Vec<String>, String> {
+    let mut remnants = Vec::new();
+    collect_remnants(base, base, false, &mut remnants)?;
+    remnants.sort();
+    Ok(remnants)
+}
+
+#[tauri::command(async)]
+pub fn find_remnants(app: AppHandle) -> Result<Vec<String>, String> {
+    let base = get_base_dir(&app)?;
+    find_remnants_in(&base)
+}
+
+/// Live element counts per `type` across every canvas under `folder_path`.
+/// Unparseable canvases are skipped.
+fn folder_element_histogram_in(
+    base: &Path,
+    folder_path: &str,
+) -> Result<std::collections::HashMap<String, u64>, String> {
+    let dir = base.join(folder_path);
+    if !dir.is_dir() {
+        return Err(format!("Not a folder: {}", folder_path));
+    }
+
+    let mut histogram = std::collections::HashMap::new();
+    for path in walk_canvases(base, &dir)? {
+        let Ok(doc) = read_canvas_json(&base.join(&path)) else {
+            continue;
+        };
+        for el in live_elements(&doc) {
+            if let Some(kind) = el.get("type").and_then(|v| v.as_str()) {
+                *histogram.entry(kind.to_string()).or_default() += 1;
+            }
+        }
+    }
+    Ok(histogram)
+}
+
+#[tauri::command(async)]
+pub fn folder_element_histogram(
+    app: AppHandle,
+    folder_path: String,
+) -> Result<std::collections::HashMap<String, u64>, String> {
+    let folder_path = normalize_relative_path(&folder_path)?;
+    let base = get_base_dir(&app)?;
+    folder_element_histogram_in(&base, &folder_path)
+}
+
+/// Canvas schema versions this app can open.
+const SUPPORTED_CANVAS_VERSIONS: std::ops::RangeInclusive<u64> = 1..=CANVAS_VERSION;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CompatReport {
+    /// The file's `version`, if it has one.
+    pub version: Option<u64>,
+    #[serde(rename = "isSupported")]
+    pub is_supported: bool,
+    #[serde(rename = "minSupported")]
+    pub min_supported: u64,
+    #[serde(rename = "maxSupported")]
+    pub max_supported: u64,
+}
+
+fn compat_report(doc: &serde_json::Value) -> CompatReport {
+    let version = doc.get("version").and_then(|v| v.as_u64());
+    CompatReport {
+        version,
+        is_supported: version.is_some_and(|v| SUPPORTED_CANVAS_VERSIONS.contains(&v)),
+        min_supported: *SUPPORTED_CANVAS_VERSIONS.start(),
+        max_supported: *SUPPORTED_CANVAS_VERSIONS.end(),
+    }
+}
+
+#[tauri::command(async)]
+pub fn check_import_compatibility(source_absolute_path: String) -> Result<CompatReport, String> {
+    let source = PathBuf::from(&source_absolute_path);
+    if !source.is_absolute() {
+        return Err("Import source must be an absolute path".to_string());
+    }
+    let doc = read_canvas_json(&source)?;
+    Ok(compat_report(&doc))
+}
+
+/// Exchange the contents of two canvases. Both new contents are staged in hidden temp
+/// files before either original is replaced, so an interruption never loses a side.
+fn swap_canvases_in(base: &Path, path_a: &str, path_b: &str) -> Result<(), String> {
+    if path_a == path_b {
+        return Err("Cannot swap a canvas with itself".to_string());
+    }
+    let (full_a, full_b) = (base.join(path_a), base.join(path_b));
+    for (path, full) in [(path_a, &full_a), (path_b, &full_b)] {
+        if !full.is_file() {
+            return Err(format!("Canvas not found: {}", path));
+        }
+    }
+
+    // Swap the canvas content, re-encoding it for whichever storage each side uses
+    let content_a = read_canvas_bytes(&full_a).map_err(|e| e.to_string())?;
+    let content_b = read_canvas_bytes(&full_b).map_err(|e| e.to_string())?;
+    let (content_a, content_b) = (
+        encode_canvas(&full_b, &content_a)?,
+        encode_canvas(&full_a, &content_b)?,
+    );
+
+    let staged = |full: &Path| {
+        let name = full.file_name().unwrap_or_default().to_string_lossy();
+        full.with_file_name(format!(".{}.swap.tmp", name))
+    };
+    let (tmp_a, tmp_b) = (staged(&full_a), staged(&full_b));
+
+    let stage = fs::write(&tmp_a, &content_b).and_then(|_| fs::write(&tmp_b, &content_a));
+    if let Err(e) = stage {
+        let _ = fs::remove_file(&tmp_a);
+        let _ = fs::remove_file(&tmp_b);
+        return Err(e.to_string());
+    }
+
+    fs::rename(&tmp_a, &full_a).map_err(|e| e.to_string())?;
+    fs::rename(&tmp_b, &full_b).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command(async)]
+pub fn swap_canvases(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    path_a: String,
+    path_b: String,
+) -> Result<(), String> {
+    fs_ops.run(|| {
+        let path_a = normalize_relative_path(&path_a)?;
+        let path_b = normalize_relative_path(&path_b)?;
+        let base = get_base_dir(&app)?;
+        swap_canvases_in(&base, &path_a, &path_b)
+    })
+}
+
+/// Copy the selected live elements (plus text bound inside them) into a fresh document with
+/// new ids. References between extracted elements are remapped; bindings to anything left
+/// behind are dropped. Embedded files used by the extracted images come along.
+fn extract_elements_from(doc: &serde_json::Value, element_ids: &[String]) -> serde_json::Value {
+    let selected: std::collections::HashSet<&str> =
+        element_ids.iter().map(String::as_str).collect();
+    let picked: Vec<&serde_json::Value> = live_elements(doc)
+        .filter(|el| {
+            let id = el.get("id").and_then(|v| v.as_str()).unwrap_or_default();
+            let container = el.get("containerId").and_then(|v| v.as_str());
+            selected.contains(id) || container.is_some_and(|c| selected.contains(c))
+        })
+        .collect();
+
+    let salt = now_millis();
+    let remap: std::collections::HashMap<String, String> = picked
+        .iter()
+        .filter_map(|el| el.get("id").and_then(|v| v.as_str()))
+        .map(|id| {
+            let new_id = format!("{:016x}", fnv1a(format!("{}:{}", salt, id).as_bytes()));
+            (id.to_string(), new_id)
+        })
+        .collect();
+    let remapped = |v: &serde_json::Value| {
+        v.as_str()
+            .and_then(|id| remap.get(id))
+            .map(|id| serde_json::json!(id))
+    };
+
+    let mut file_ids = std::collections::BTreeSet::new();
+    let elements = picked
+        .into_iter()
+        .map(|el| {
+            let mut el = el.clone();
+            el["id"] = remapped(&el["id"]).unwrap_or_default();
+            for key in ["containerId", "frameId"] {
+                if el.get(key).is_some_and(|v| !v.is_null()) {
+                    el[key] = remapped(&el[key]).unwrap_or(serde_json::Value::Null);
+                }
+            }
+            for key in ["startBinding", "endBinding"] {
+                if let Some(binding) = el.get(key).filter(|v| !v.is_null()) {
+                    let target = remapped(&binding["elementId"]);
+                    el[key] = match target {
+                        Some(id) => {
+                            let mut binding = binding.clone();
+                            binding["elementId"] = id;
+                            binding
+                        }
+                        None => serde_json::Value::Null,
+                    };
+                }
+            }
+            if let Some(bound) = el.get("boundElements").and_then(|v| v.as_array()) {
+                let kept: Vec<serde_json::Value> = bound
+                    .iter()
+                    .filter_map(|b| {
+                        let mut b = b.clone();
+                        b["id"] = remapped(&b["id"])?;
+                        Some(b)
+                    })
+                    .collect();
+                el["boundElements"] = serde_json::json!(kept);
+            }
+            if let Some(file_id) = el.get("fileId").and_then(|v| v.as_str()) {
+                file_ids.insert(file_id.to_string());
+            }
+            el
+        })
+        .collect();
+
+    let source_files = doc.get("files").and_then(|f| f.as_object());
+    let files: serde_json::Map<String, serde_json::Value> = file_ids
+        .into_iter()
+        .filter_map(|id| {
+            let entry = source_files?.get(&id)?.clone();
+            Some((id, entry))
+        })
+        .collect();
+
+    canvas_document(elements, serde_json::Value::Object(files))
+}
+
+fn extract_elements_in(
+    base: &Path,
+    source_path: &str,
+    element_ids: &[String],
+    dest_path: &str,
+) -> Result<usize, String> {
+    let dest = base.join(dest_path);
+    if dest.exists() {
+        return Err("Destination file already exists".to_string());
+    }
+
+    let doc = read_canvas_json(&base.join(source_path))?;
+    let extracted = extract_elements_from(&doc, element_ids);
+    let count = count_live_elements(&extracted) as usize;
+    if count == 0 {
+        return Err("None of the given elements were found".to_string());
+    }
+
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    write_canvas_json(&dest, &extracted)?;
+    let _ = append_history(base, "create", None, Some(dest_path));
+    Ok(count)
+}
+
+#[tauri::command(async)]
+pub fn extract_elements(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    source_path: String,
+    element_ids: Vec<String>,
+    dest_path: String,
+) -> Result<usize, String> {
+    fs_ops.run(|| {
+        let source_path = normalize_relative_path(&source_path)?;
+        let dest_path = normalize_relative_path(&dest_path)?;
+        let base = get_base_dir(&app)?;
+        extract_elements_in(&base, &source_path, &element_ids, &dest_path)
+    })
+}
+
+/// Count folders and canvases per depth into `levels`, where `depth` is that of `dir`'s children.
+fn count_by_depth(dir: &Path, depth: usize, levels: &mut Vec<(u64, u64)>) -> Result<(), String> {
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+        let is_folder = entry.file_type().map_err(|e| e.to_string())?.is_dir();
+        if !is_folder && !is_canvas_name(&name) {
+            continue;
+        }
+
+        if levels.len() <= depth {
+            levels.resize(depth + 1, (0, 0));
+        }
+        if is_folder {
+            levels[depth].0 += 1;
+            count_by_depth(&entry.path(), depth + 1, levels)?;
+        } else {
+            levels[depth].1 += 1;
+        }
+    }
+    Ok(())
+}
+
+/// `(depth, folders, canvases)` for each depth level; depth 0 is the root's direct children.
+fn depth_histogram_in(base: &Path) -> Result<Vec<(u32, u64, u64)>, String> {
+    let mut levels = Vec::new();
+    count_by_depth(base, 0, &mut levels)?;
+    Ok(levels
+        .into_iter()
+        .enumerate()
+        .map(|(depth, (folders, canvases))| (depth as u32, folders, canvases))
+        .collect())
+}
+
+#[tauri::command(async)]
+pub fn depth_histogram(app: AppHandle) -> Result<Vec<(u32, u64, u64)>, String> {
+    let base = get_base_dir(&app)?;
+    depth_histogram_in(&base)
+}
+
+/// Remove the version history of each canvas in `paths` that no longer exists in the vault.
+/// Best-effort; returns how many history folders were removed.
+fn drop_versions_of_missing(base: &Path, paths: &[String]) -> usize {
+    let mut removed = 0;
+    for path in paths {
+        let dir = get_versions_dir(base, path);
+        if !base.join(path).exists() && dir.is_dir() && fs::remove_dir_all(&dir).is_ok() {
+            removed += 1;
+        }
+    }
+    removed
+}
+
+/// Original vault paths of every canvas waiting in the trash, sub-trashes included.
+/// Their version history is kept until they are permanently deleted.
+fn trashed_canvas_paths(trash: &Path) -> Result<Vec<String>, String> {
+    let mut paths = Vec::new();
+    for item in read_trash_items(trash)? {
+        if item.is_folder {
+            let dir = trash.join(&item.trash_path);
+            paths.extend(
+                walk_canvases(&dir, &dir)?
+                    .into_iter()
+                    .map(|p| format!("{}/{}", item.original_path, p)),
+            );
+        } else {
+            paths.push(item.original_path);
+        }
+    }
+    Ok(paths)
+}
+
+/// Remove version folders that neither a live canvas nor a trashed one maps to.
+/// Returns how many were removed.
+fn prune_orphan_versions_in(base: &Path, trash: &Path) -> Result<usize, String> {
+    let versions_root = base.join(VERSIONS_DIR);
+    if !versions_root.is_dir() {
+        return Ok(0);
+    }
+
+    let live: std::collections::HashSet<PathBuf> = walk_canvases(base, base)?
+        .iter()
+        .chain(&trashed_canvas_paths(trash)?)
+        .map(|path| get_versions_dir(base, path))
+        .collect();
+    let mut pruned = 0;
+    for entry in fs::read_dir(&versions_root).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        if !entry.file_type().map_err(|e| e.to_string())?.is_dir() || live.contains(&entry.path()) {
+            continue;
+        }
+        fs::remove_dir_all(entry.path()).map_err(|e| e.to_string())?;
+        pruned += 1;
+    }
+    Ok(pruned)
+}
+
+#[tauri::command(async)]
+pub fn prune_orphan_versions(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+) -> Result<usize, String> {
+    fs_ops.run(|| {
+        let base = get_base_dir(&app)?;
+        let trash = get_trash_dir(&app)?;
+        prune_orphan_versions_in(&base, &trash)
+    })
+}
+
+const OPEN_COUNTS_FILE: &str = ".opencounts.json";
+const OPENED_FILE: &str = ".opened.json";
+
+/// A number per canvas path: how many times it was opened (`.opencounts.json`) or when it
+/// was last marked opened, in Unix millis (`.opened.json`). Kept apart from the canvases so
+/// recording an open never rewrites or touches the file itself.
+type OpenIndex = std::collections::BTreeMap<String, u64>;
+
+fn load_open_index(base: &Path, file: &str) -> OpenIndex {
+    fs::read_to_string(base.join(file))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn save_open_index(base: &Path, file: &str, index: &OpenIndex) -> Result<(), String> {
+    let json = serde_json::to_string(index).map_err(|e| e.to_string())?;
+    write_atomic(&base.join(file), &json)
+}
+
+/// How many times each canvas has been opened, by path.
+fn load_open_counts(base: &Path) -> OpenIndex {
+    load_open_index(base, OPEN_COUNTS_FILE)
+}
+
+/// When each canvas was last marked opened, by path.
+fn load_opened(base: &Path) -> OpenIndex {
+    load_open_index(base, OPENED_FILE)
+}
+
+fn mark_opened_in(base: &Path, path: &str, now: u64) -> Result<(), String> {
+    if !base.join(path).is_file() {
+        return Err(format!("Canvas not found: {}", path));
+    }
+    let mut opened = load_opened(base);
+    opened.insert(path.to_string(), now);
+    save_open_index(base, OPENED_FILE, &opened)
+}
+
+#[tauri::command(async)]
+pub fn mark_opened(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    path: String,
+) -> Result<(), String> {
+    fs_ops.run(|| {
+        let path = normalize_relative_path(&path)?;
+        let base = get_base_dir(&app)?;
+        mark_opened_in(&base, &path, now_millis())
+    })
+}
+
+/// Rekey the recent list, open counts and opened times after `old` (a canvas or a folder)
+/// moved to `new`.
+fn move_open_stats(base: &Path, old: &str, new: &str) -> Result<(), String> {
+    let remap = |p: &str| -> Option<String> {
+        if p == old {
+            Some(new.to_string())
+        } else {
+            p.strip_prefix(old)
+                .and_then(|rest| rest.strip_prefix('/'))
+                .map(|rest| format!("{}/{}", new, rest))
+        }
+    };
+
+    let recent = load_recent(base);
+    if recent.iter().any(|p| remap(p).is_some()) {
+        let moved: Vec<String> = recent.into_iter().map(|p| remap(&p).unwrap_or(p)).collect();
+        save_recent(base, &moved)?;
+    }
+
+    for file in [OPEN_COUNTS_FILE, OPENED_FILE] {
+        let index = load_open_index(base, file);
+        if index.keys().any(|p| remap(p).is_some()) {
+            let moved: OpenIndex = index
+                .into_iter()
+                .map(|(p, n)| (remap(&p).unwrap_or(p), n))
+                .collect();
+            save_open_index(base, file, &moved)?;
+        }
+    }
+    Ok(())
+}
+
+/// Drop the recent-list, open-count and opened-time entries of `paths` that no longer exist
+/// in the vault. Best-effort, like `drop_versions_of_missing`.
+fn forget_open_stats(base: &Path, paths: &[String]) {
+    let gone: std::collections::HashSet<&str> = paths
+        .iter()
+        .filter(|p| !base.join(p).exists())
+        .map(String::as_str)
+        .collect();
+    if gone.is_empty() {
+        return;
+    }
+
+    let recent = load_recent(base);
+    if recent.iter().any(|p| gone.contains(p.as_str())) {
+        let kept: Vec<String> = recent
+            .into_iter()
+            .filter(|p| !gone.contains(p.as_str()))
+            .collect();
+        let _ = save_recent(base, &kept);
+    }
+
+    for file in [OPEN_COUNTS_FILE, OPENED_FILE] {
+        let mut index = load_open_index(base, file);
+        let before = index.len();
+        index.retain(|p, _| !gone.contains(p.as_str()));
+        if index.len() != before {
+            let _ = save_open_index(base, file, &index);
+        }
+    }
+}
+
+/// The `limit` most opened canvases that still exist, most opened first.
+fn list_most_opened_in(base: &Path, limit: usize) -> Vec<(String, u64)> {
+    let mut counts: Vec<(String, u64)> = load_open_counts(base)
+        .into_iter()
+        .filter(|(path, _)| base.join(path).is_file())
+        .collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts.truncate(limit);
+    counts
+}
+
+#[tauri::command(async)]
+pub fn list_most_opened(app: AppHandle, limit: usize) -> Result<Vec<(String, u64)>, String> {
+    let base = get_base_dir(&app)?;
+    Ok(list_most_opened_in(&base, limit))
+}
+
+/// Write the relative path of every canvas in the vault to `dest`, one per line, sorted.
+fn export_path_list_to(base: &Path, dest: &Path) -> Result<usize, String> {
+    let mut paths = walk_canvases(base, base)?;
+    paths.sort();
+
+    let mut list = String::new();
+    for path in &paths {
+        list.push_str(path);
+        list.push('\n');
+    }
+    fs::write(dest, list).map_err(|e| e.to_string())?;
+    Ok(paths.len())
+}
+
+#[tauri::command(async)]
+pub fn export_path_list(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    dest_absolute_path: String,
+) -> Result<usize, String> {
+    fs_ops.run(|| {
+        let base = get_base_dir(&app)?;
+        let dest = PathBuf::from(&dest_absolute_path);
+
+        if !dest.is_absolute() {
+            return Err("Path list destination must be an absolute path".to_string());
+        }
+
+        export_path_list_to(&base, &dest)
+    })
+}
+
+/// The canvas's title by convention: the first line of its first non-empty live text element.
+fn canvas_title(doc: &serde_json::Value) -> Option<&str> {
+    live_elements(doc)
+        .filter(|el| el.get("type").and_then(|v| v.as_str()) == Some("text"))
+        .filter_map(|el| el.get("text").and_then(|v| v.as_str()))
+        .filter_map(|text| text.lines().map(str::trim).find(|line| !line.is_empty()))
+        .next()
+}
+
+/// Canvases whose title text differs from the file name, as `(path, title, base name)`.
+fn find_title_mismatches_in(base: &Path) -> Result<Vec<(String, String, String)>, String> {
+    let mut mismatches = Vec::new();
+    for path in walk_canvases(base, base)? {
+        let Ok(doc) = read_canvas_json(&base.join(&path)) else {
+            continue;
+        };
+        let stem = canvas_stem(&path);
+        if let Some(title) = canvas_title(&doc) {
+            if title != stem {
+                mismatches.push((path.clone(), title.to_string(), stem.to_string()));
+            }
+        }
+    }
+    Ok(mismatches)
+}
+
+#[tauri::command(async)]
+pub fn find_title_mismatches(app: AppHandle) -> Result<Vec<(String, String, String)>, String> {
+    let base = get_base_dir(&app)?;
+    find_title_mismatches_in(&base)
+}
+
+/// Suffix of a canvas stored gzip-compressed by `compress_vault`.
+const COMPRESSED_CANVAS_SUFFIX: &str = ".excalidraw.gz";
+
+/// Whether a file name is a canvas, plain or compressed.
+fn is_canvas_name(name: &str) -> bool {
+    name.ends_with(".excalidraw") || name.ends_with(COMPRESSED_CANVAS_SUFFIX)
+}
+
+fn is_compressed_canvas(path: &Path) -> bool {
+    path.to_string_lossy().ends_with(COMPRESSED_CANVAS_SUFFIX)
+}
+
+/// Content of a canvas file, gunzipped when it is stored compressed. An empty file reads as empty.
+fn read_canvas_bytes(path: &Path) -> std::io::Result<Vec<u8>> {
+    use std::io::Read;
+
+    let raw = fs::read(path)?;
+    if raw.is_empty() || !is_compressed_canvas(path) {
+        return Ok(raw);
+    }
+    let mut content = Vec::new();
+    flate2::read::GzDecoder::new(raw.as_slice()).read_to_end(&mut content)?;
+    Ok(content)
+}
+
+fn read_canvas_text(path: &Path) -> Result<String, String> {
+    let content = read_canvas_bytes(path).map_err(|e| e.to_string())?;
+    String::from_utf8(content).map_err(|e| e.to_string())
+}
+
+/// `content` as it is stored at `path`: gzipped for a compressed canvas, unchanged otherwise.
+fn encode_canvas(path: &Path, content: &[u8]) -> Result<Vec<u8>, String> {
+    use std::io::Write;
+
+    if !is_compressed_canvas(path) {
+        return Ok(content.to_vec());
+    }
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(content).map_err(|e| e.to_string())?;
+    encoder.finish().map_err(|e| e.to_string())
+}
+
+/// `write_atomic` for canvas content, compressing it when `path` is a compressed canvas.
+fn write_canvas_bytes(path: &Path, content: &[u8]) -> Result<(), String> {
+    if is_compressed_canvas(path) {
+        write_atomic(path, encode_canvas(path, content)?)
+    } else {
+        write_atomic(path, content)
+    }
+}
+
+/// Gzip every canvas into `<name>.excalidraw.gz` and remove the original, carrying its
+/// version history, icon and open statistics along. Canvases whose compressed counterpart
+/// already exists are left alone. Returns how many were compressed.
+fn compress_vault_in(base: &Path) -> Result<usize, String> {
+    let mut compressed = 0;
+    for path in walk_files_with_suffix(base, base, ".excalidraw")? {
+        let source = base.join(&path);
+        let dest_path = format!("{}.gz", path);
+        let dest = base.join(&dest_path);
+        if dest.exists() {
+            continue;
+        }
+
+        let content = fs::read(&source).map_err(|e| e.to_string())?;
+        write_canvas_bytes(&dest, &content)?;
+        fs::remove_file(&source).map_err(|e| e.to_string())?;
+        move_sidecars(base, &path, &dest_path);
+        compressed += 1;
+    }
+    Ok(compressed)
+}
+
+/// Reverse `compress_vault_in`, restoring each `.excalidraw.gz` to a plain canvas along with
+/// its sidecars. Returns how many were decompressed.
+fn decompress_vault_in(base: &Path) -> Result<usize, String> {
+    let mut decompressed = 0;
+    for path in walk_files_with_suffix(base, base, COMPRESSED_CANVAS_SUFFIX)? {
+        let source = base.join(&path);
+        let dest_path = path.trim_end_matches(".gz").to_string();
+        let dest = base.join(&dest_path);
+        if dest.exists() {
+            continue;
+        }
+
+        let content = read_canvas_bytes(&source).map_err(|e| e.to_string())?;
+        write_atomic(&dest, content)?;
+        fs::remove_file(&source).map_err(|e| e.to_string())?;
+        move_sidecars(base, &path, &dest_path);
+        decompressed += 1;
+    }
+    Ok(decompressed)
+}
+
+#[tauri::command(async)]
+pub fn compress_vault(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+) -> Result<usize, String> {
+    fs_ops.run(|| {
+        let base = get_base_dir(&app)?;
+        compress_vault_in(&base)
+    })
+}
+
+#[tauri::command(async)]
+pub fn decompress_vault(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+) -> Result<usize, String> {
+    fs_ops.run(|| {
+        let base = get_base_dir(&app)?;
+        decompress_vault_in(&base)
+    })
+}
+
+/// Canvases and the IDs of their live elements whose width or height exceeds `max_dimension`.
+fn find_giant_elements_in(
+    base: &Path,
+    max_dimension: f64,
+) -> Result<Vec<(String, String)>, String> {
+    let mut giants = Vec::new();
+    for path in walk_canvases(base, base)? {
+        let Ok(doc) = read_canvas_json(&base.join(&path)) else {
+            continue;
+        };
+        for el in live_elements(&doc) {
+            let size = |key: &str| el.get(key).and_then(|v| v.as_f64()).unwrap_or(0.0).abs();
+            if size("width") > max_dimension || size("height") > max_dimension {
+                let id = el.get("id").and_then(|v| v.as_str()).unwrap_or_default();
+                giants.push((path.clone(), id.to_string()));
+            }
+        }
+    }
+    Ok(giants)
+}
+
+#[tauri::command(async)]
+pub fn find_giant_elements(
+    app: AppHandle,
+    max_dimension: f64,
+) -> Result<Vec<(String, String)>, String> {
+    let base = get_base_dir(&app)?;
+    find_giant_elements_in(&base, max_dimension)
+}
+
+/// Stand-in for embedded file data in `read_canvas_light`.
+const LIGHT_DATA_URL_PLACEHOLDER: &str = "data:,";
+
+/// Replace the `dataURL` of every embedded file with a placeholder, keeping ids, mime types
+/// and other fields so the document keeps its shape.
+fn strip_file_data(doc: &mut serde_json::Value) {
+    let Some(files) = doc.get_mut("files").and_then(|v| v.as_object_mut()) else {
+        return;
+    };
+    for file in files.values_mut() {
+        if let Some(data_url) = file.get_mut("dataURL") {
+            *data_url = LIGHT_DATA_URL_PLACEHOLDER.into();
+        }
+    }
+}
+
+#[tauri::command(async)]
+pub fn read_canvas_light(app: AppHandle, path: String) -> Result<String, String> {
+    let path = normalize_relative_path(&path)?;
+    let base = get_base_dir(&app)?;
+    let mut doc = read_canvas_json(&base.join(&path))?;
+    strip_file_data(&mut doc);
+    serde_json::to_string(&doc).map_err(|e| e.to_string())
+}
+
+/// Names of the items directly in `trash`, and the names legacy `.meta` sidecars refer to,
+/// leaving out the index and sub-trashes.
+fn trash_entries(
+    trash: &Path,
+    namespaces: &[(String, PathBuf)],
+) -> Result<(std::collections::HashSet<String>, Vec<String>), String> {
+    let mut items = std::collections::HashSet::new();
+    let mut sidecars = Vec::new();
+    for entry in fs::read_dir(trash).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name == TRASH_INDEX
+            || name.starts_with('.')
+            || namespaces.iter().any(|(n, _)| *n == name)
+        {
+            continue;
+        }
+        match name.strip_suffix(".meta") {
+            Some(trash_name) => sidecars.push(trash_name.to_string()),
+            None => {
+                items.insert(name);
+            }
+        }
+    }
+    Ok((items, sidecars))
+}
+
+/// Whether the trash holds orphans: items without metadata (invisible to `list_trash`)
+/// or metadata whose item is gone.
+fn trash_needs_repair_in(trash: &Path) -> Result<bool, String> {
+    if !trash.exists() {
+        return Ok(false);
+    }
+
+    let index = read_trash_index(trash);
+    let namespaces = trash_namespaces(trash, &index)?;
+    for (_, dir) in &namespaces {
+        if trash_needs_repair_in(dir)? {
+            return Ok(true);
+        }
+    }
+
+    let (items, sidecars) = trash_entries(trash, &namespaces)?;
+    let has_meta = |name: &str| trash_meta(&index, trash, name).is_ok();
+    Ok(items.iter().any(|name| !has_meta(name))
+        || index.keys().any(|name| !items.contains(name))
+        || sidecars.iter().any(|name| !items.contains(name)))
+}
+
+/// Trash time and original name encoded in a name `move_to_trash` gave an item:
+/// `<ts>_<name>`, or `<ts>_<n>_<name>` for a same-millisecond duplicate.
+fn parse_trash_name(trash_name: &str) -> Option<(u64, &str)> {
+    let (ts, rest) = trash_name.split_once('_')?;
+    let ts = ts.parse().ok()?;
+    let name = match rest.split_once('_') {
+        Some((n, name)) if n.parse::<u32>().is_ok_and(|n| n >= 2) && !name.is_empty() => name,
+        _ => rest,
+    };
+    Some((ts, name))
+}
+
+/// Fix the orphans `trash_needs_repair_in` reports without touching anything else in the
+/// trash: items without metadata are indexed again, under the original name and time their
+/// trash name records (inside `namespace` for a sub-trash), so they show up in `list_trash`;
+/// metadata whose item is gone is dropped. Returns how many entries were fixed.
+fn repair_trash_in(trash: &Path, namespace: Option<&str>) -> Result<usize, String> {
+    if !trash.exists() {
+        return Ok(0);
+    }
+
+    let mut index = read_trash_index(trash);
+    let namespaces = trash_namespaces(trash, &index)?;
+    let mut repaired = 0;
+    for (name, dir) in &namespaces {
+        repaired += repair_trash_in(dir, Some(name))?;
+    }
+
+    let (items, sidecars) = trash_entries(trash, &namespaces)?;
+    let mut orphans: Vec<&String> = items
+        .iter()
+        .filter(|name| trash_meta(&index, trash, name).is_err())
+        .collect();
+    orphans.sort();
+    let stale = index.len();
+    index.retain(|name, _| items.contains(name));
+    let dropped = stale - index.len();
+
+    for name in &orphans {
+        let (trashed_at, original) = match parse_trash_name(name) {
+            Some((ts, original)) => (ts, original),
+            None => {
+                let modified = fs::symlink_metadata(trash.join(name))
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map_or(0, |d| d.as_millis() as u64);
+                (modified, name.as_str())
+            }
+        };
+        let original_path = match namespace {
+            Some(namespace) => format!("{}/{}", namespace, original),
+            None => original.to_string(),
+        };
+        index.insert(
+            name.to_string(),
+            TrashMeta {
+                original_path,
+                trashed_at,
+            },
+        );
+    }
+    if dropped > 0 || !orphans.is_empty() {
+        write_trash_index(trash, &index)?;
+    }
+    repaired += dropped + orphans.len();
+
+    for name in sidecars.iter().filter(|name| !items.contains(*name)) {
+        fs::remove_file(trash.join(format!("{}.meta", name))).map_err(|e| e.to_string())?;
+        repaired += 1;
+    }
+    Ok(repaired)
+}
+
+/// Checked by the UI once it has mounted, so it can prompt about orphans in the trash.
+#[tauri::command(async)]
+pub fn trash_needs_repair(app: AppHandle) -> Result<bool, String> {
+    let trash = get_trash_dir(&app)?;
+    trash_needs_repair_in(&trash)
+}
+
+#[tauri::command(async)]
+pub fn repair_trash(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+) -> Result<usize, String> {
+    fs_ops.run(|| {
+        let trash = get_trash_dir(&app)?;
+        repair_trash_in(&trash, None)
+    })
+}
+
+/// ISO week (`YYYY-Www`, UTC) containing a modification time given in Unix seconds.
+fn iso_week_key(modified_secs: u64) -> String {
+    let date = time::OffsetDateTime::from_unix_timestamp(modified_secs as i64)
+        .unwrap_or(time::OffsetDateTime::UNIX_EPOCH);
+    let (year, week, _) = date.to_iso_week_date();
+    format!("{:04}-W{:02}", year, week)
+}
+
+/// Group every canvas by the ISO week of its modification time, newest week first.
+fn group_by_modified_week_in(base: &Path) -> Result<Vec<(String, Vec<FileItem>)>, String> {
+    let mut groups: std::collections::BTreeMap<String, Vec<FileItem>> = Default::default();
+    let opened = load_opened(base);
+    for path in walk_canvases(base, base)? {
+        let item = canvas_file_item(base, &path, &opened)?;
+        groups
+            .entry(iso_week_key(item.modified.unwrap_or(0)))
+            .or_default()
+            .push(item);
+    }
+    Ok(groups.into_iter().rev().collect())
+}
+
+#[tauri::command(async)]
+pub fn group_by_modified_week(app: AppHandle) -> Result<Vec<(String, Vec<FileItem>)>, String> {
+    let base = get_base_dir(&app)?;
+    group_by_modified_week_in(&base)
+}
+
+/// Replace `find` with `replace` in the live text elements of `doc`, returning how many
+/// occurrences of the displayed text were replaced. `originalText` is kept in step.
+fn replace_text_in_doc(doc: &mut serde_json::Value, find: &str, replace: &str) -> u64 {
+    let Some(elements) = doc.get_mut("elements").and_then(|v| v.as_array_mut()) else {
+        return 0;
+    };
+
+    let mut count = 0;
+    for el in elements {
+        let is_live_text = el.get("type").and_then(|v| v.as_str()) == Some("text")
+            && !el
+                .get("isDeleted")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+        if !is_live_text {
+            continue;
+        }
+        for key in ["text", "originalText"] {
+            let Some(text) = el.get(key).and_then(|v| v.as_str()) else {
+                continue;
+            };
+            let matches = text.matches(find).count() as u64;
+            if matches == 0 {
+                continue;
+            }
+            if key == "text" {
+                count += matches;
+            }
+            el[key] = text.replace(find, replace).into();
+        }
+    }
+    count
+}
+
+/// Replace text across every canvas, returning per-canvas replacement counts for the
+/// canvases that matched. Files are only rewritten when `dry_run` is false.
+fn replace_text_in_canvases_in(
+    base: &Path,
+    find: &str,
+    replace: &str,
+    dry_run: bool,
+) -> Result<Vec<(String, u64)>, String> {
+    if find.is_empty() {
+        return Err("Search text must not be empty".to_string());
+    }
+
+    let mut results = Vec::new();
+    for path in walk_canvases(base, base)? {
+        let full_path = base.join(&path);
+        let Ok(mut doc) = read_canvas_json(&full_path) else {
+            continue;
+        };
+        let count = replace_text_in_doc(&mut doc, find, replace);
+        if count == 0 {
+            continue;
+        }
+        if !dry_run {
+            write_canvas_json(&full_path, &doc)?;
+        }
+        results.push((path, count));
+    }
+    Ok(results)
+}
+
+#[tauri::command(async)]
+pub fn replace_text_in_canvases(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    find: String,
+    replace: String,
+    dry_run: bool,
+) -> Result<Vec<(String, u64)>, String> {
+    fs_ops.run(|| {
+        let base = get_base_dir(&app)?;
+        replace_text_in_canvases_in(&base, &find, &replace, dry_run)
+    })
+}
+
+/// Mutating filesystem operations allowed to run at once unless the settings say otherwise.
+const DEFAULT_MAX_CONCURRENT_OPS: usize = 4;
+
+/// Counting semaphore bounding how many mutating commands touch the disk at once, managed as
+/// Tauri state so bulk operations from the frontend can't thrash the disk or exhaust handles.
+pub struct FsSemaphore {
+    /// `(permits in use, limit)`
+    state: std::sync::Mutex<(usize, usize)>,
+    released: std::sync::Condvar,
+}
+
+impl Default for FsSemaphore {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_CONCURRENT_OPS)
+    }
+}
+
+impl FsSemaphore {
+    fn new(limit: usize) -> Self {
+        Self {
+            state: std::sync::Mutex::new((0, limit.max(1))),
+            released: std::sync::Condvar::new(),
+        }
+    }
+
+    fn set_limit(&self, limit: usize) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.1 = limit.max(1);
+        self.released.notify_all();
+    }
+
+    fn acquire(&self) -> FsPermit<'_> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        while state.0 >= state.1 {
+            state = self.released.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+        state.0 += 1;
+        FsPermit(self)
+    }
+
+    /// Run `op` while holding a permit, which is returned however `op` finishes.
+    fn run<T>(&self, op: impl FnOnce() -> T) -> T {
+        let _permit = self.acquire();
+        op()
+    }
+}
+
+struct FsPermit<'a>(&'a FsSemaphore);
+
+impl Drop for FsPermit<'_> {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.0 -= 1;
+        self.0.released.notify_one();
+    }
+}
+
+/// Apply the saved concurrency limit to the managed `FsSemaphore`. Called once at startup.
+pub fn configure_fs_semaphore(app: &AppHandle) {
+    if let Ok(app_data) = get_app_data_dir(app) {
+        if let Some(limit) = load_settings(&app_data).max_concurrent_ops {
+            app.state::<FsSemaphore>().set_limit(limit);
+        }
+    }
+}
+
+/// What kind of storage the vault lives on. Atomic renames and file watching are less
+/// reliable on network and removable drives, so the UI warns about those.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub enum StorageType {
+    Local,
+    Network,
+    Removable,
+    Unknown,
+}
+
+/// Classify `path` from the contents of `/proc/mounts`, using the longest matching mount point.
+#[cfg(target_os = "linux")]
+fn storage_type_from_mounts(mounts: &str, path: &Path) -> StorageType {
+    const NETWORK_FS: [&str; 10] = [
+        "nfs",
+        "nfs4",
+        "cifs",
+        "smb3",
+        "smbfs",
+        "fuse.sshfs",
+        "9p",
+        "afs",
+        "ceph",
+        "glusterfs",
+    ];
+    const REMOVABLE_FS: [&str; 4] = ["vfat", "exfat", "iso9660", "udf"];
+
+    let best = mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let _device = fields.next()?;
+            // Spaces and other separators in mount points are octal-escaped, e.g. `\040`
+            let mount_point = fields.next()?.replace("\\040", " ").replace("\\011", "\t");
+            let fs_type = fields.next()?;
+            Some((PathBuf::from(mount_point), fs_type))
+        })
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.components().count());
+
+    let Some((mount_point, fs_type)) = best else {
+        return StorageType::Unknown;
+    };
+    if NETWORK_FS.contains(&fs_type) {
+        StorageType::Network
+    } else if REMOVABLE_FS.contains(&fs_type)
+        || mount_point.starts_with("/media")
+        || mount_point.starts_with("/run/media")
+    {
+        StorageType::Removable
+    } else {
+        StorageType::Local
+    }
+}
+
+/// Map a `GetDriveTypeW` result onto `StorageType`.
+#[cfg(windows)]
+fn storage_type_from_drive_type(drive_type: u32) -> StorageType {
+    match drive_type {
+        // DRIVE_REMOVABLE, DRIVE_CDROM
+        2 | 5 => StorageType::Removable,
+        // DRIVE_FIXED, DRIVE_RAMDISK
+        3 | 6 => StorageType::Local,
+        // DRIVE_REMOTE
+        4 => StorageType::Network,
+        _ => StorageType::Unknown,
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn detect_storage_type(path: &Path) -> StorageType {
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    match fs::read_to_string("/proc/mounts") {
+        Ok(mounts) => storage_type_from_mounts(&mounts, &canonical),
+        Err(_) => StorageType::Unknown,
+    }
+}
+
+#[cfg(windows)]
+fn detect_storage_type(path: &Path) -> StorageType {
+    use std::os::windows::ffi::OsStrExt;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetDriveTypeW(root_path_name: *const u16) -> u32;
+    }
+
+    let Some(std::path::Component::Prefix(prefix)) = path.components().next() else {
+        return StorageType::Unknown;
+    };
+    let mut root: Vec<u16> = prefix.as_os_str().encode_wide().collect();
+    root.extend(['\\' as u16, 0]);
+    // SAFETY: `root` is a NUL-terminated UTF-16 string that outlives the call
+    storage_type_from_drive_type(unsafe { GetDriveTypeW(root.as_ptr()) })
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn detect_storage_type(path: &Path) -> StorageType {
+    if path.starts_with("/Volumes") {
+        StorageType::Removable
+    } else {
+        StorageType::Local
+    }
+}
+
+#[tauri::command(async)]
+pub fn vault_storage_type(app: AppHandle) -> Result<StorageType, String> {
+    let base = get_base_dir(&app)?;
+    Ok(detect_storage_type(&base))
+}
+
+/// First free sibling name for a copy of `name`: `<stem> (copy)<ext>`, then `<stem> (copy 2)<ext>`, ...
+fn copy_name_in(dir: &Path, name: &str, is_folder: bool) -> PathBuf {
+    let (stem, ext) = if is_folder {
+        (name, "")
+    } else {
+        split_name(name)
+    };
+    std::iter::once(format!("{} (copy){}", stem, ext))
+        .chain((2..).map(|n| format!("{} (copy {}){}", stem, n, ext)))
+        .map(|candidate| dir.join(candidate))
+        .find(|p| !p.exists())
+        .expect("unbounded range always yields a free name")
+}
+
+/// Recursively copy the directory `from` to the not-yet-existing `to`.
+fn copy_tree(from: &Path, to: &Path) -> Result<(), String> {
+    fs::create_dir(to).map_err(|e| e.to_string())?;
+    for entry in fs::read_dir(from).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let target = to.join(entry.file_name());
+        if entry.file_type().map_err(|e| e.to_string())?.is_dir() {
+            copy_tree(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// Copy a canvas or folder next to itself under a fresh `(copy)` name, returning the new path.
+fn duplicate_entry(base: &Path, path: &str) -> Result<String, String> {
+    if is_vault_root(path) {
+        return Err("Cannot duplicate the vault root".to_string());
+    }
+    let source = base.join(path);
+    let metadata = fs::metadata(&source).map_err(|e| e.to_string())?;
+    let name = source
+        .file_name()
+        .ok_or("Invalid path")?
+        .to_string_lossy()
+        .to_string();
+    let parent = source.parent().ok_or("Invalid path")?;
+
+    let dest = copy_name_in(parent, &name, metadata.is_dir());
+    if metadata.is_dir() {
+        copy_tree(&source, &dest)?;
+    } else {
+        fs::copy(&source, &dest).map_err(|e| e.to_string())?;
+    }
+
+    let new_path = relative_to(base, &dest);
+    let source_meta = get_icon_meta_path(base, path);
+    if source_meta.exists() {
+        let _ = fs::copy(&source_meta, get_icon_meta_path(base, &new_path));
+    }
+    let _ = append_history(base, "create", None, Some(&new_path));
+    Ok(new_path)
+}
+
+#[tauri::command(async)]
+pub fn duplicate_item(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    path: String,
+) -> Result<String, String> {
+    fs_ops.run(|| {
+        let path = normalize_relative_path(&path)?;
+        let base = get_base_dir(&app)?;
+        duplicate_entry(&base, &path)
+    })
+}
+
+/// A node of a parsed Mermaid flowchart.
+#[derive(Debug, PartialEq)]
+struct MermaidNode {
+    id: String,
+    label: String,
+    /// `"rectangle"` or `"diamond"`.
+    shape: &'static str,
+    rounded: bool,
+}
+
+/// A link between two nodes, by index into `MermaidGraph::nodes`.
+#[derive(Debug, PartialEq)]
+struct MermaidEdge {
+    from: usize,
+    to: usize,
+    label: Option<String>,
+    arrowhead: bool,
+}
+
+/// A node as referenced in a statement: its id and, when given, `(label, shape, rounded)`.
+type MermaidNodeRef = (String, Option<(String, &'static str, bool)>);
+
+/// A link as written in a statement: its optional label and whether it ends in an arrowhead.
+type MermaidLinkRef = (Option<String>, bool);
+
+/// Mermaid keywords outside the supported subset, which would otherwise parse as bare nodes.
+const MERMAID_UNSUPPORTED_KEYWORDS: [&str; 8] = [
+    "subgraph",
+    "end",
+    "direction",
+    "classDef",
+    "class",
+    "style",
+    "linkStyle",
+    "click",
+];
+
+#[derive(Debug, PartialEq)]
+struct MermaidGraph {
+    /// `TD`/`TB` lay ranks out top to bottom, `LR` left to right.
+    vertical: bool,
+    nodes: Vec<MermaidNode>,
+    edges: Vec<MermaidEdge>,
+}
+
+impl MermaidGraph {
+    /// Index of node `id`, registering it on first sight. A later shape or label fills in
+    /// one that was referenced bare, as in `A --> B` followed by `B{Done?}`.
+    fn node(&mut self, parsed: MermaidNodeRef) -> usize {
+        let (id, shape) = parsed;
+        let index = match self.nodes.iter().position(|n| n.id == id) {
+            Some(index) => index,
+            None => {
+                self.nodes.push(MermaidNode {
+                    label: id.clone(),
+                    id,
+                    shape: "rectangle",
+                    rounded: false,
+                });
+                self.nodes.len() - 1
+            }
+        };
+        if let Some((label, shape, rounded)) = shape {
+            let node = &mut self.nodes[index];
+            node.label = label;
+            node.shape = shape;
+            node.rounded = rounded;
+        }
+        index
+    }
+}
+
+/// Split a node reference such as `A`, `A[Label]`, `A(Label)` or `A{Label}` off the front of `s`.
+fn parse_mermaid_node(s: &str) -> Option<(MermaidNodeRef, &str)> {
+    let s = s.trim_start();
+    let id_len = s
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(s.len());
+    if id_len == 0 {
+        return None;
+    }
+    let (id, rest) = s.split_at(id_len);
+
+    let (close, shape, rounded) = match rest.chars().next() {
+        Some('[') => (']', "rectangle", false),
+        Some('(') => (')', "rectangle", true),
+        Some('{') => ('}', "diamond", false),
+        _ => return Some(((id.to_string(), None), rest)),
+    };
+    let end = rest.find(close)?;
+    let label = rest[1..end].trim().trim_matches('"').to_string();
+    Some((
+        (id.to_string(), Some((label, shape, rounded))),
+        &rest[end + 1..],
+    ))
+}
+
+/// Split a link (`-->` or `---`, optionally followed by `|label|`) off the front of `s`.
+fn parse_mermaid_link(s: &str) -> Option<(MermaidLinkRef, &str)> {
+    let s = s.trim_start();
+    let (arrowhead, rest) = if let Some(rest) = s.strip_prefix("-->") {
+        (true, rest)
+    } else {
+        (false, s.strip_prefix("---")?)
+    };
+
+    let Some(labelled) = rest.trim_start().strip_prefix('|') else {
+        return Some(((None, arrowhead), rest));
+    };
+    let end = labelled.find('|')?;
+    let label = labelled[..end].trim().to_string();
+    Some(((Some(label), arrowhead), &labelled[end + 1..]))
+}
+
+/// Parse one statement: a node followed by any number of `link node` steps.
+fn parse_mermaid_statement(
+    statement: &str,
+) -> Option<(MermaidNodeRef, Vec<(MermaidLinkRef, MermaidNodeRef)>)> {
+    let keyword = statement.split_whitespace().next()?;
+    if MERMAID_UNSUPPORTED_KEYWORDS.contains(&keyword) {
+        return None;
+    }
+
+    let (first, mut rest) = parse_mermaid_node(statement)?;
+    let mut steps = Vec::new();
+    while !rest.trim().is_empty() {
+        let (link, after_link) = parse_mermaid_link(rest)?;
+        let (node, after_node) = parse_mermaid_node(after_link)?;
+        steps.push((link, node));
+        rest = after_node;
+    }
+    Some((first, steps))
+}
+
+/// Parse the supported subset of Mermaid flowchart syntax: a `flowchart`/`graph` header with
+/// a `TD`, `TB` or `LR` direction, then node declarations and (chained) `-->`/`---` links.
+/// Every line outside that subset is reported in the error.
+fn parse_mermaid_flowchart(src: &str) -> Result<MermaidGraph, String> {
+    let mut lines = src
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with("%%"));
+
+    let (_, header) = lines.next().ok_or("Mermaid source is empty")?;
+    let mut words = header.split_whitespace();
+    let vertical = match (words.next(), words.next(), words.next()) {
+        (Some("flowchart" | "graph"), Some("TD" | "TB") | None, None) => true,
+        (Some("flowchart" | "graph"), Some("LR"), None) => false,
+        _ => return Err(format!("Unsupported Mermaid diagram header: {}", header)),
+    };
+
+    let mut graph = MermaidGraph {
+        vertical,
+        nodes: Vec::new(),
+        edges: Vec::new(),
+    };
+    let mut unsupported = Vec::new();
+    for (line_no, line) in lines {
+        for statement in line.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+            let Some((first, steps)) = parse_mermaid_statement(statement) else {
+                unsupported.push(format!("line {}: {}", line_no, statement));
+                continue;
+            };
+            let mut from = graph.node(first);
+            for ((label, arrowhead), node) in steps {
+                let to = graph.node(node);
+                graph.edges.push(MermaidEdge {
+                    from,
+                    to,
+                    label,
+                    arrowhead,
+                });
+                from = to;
+            }
+        }
+    }
+
+    if !unsupported.is_empty() {
+        return Err(format!(
+            "Unsupported Mermaid syntax: {}",
+            unsupported.join("; ")
+        ));
+    }
+    Ok(graph)
+}
+
+/// Lay out a parsed flowchart in ranks along its direction and build the canvas, with
+/// labels bound to their shapes and arrows bound to the nodes they connect.
+fn mermaid_canvas(graph: &MermaidGraph) -> serde_json::Value {
+    const RANK_GAP: f64 = 140.0;
+    const SIBLING_GAP: f64 = 220.0;
+    const NODE_HEIGHT: f64 = 60.0;
+
+    // Longest-path ranks, capped at the node count so cycles terminate
+    let node_count = graph.nodes.len();
+    let mut ranks = vec![0usize; node_count];
+    for _ in 0..node_count {
+        for edge in &graph.edges {
+            let next = ranks[edge.from] + 1;
+            if next > ranks[edge.to] && next < node_count {
+                ranks[edge.to] = next;
+            }
+        }
+    }
+
+    let mut per_rank = vec![0usize; node_count.max(1)];
+    let mut boxes = Vec::with_capacity(node_count);
+    let mut elements = Vec::new();
+    for (i, node) in graph.nodes.iter().enumerate() {
+        let slot = per_rank[ranks[i]];
+        per_rank[ranks[i]] += 1;
+        let width = (node.label.chars().count() as f64 * 12.0 + 40.0).max(120.0);
+        let height = if node.shape == "diamond" {
+            NODE_HEIGHT * 1.5
+        } else {
+            NODE_HEIGHT
+        };
+        let (along, across) = (ranks[i] as f64 * RANK_GAP, slot as f64 * SIBLING_GAP);
+        let (x, y) = if graph.vertical {
+            (across, along)
+        } else {
+            (along * 1.5, across / 2.0)
+        };
+        boxes.push((x, y, width, height));
+
+        let shape_id = format!("mermaid-node-{}", node.id);
+        let label_id = format!("mermaid-label-{}", node.id);
+        let mut shape = base_element(&shape_id, node.shape, x, y, width, height);
+        if node.rounded {
+            shape["roundness"] = serde_json::json!({"type": 3});
+        }
+        shape["boundElements"] = serde_json::json!([{"type": "text", "id": label_id}]);
+        elements.push(shape);
+        elements.push(bound_label(
+            &label_id,
+            &shape_id,
+            (x, y, width, height),
+            &node.label,
+        ));
+    }
+
+    for (i, edge) in graph.edges.iter().enumerate() {
+        let arrow_id = format!("mermaid-edge-{}", i);
+        let (fx, fy, fw, fh) = boxes[edge.from];
+        let (tx, ty, tw, th) = boxes[edge.to];
+        let (start, end) = if graph.vertical {
+            ((fx + fw / 2.0, fy + fh), (tx + tw / 2.0, ty))
+        } else {
+            ((fx + fw, fy + fh / 2.0), (tx, ty + th / 2.0))
+        };
+        let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+
+        let mut arrow = base_element(&arrow_id, "arrow", start.0, start.1, dx.abs(), dy.abs());
+        let fields = arrow.as_object_mut().expect("base_element is an object");
+        fields.insert("points".into(), serde_json::json!([[0, 0], [dx, dy]]));
+        fields.insert("lastCommittedPoint".into(), serde_json::Value::Null);
+        fields.insert("roundness".into(), serde_json::json!({"type": 2}));
+        for (key, node) in [("startBinding", edge.from), ("endBinding", edge.to)] {
+            let binding = serde_json::json!({
+                "elementId": format!("mermaid-node-{}", graph.nodes[node].id),
+                "focus": 0,
+                "gap": 4,
+            });
+            fields.insert(key.into(), binding);
+        }
+        fields.insert("startArrowhead".into(), serde_json::Value::Null);
+        let end_arrowhead = if edge.arrowhead {
+            "arrow".into()
+        } else {
+            serde_json::Value::Null
+        };
+        fields.insert("endArrowhead".into(), end_arrowhead);
+
+        for node in [edge.from, edge.to] {
+            // Each node pushed its shape and then its label, so shapes sit at even indices
+            let shape_index = node * 2;
+            if let Some(bound) = elements[shape_index]["boundElements"].as_array_mut() {
+                bound.push(serde_json::json!({"type": "arrow", "id": arrow_id}));
+            }
+        }
+        if let Some(label) = &edge.label {
+            let label_id = format!("mermaid-edge-label-{}", i);
+            arrow["boundElements"] = serde_json::json!([{"type": "text", "id": label_id}]);
+            let midpoint = (start.0 + dx / 2.0, start.1 + dy / 2.0, 0.0, 0.0);
+            elements.push(arrow);
+            elements.push(bound_label(&label_id, &arrow_id, midpoint, label));
+        } else {
+            elements.push(arrow);
+        }
+    }
+
+    canvas_document(elements, serde_json::json!({}))
+}
+
+/// A centred text element bound to the container `container_id` occupying `(x, y, width, height)`.
+fn bound_label(
+    id: &str,
+    container_id: &str,
+    (x, y, width, height): (f64, f64, f64, f64),
+    text: &str,
+) -> serde_json::Value {
+    let mut label = text_element(id, x, y, text);
+    let text_width = label["width"].as_f64().unwrap_or(0.0);
+    let text_height = label["height"].as_f64().unwrap_or(0.0);
+    label["x"] = (x + (width - text_width) / 2.0).into();
+    label["y"] = (y + (height - text_height) / 2.0).into();
+    label["containerId"] = container_id.into();
+    label["textAlign"] = "center".into();
+    label["verticalAlign"] = "middle".into();
+    label
+}
+
+fn create_canvas_from_mermaid_in(
+    base: &Path,
+    mermaid_src: &str,
+    dest_path: &str,
+) -> Result<(), String> {
+    let dest = base.join(dest_path);
+    if dest.exists() {
+        return Err("Destination file already exists".to_string());
+    }
+
+    let graph = parse_mermaid_flowchart(mermaid_src)?;
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    write_canvas_json(&dest, &mermaid_canvas(&graph))?;
+    let _ = append_history(base, "create", None, Some(dest_path));
+    Ok(())
+}
+
+#[tauri::command(async)]
+pub fn create_canvas_from_mermaid(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    mermaid_src: String,
+    dest_path: String,
+) -> Result<(), String> {
+    fs_ops.run(|| {
+        let dest_path = normalize_relative_path(&dest_path)?;
+        let base = get_base_dir(&app)?;
+        create_canvas_from_mermaid_in(&base, &mermaid_src, &dest_path)
+    })
+}
+
+/// `dir` with symlinks resolved, as a string. Errors if it doesn't exist.
+fn canonical_dir_string(dir: &Path) -> Result<String, String> {
+    if !dir.is_dir() {
+        return Err(format!("Directory does not exist: {}", dir.display()));
+    }
+    let canonical = dir.canonicalize().map_err(|e| e.to_string())?;
+    Ok(canonical.to_string_lossy().to_string())
+}
+
+#[tauri::command(async)]
+pub fn get_base_directory_canonical(app: AppHandle) -> Result<String, String> {
+    canonical_dir_string(&get_base_dir(&app)?)
+}
+
+#[tauri::command(async)]
+pub fn move_item(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    source: String,
+    dest_dir: String,
+) -> Result<String, String> {
+    fs_ops.run(|| {
+        let source = normalize_relative_path(&source)?;
+        let dest_dir = normalize_relative_path(&dest_dir)?;
+        let base = get_base_dir(&app)?;
+        let trash = get_trash_dir(&app)?;
+
+        let new_path = move_item_into(&base, &source, &dest_dir, false)?;
+        let _ = retarget_trash_metas(&trash, &source, &new_path);
+        emit_folder_renamed(&app, &base, &source, &new_path);
+        Ok(new_path)
+    })
+}
+
+/// Folder names used more than once in the vault, each with the paths of every folder bearing it.
+fn find_duplicate_folder_names_in(base: &Path) -> Result<Vec<(String, Vec<String>)>, String> {
+    let mut by_name: std::collections::BTreeMap<String, Vec<String>> = Default::default();
+    walk_discovered(base, base, &mut |item| {
+        if item.is_folder {
+            let name = item
+                .path
+                .rsplit('/')
+                .next()
+                .unwrap_or(&item.path)
+                .to_string();
+            by_name.entry(name).or_default().push(item.path);
+        }
+    })?;
+
+    Ok(by_name
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|(name, mut paths)| {
+            paths.sort();
+            (name, paths)
+        })
+        .collect())
+}
+
+#[tauri::command(async)]
+pub fn find_duplicate_folder_names(app: AppHandle) -> Result<Vec<(String, Vec<String>)>, String> {
+    let base = get_base_dir(&app)?;
+    find_duplicate_folder_names_in(&base)
+}
+
+/// Every canvas in the vault whose file name contains `query`, ignoring case. Folder names are
+/// not matched, so a hit is always the canvas itself.
+fn search_canvases_in(base: &Path, query: &str) -> Result<Vec<FileItem>, String> {
+    let query = query.to_lowercase();
+    let opened = load_opened(base);
+    walk_canvases(base, base)?
+        .into_iter()
+        .filter(|path| {
+            let name = path.rsplit('/').next().unwrap_or(path);
+            name.to_lowercase().contains(&query)
+        })
+        .map(|path| canvas_file_item(base, &path, &opened))
+        .collect()
+}
+
+#[tauri::command(async)]
+pub fn search_canvases(app: AppHandle, query: String) -> Result<Vec<FileItem>, String> {
+    let base = get_base_dir(&app)?;
+    search_canvases_in(&base, &query)
+}
+
+/// Page size for `export_pdf`. `Fit` sizes the page to the drawing; paper sizes scale the
+/// drawing down to fit, turning the page to landscape for wide drawings.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PdfPageSize {
+    #[default]
+    Fit,
+    A4,
+    Letter,
+}
+
+impl PdfPageSize {
+    /// Portrait page dimensions in points, or `None` to size the page to the content.
+    fn points(self) -> Option<(f64, f64)> {
+        match self {
+            PdfPageSize::Fit => None,
+            PdfPageSize::A4 => Some((A4_INCHES.0 * 72.0, A4_INCHES.1 * 72.0)),
+            PdfPageSize::Letter => Some((612.0, 792.0)),
+        }
+    }
+}
+
+/// `#rgb` / `#rrggbb` as 0–1 components; `None` for `transparent` and anything unparseable.
+fn parse_hex_color(color: &str) -> Option<(f32, f32, f32)> {
+    let hex = color.strip_prefix('#')?;
+    let expanded: String = match hex.len() {
+        3 => hex.chars().flat_map(|c| [c, c]).collect(),
+        6 => hex.to_string(),
+        _ => return None,
+    };
+    let channel = |i: usize| {
+        u8::from_str_radix(&expanded[i..i + 2], 16)
+            .ok()
+            .map(|v| f32::from(v) / 255.0)
+    };
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Outline of a shape or stroke in canvas coordinates, rotated by the element's `angle`,
+/// and whether the path is closed. `None` for text, images and unknown types.
+fn element_outline(el: &serde_json::Value) -> Option<(Vec<(f64, f64)>, bool)> {
+    let num = |key: &str| el.get(key).and_then(|v| v.as_f64()).unwrap_or(0.0);
+    let (x, y, w, h) = (num("x"), num("y"), num("width"), num("height"));
+
+    let (points, closed) = match el.get("type").and_then(|v| v.as_str())? {
+        "rectangle" => (vec![(x, y), (x + w, y), (x + w, y + h), (x, y + h)], true),
+        "diamond" => (
+            vec![
+                (x + w / 2.0, y),
+                (x + w, y + h / 2.0),
+                (x + w / 2.0, y + h),
+                (x, y + h / 2.0),
+            ],
+            true,
+        ),
+        "ellipse" => {
+            const SEGMENTS: usize = 48;
+            let (cx, cy) = (x + w / 2.0, y + h / 2.0);
+            let points = (0..SEGMENTS)
+                .map(|i| {
+                    let t = i as f64 / SEGMENTS as f64 * std::f64::consts::TAU;
+                    (cx + w / 2.0 * t.cos(), cy + h / 2.0 * t.sin())
+                })
+                .collect();
+            (points, true)
+        }
+        "line" | "arrow" | "freedraw" => {
+            let points = el
+                .get("points")?
+                .as_array()?
+                .iter()
+                .filter_map(|p| Some((x + p.get(0)?.as_f64()?, y + p.get(1)?.as_f64()?)))
+                .collect();
+            (points, false)
+        }
+        _ => return None,
+    };
+
+    let angle = num("angle");
+    if angle == 0.0 {
+        return Some((points, closed));
+    }
+    let (cx, cy) = (x + w / 2.0, y + h / 2.0);
+    let (sin, cos) = angle.sin_cos();
+    let rotated = points
+        .into_iter()
+        .map(|(px, py)| {
+            let (dx, dy) = (px - cx, py - cy);
+            (cx + dx * cos - dy * sin, cy + dx * sin + dy * cos)
+        })
+        .collect();
+    Some((rotated, closed))
+}
+
+/// The two strokes of an arrowhead at the end of `points`, in canvas coordinates.
+fn arrowhead(points: &[(f64, f64)]) -> Option<[[(f64, f64); 2]; 2]> {
+    const LENGTH: f64 = 15.0;
+    const SPREAD: f64 = 0.45;
+
+    let (&tip, rest) = points.split_last()?;
+    let &from = rest.last()?;
+    let direction = (from.1 - tip.1).atan2(from.0 - tip.0);
+    let wing = |offset: f64| {
+        let a = direction + offset;
+        [tip, (tip.0 + LENGTH * a.cos(), tip.1 + LENGTH * a.sin())]
+    };
+    Some([wing(SPREAD), wing(-SPREAD)])
+}
+
+/// What `export_pdf` had to leave off the page, by element id, so the UI can warn about it.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct PdfExportReport {
+    /// Text elements with characters the built-in font cannot show.
+    #[serde(rename = "skippedText")]
+    pub skipped_text: Vec<String>,
+    /// Image elements, which are not rendered.
+    #[serde(rename = "skippedImages")]
+    pub skipped_images: Vec<String>,
+}
+
+/// Whether the built-in PDF fonts can show `text`: their WinAnsi (Windows-1252) encoding
+/// drops anything else, so the text must survive an encode/decode round trip.
+fn pdf_builtin_font_covers(text: &str) -> bool {
+    use printpdf::lopdf::Document;
+
+    let encoded = Document::encode_text(Some("WinAnsiEncoding"), text);
+    Document::decode_text(Some("WinAnsiEncoding"), &encoded) == text
+}
+
+/// Render the live elements of a canvas as vector shapes and text on a single PDF page.
+/// Text uses the built-in Helvetica font, which only covers Windows-1252 characters; text it
+/// cannot show and images are left out and listed in the returned report.
+fn canvas_pdf(
+    doc: &serde_json::Value,
+    page_size: PdfPageSize,
+) -> Result<(Vec<u8>, PdfExportReport), String> {
+    use printpdf::path::{PaintMode, WindingOrder};
+    use printpdf::{BuiltinFont, Color, Line, Mm, PdfDocument, Point, Polygon, Pt, Rgb};
+
+    /// Canvas pixels are CSS pixels: 96 per inch, against 72 points per inch.
+    const PX_TO_PT: f64 = 0.75;
+    const MARGIN_PT: f64 = 24.0;
+
+    let (min_x, min_y, max_x, max_y) = content_bounds(doc).unwrap_or((0.0, 0.0, 0.0, 0.0));
+    let (content_w, content_h) = ((max_x - min_x) * PX_TO_PT, (max_y - min_y) * PX_TO_PT);
+    let (page_w, page_h, scale) = match page_size.points() {
+        None => (
+            content_w + 2.0 * MARGIN_PT,
+            content_h + 2.0 * MARGIN_PT,
+            PX_TO_PT,
+        ),
+        Some((short, long)) => {
+            let (page_w, page_h) = if content_w > content_h {
+                (long, short)
+            } else {
+                (short, long)
+            };
+            let fit = ((page_w - 2.0 * MARGIN_PT) / content_w.max(1.0))
+                .min((page_h - 2.0 * MARGIN_PT) / content_h.max(1.0))
+                .min(1.0);
+            (page_w, page_h, PX_TO_PT * fit)
+        }
+    };
+    // Canvas y grows downwards, PDF y upwards from the bottom of the page
+    let to_pdf = |(x, y): (f64, f64)| Point {
+        x: Pt((MARGIN_PT + (x - min_x) * scale) as f32),
+        y: Pt((page_h - MARGIN_PT - (y - min_y) * scale) as f32),
+    };
+
+    let (pdf, page, layer) = PdfDocument::new(
+        "Excalidraw canvas",
+        Mm::from(Pt(page_w as f32)),
+        Mm::from(Pt(page_h as f32)),
+        "Canvas",
+    );
+    let layer = pdf.get_page(page).get_layer(layer);
+    let font = pdf
+        .add_builtin_font(BuiltinFont::Helvetica)
+        .map_err(|e| e.to_string())?;
+    let rgb = |(r, g, b): (f32, f32, f32)| Color::Rgb(Rgb::new(r, g, b, None));
+
+    let mut report = PdfExportReport::default();
+    for el in live_elements(doc) {
+        let id = || {
+            el.get("id")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string()
+        };
+        let stroke = el
+            .get("strokeColor")
+            .and_then(|v| v.as_str())
+            .and_then(parse_hex_color)
+            .unwrap_or((0.0, 0.0, 0.0));
+        layer.set_outline_color(rgb(stroke));
+        layer.set_fill_color(rgb(stroke));
+        let stroke_width = el
+            .get("strokeWidth")
+            .and_then(|v| v.as_f64())
+            .unwrap_or(1.0);
+        layer.set_outline_thickness((stroke_width * scale) as f32);
+
+        if el.get("type").and_then(|v| v.as_str()) == Some("image") {
+            report.skipped_images.push(id());
+            continue;
+        }
+        if el.get("type").and_then(|v| v.as_str()) == Some("text") {
+            let text = el.get("text").and_then(|v| v.as_str()).unwrap_or_default();
+            if !pdf_builtin_font_covers(text) {
+                report.skipped_text.push(id());
+                continue;
+            }
+            let font_size = el.get("fontSize").and_then(|v| v.as_f64()).unwrap_or(20.0);
+            let line_height = el
+                .get("lineHeight")
+                .and_then(|v| v.as_f64())
+                .unwrap_or(1.25);
+            let x = el.get("x").and_then(|v| v.as_f64()).unwrap_or(0.0);
+            let y = el.get("y").and_then(|v| v.as_f64()).unwrap_or(0.0);
+            for (i, line) in text.lines().enumerate() {
+                let baseline = to_pdf((x, y + font_size * (line_height * i as f64 + 1.0)));
+                layer.use_text(
+                    line,
+                    (font_size * scale) as f32,
+                    Mm::from(baseline.x),
+                    Mm::from(baseline.y),
+                    &font,
+                );
+            }
+            continue;
+        }
+
+        let Some((points, closed)) = element_outline(el) else {
+            continue;
+        };
+        let path: Vec<(Point, bool)> = points.iter().map(|&p| (to_pdf(p), false)).collect();
+        if closed {
+            let fill = el
+                .get("backgroundColor")
+                .and_then(|v| v.as_str())
+                .and_then(parse_hex_color);
+            if let Some(fill) = fill {
+                layer.set_fill_color(rgb(fill));
+            }
+            layer.add_polygon(Polygon {
+                rings: vec![path],
+                mode: if fill.is_some() {
+                    PaintMode::FillStroke
+                } else {
+                    PaintMode::Stroke
+                },
+                winding_order: WindingOrder::NonZero,
+            });
+        } else {
+            layer.add_line(Line {
+                points: path,
+                is_closed: false,
+            });
+            let has_arrowhead = el.get("type").and_then(|v| v.as_str()) == Some("arrow")
+                && el.get("endArrowhead").is_some_and(|v| !v.is_null());
+            if has_arrowhead {
+                for wing in arrowhead(&points).into_iter().flatten() {
+                    layer.add_line(Line {
+                        points: wing.iter().map(|&p| (to_pdf(p), false)).collect(),
+                        is_closed: false,
+                    });
+                }
+            }
+        }
+    }
+
+    let bytes = pdf.save_to_bytes().map_err(|e| e.to_string())?;
+    Ok((bytes, report))
+}
+
+#[tauri::command(async)]
+pub fn export_pdf(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    path: String,
+    dest_absolute_path: String,
+    page_size: Option<PdfPageSize>,
+) -> Result<PdfExportReport, String> {
+    fs_ops.run(|| {
+        let path = normalize_relative_path(&path)?;
+        let base = get_base_dir(&app)?;
+        let dest = PathBuf::from(&dest_absolute_path);
+
+        if !dest.is_absolute() {
+            return Err("PDF destination must be an absolute path".to_string());
+        }
+
+        let doc = read_canvas_json(&base.join(&path))?;
+        let (pdf, report) = canvas_pdf(&doc, page_size.unwrap_or_default())?;
+        fs::write(&dest, pdf).map_err(|e| e.to_string())?;
+        Ok(report)
+    })
+}
+
+/// A canvas whose text elements match a `search_canvas_text` query.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SearchHit {
+    pub path: String,
+    /// Full text of each matching text element, in z-order.
+    pub snippets: Vec<String>,
+}
+
+/// Canvases with live text elements containing `query`, ignoring case. Canvases that
+/// cannot be read or parsed are skipped.
+fn search_canvas_text_in(base: &Path, query: &str) -> Result<Vec<SearchHit>, String> {
+    let query = query.to_lowercase();
+    let mut hits = Vec::new();
+    for path in walk_canvases(base, base)? {
+        let Ok(doc) = read_canvas_json(&base.join(&path)) else {
+            continue;
+        };
+        let snippets: Vec<String> = live_elements(&doc)
+            .filter(|el| el.get("type").and_then(|v| v.as_str()) == Some("text"))
+            .filter_map(|el| el.get("text").and_then(|v| v.as_str()))
+            .filter(|text| text.to_lowercase().contains(&query))
+            .map(str::to_string)
+            .collect();
+        if !snippets.is_empty() {
+            hits.push(SearchHit { path, snippets });
+        }
+    }
+    Ok(hits)
+}
+
+#[tauri::command(async)]
+pub fn search_canvas_text(app: AppHandle, query: String) -> Result<Vec<SearchHit>, String> {
+    let base = get_base_dir(&app)?;
+    search_canvas_text_in(&base, &query)
+}
+
+/// Drop `boundElements` entries and null out `containerId` / `frameId` references that point
+/// to elements missing from the canvas (or deleted). Returns how many references were removed.
+fn fix_dangling_bindings_in_doc(doc: &mut serde_json::Value) -> usize {
+    let live: std::collections::HashSet<String> = live_elements(doc)
+        .filter_map(|el| el.get("id").and_then(|v| v.as_str()))
+        .map(str::to_string)
+        .collect();
+    let Some(elements) = doc.get_mut("elements").and_then(|v| v.as_array_mut()) else {
+        return 0;
+    };
+
+    let mut fixed = 0;
+    for el in elements {
+        if let Some(bound) = el.get_mut("boundElements").and_then(|v| v.as_array_mut()) {
+            let before = bound.len();
+            bound.retain(|b| {
+                b.get("id")
+                    .and_then(|v| v.as_str())
+                    .is_some_and(|id| live.contains(id))
+            });
+            fixed += before - bound.len();
+        }
+        for key in ["containerId", "frameId"] {
+            let dangling = el
+                .get(key)
+                .and_then(|v| v.as_str())
+                .is_some_and(|id| !live.contains(id));
+            if dangling {
+                el[key] = serde_json::Value::Null;
+                fixed += 1;
+            }
+        }
+    }
+    fixed
+}
+
+fn fix_dangling_bindings_in(base: &Path, path: &str) -> Result<usize, String> {
+    let full_path = base.join(path);
+    let mut doc = read_canvas_json(&full_path)?;
+    let fixed = fix_dangling_bindings_in_doc(&mut doc);
+    if fixed > 0 {
+        write_canvas_json(&full_path, &doc)?;
+    }
+    Ok(fixed)
+}
+
+#[tauri::command(async)]
+pub fn fix_dangling_bindings(
+    app: AppHandle,
+    fs_ops: tauri::State<'_, FsSemaphore>,
+    path: String,
+) -> Result<usize, String> {
+    fs_ops.run(|| {
+        let path = normalize_relative_path(&path)?;
+        let base = get_base_dir(&app)?;
+        fix_dangling_bindings_in(&base, &path)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    // ──────────────────────────────────────────────
+    // safe_relative_path のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn safe_relative_path_空文字は許可する() {
+        assert!(safe_relative_path("").is_ok());
+    }
+
+    #[test]
+    fn safe_relative_path_通常のファイル名は許可する() {
+        assert!(safe_relative_path("test.excalidraw").is_ok());
+    }
+
+    #[test]
+    fn safe_relative_path_ネストパスは許可する() {
+        assert!(safe_relative_path("folder/test.excalidraw").is_ok());
+    }
+
+    #[test]
+    fn safe_relative_path_深くネストされたパスは許可する() {
+        assert!(safe_relative_path("a/b/c/d/test.excalidraw").is_ok());
+    }
+
+    #[test]
+    fn safe_relative_path_親ディレクトリ参照を拒否する() {
+        let err = safe_relative_path("../secret").unwrap_err();
+        assert!(err.contains("パストラバーサル"), "expected traversal error, got: {err}");
+    }
+
+    #[test]
+    fn safe_relative_path_中間の親ディレクトリ参照を拒否する() {
+        let err = safe_relative_path("folder/../etc/passwd").unwrap_err();
+        assert!(err.contains("パストラバーサル"), "expected traversal error, got: {err}");
+    }
+
+    #[test]
+    fn safe_relative_path_絶対パスを拒否する_ルートスラッシュ() {
+        // Unix: /etc/passwd
+        let result = safe_relative_path("/etc/passwd");
+        assert!(result.is_err(), "absolute path should be rejected");
+    }
+
+    #[test]
+    fn safe_relative_path_複数の親ディレクトリ参照を拒否する() {
+        let err = safe_relative_path("../../etc/shadow").unwrap_err();
+        assert!(err.contains("パストラバーサル"), "expected traversal error, got: {err}");
+    }
+
+    // ──────────────────────────────────────────────
     // collect_items のテスト
     // ──────────────────────────────────────────────
 
-    fn make_file(dir: &std::path::Path, name: &str) {
-        fs::write(dir.join(name), "dummy").unwrap();
+    fn make_file(dir: &std::path::Path, name: &str) {
+        fs::write(dir.join(name), "dummy").unwrap();
+    }
+
+    fn make_dir(dir: &std::path::Path, name: &str) -> PathBuf {
+        let p = dir.join(name);
+        fs::create_dir_all(&p).unwrap();
+        p
+    }
+
+    #[test]
+    fn collect_items_空ディレクトリは空ベクタを返す() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        let result = collect_items(&base, &base).unwrap();
+        assert!(result.is_empty());
+    }
+
+    #[test]
+    fn collect_items_excalidrawファイルのみ収集する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        make_file(&base, "canvas.excalidraw");
+        make_file(&base, "README.md");
+        make_file(&base, "image.png");
+
+        let result = collect_items(&base, &base).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].name, "canvas.excalidraw");
+        assert!(!result[0].is_folder);
+    }
+
+    #[test]
+    fn collect_items_ドットで始まるファイルをスキップする() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        make_file(&base, ".hidden.excalidraw");
+        make_file(&base, "visible.excalidraw");
+
+        let result = collect_items(&base, &base).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].name, "visible.excalidraw");
+    }
+
+    #[test]
+    fn collect_items_ドットで始まるフォルダをスキップする() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        make_dir(&base, ".git");
+        make_dir(&base, "myFolder");
+
+        let result = collect_items(&base, &base).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].name, "myFolder");
+        assert!(result[0].is_folder);
+    }
+
+    #[test]
+    fn collect_items_フォルダはファイルより先に並ぶ() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        make_file(&base, "zzz.excalidraw");
+        make_dir(&base, "aaa");
+
+        let result = collect_items(&base, &base).unwrap();
+        assert_eq!(result.len(), 2);
+        assert!(result[0].is_folder, "folder should come first");
+        assert!(!result[1].is_folder, "file should come second");
+    }
+
+    #[test]
+    fn collect_items_同種アイテムはアルファベット順で並ぶ() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        make_file(&base, "zzz.excalidraw");
+        make_file(&base, "aaa.excalidraw");
+        make_file(&base, "mmm.excalidraw");
+
+        let result = collect_items(&base, &base).unwrap();
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].name, "aaa.excalidraw");
+        assert_eq!(result[1].name, "mmm.excalidraw");
+        assert_eq!(result[2].name, "zzz.excalidraw");
+    }
+
+    #[test]
+    fn collect_items_アルファベット順は大文字小文字を区別しない() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        make_file(&base, "Banana.excalidraw");
+        make_file(&base, "apple.excalidraw");
+
+        let result = collect_items(&base, &base).unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].name.to_lowercase(), "apple.excalidraw");
+        assert_eq!(result[1].name.to_lowercase(), "banana.excalidraw");
+    }
+
+    #[test]
+    fn collect_items_フォルダ内のファイルを再帰的に収集する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        let sub = make_dir(&base, "subFolder");
+        make_file(&sub, "child.excalidraw");
+
+        let result = collect_items(&base, &base).unwrap();
+        assert_eq!(result.len(), 1);
+        assert!(result[0].is_folder);
+        assert_eq!(result[0].name, "subFolder");
+        let children = result[0].children.as_ref().unwrap();
+        assert_eq!(children.len(), 1);
+        assert_eq!(children[0].name, "child.excalidraw");
+    }
+
+    #[test]
+    fn collect_items_相対パスはスラッシュ区切りになる() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        let sub = make_dir(&base, "folder");
+        make_file(&sub, "canvas.excalidraw");
+
+        let result = collect_items(&base, &base).unwrap();
+        let children = result[0].children.as_ref().unwrap();
+        assert_eq!(children[0].path, "folder/canvas.excalidraw");
+    }
+
+    #[test]
+    fn collect_items_空のフォルダもchildren空ベクタで収集する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        make_dir(&base, "emptyFolder");
+
+        let result = collect_items(&base, &base).unwrap();
+        assert_eq!(result.len(), 1);
+        assert!(result[0].is_folder);
+        assert_eq!(result[0].name, "emptyFolder");
+        let children = result[0].children.as_ref().unwrap();
+        assert!(children.is_empty());
+    }
+
+    #[test]
+    fn collect_items_複数階層の再帰が正しく動く() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        let a = make_dir(&base, "a");
+        let b = make_dir(&a, "b");
+        make_file(&b, "deep.excalidraw");
+
+        let result = collect_items(&base, &base).unwrap();
+        assert_eq!(result.len(), 1); // a
+        let a_children = result[0].children.as_ref().unwrap();
+        assert_eq!(a_children.len(), 1); // b
+        let b_children = a_children[0].children.as_ref().unwrap();
+        assert_eq!(b_children.len(), 1); // deep.excalidraw
+        assert_eq!(b_children[0].name, "deep.excalidraw");
+        assert_eq!(b_children[0].path, "a/b/deep.excalidraw");
+    }
+
+    #[test]
+    fn collect_items_フォルダとファイルが混在するとき正しくソートする() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        make_file(&base, "z-file.excalidraw");
+        make_dir(&base, "m-folder");
+        make_file(&base, "a-file.excalidraw");
+        make_dir(&base, "z-folder");
+
+        let result = collect_items(&base, &base).unwrap();
+        assert_eq!(result.len(), 4);
+        // フォルダが先、アルファベット順
+        assert_eq!(result[0].name, "m-folder");
+        assert!(result[0].is_folder);
+        assert_eq!(result[1].name, "z-folder");
+        assert!(result[1].is_folder);
+        // ファイルが後、アルファベット順
+        assert_eq!(result[2].name, "a-file.excalidraw");
+        assert!(!result[2].is_folder);
+        assert_eq!(result[3].name, "z-file.excalidraw");
+        assert!(!result[3].is_folder);
+    }
+
+    // ──────────────────────────────────────────────
+    // format_info のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn format_info_標準のキャンバスは期待値と一致する() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("canvas.excalidraw");
+        fs::write(
+            &path,
+            r#"{"type":"excalidraw","version":2,"source":"excalidrauri","elements":[]}"#,
+        )
+        .unwrap();
+
+        let info = format_info(&read_canvas_json(&path).unwrap());
+        assert_eq!(info.format_type.as_deref(), Some("excalidraw"));
+        assert_eq!(info.version, Some(2));
+        assert_eq!(info.source.as_deref(), Some("excalidrauri"));
+        assert!(info.is_expected_type);
+        assert!(info.is_expected_version);
+        assert!(info.is_native_source);
+    }
+
+    #[test]
+    fn format_info_想定外のバージョンとsource欠落を報告する() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("old.excalidraw");
+        fs::write(&path, r#"{"type":"excalidraw","version":1,"elements":[]}"#).unwrap();
+
+        let info = format_info(&read_canvas_json(&path).unwrap());
+        assert_eq!(info.version, Some(1));
+        assert!(info.source.is_none());
+        assert!(info.is_expected_type);
+        assert!(!info.is_expected_version);
+        assert!(!info.is_native_source);
+    }
+
+    // ──────────────────────────────────────────────
+    // 操作履歴のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn rename_entry_履歴に1行追記する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        make_file(&base, "old.excalidraw");
+
+        rename_entry(&base, "old.excalidraw", "new.excalidraw").unwrap();
+
+        let log = fs::read_to_string(base.join(HISTORY_FILE)).unwrap();
+        assert_eq!(log.lines().count(), 1);
+        let entry: HistoryEntry = serde_json::from_str(log.lines().next().unwrap()).unwrap();
+        assert_eq!(entry.action, "rename");
+        assert_eq!(entry.from.as_deref(), Some("old.excalidraw"));
+        assert_eq!(entry.to.as_deref(), Some("new.excalidraw"));
+    }
+
+    #[test]
+    fn read_history_新しい順に返す() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        make_file(&base, "a.excalidraw");
+
+        rename_entry(&base, "a.excalidraw", "b.excalidraw").unwrap();
+        rename_entry(&base, "b.excalidraw", "c.excalidraw").unwrap();
+
+        let history = read_history(&base, 10).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].to.as_deref(), Some("c.excalidraw"));
+        assert_eq!(history[1].to.as_deref(), Some("b.excalidraw"));
+
+        let limited = read_history(&base, 1).unwrap();
+        assert_eq!(limited.len(), 1);
+        assert_eq!(limited[0].to.as_deref(), Some("c.excalidraw"));
+    }
+
+    #[test]
+    fn read_history_ログがなければ空を返す() {
+        let tmp = TempDir::new().unwrap();
+        assert!(read_history(tmp.path(), 10).unwrap().is_empty());
+    }
+
+    // ──────────────────────────────────────────────
+    // validate_relative_path のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn validate_relative_path_通常のパスは許可する() {
+        assert!(validate_relative_path("folder/My Canvas.excalidraw").is_ok());
+    }
+
+    #[test]
+    fn validate_relative_path_パストラバーサルを拒否する() {
+        let err = validate_relative_path("../secret.excalidraw").unwrap_err();
+        assert!(
+            err.contains("パストラバーサル"),
+            "expected traversal error, got: {err}"
+        );
+    }
+
+    #[test]
+    fn validate_relative_path_絶対パスを拒否する() {
+        let err = validate_relative_path("/etc/passwd").unwrap_err();
+        assert!(
+            err.contains("絶対パス"),
+            "expected absolute path error, got: {err}"
+        );
+    }
+
+    #[test]
+    fn validate_relative_path_予約名を拒否する() {
+        let err = validate_relative_path("folder/CON.excalidraw").unwrap_err();
+        assert!(
+            err.contains("予約"),
+            "expected reserved name error, got: {err}"
+        );
+        assert!(validate_relative_path("lpt1").is_err());
+        assert!(validate_relative_path("CONSOLE.excalidraw").is_ok());
+    }
+
+    #[test]
+    fn validate_relative_path_使用できない文字と空名を拒否する() {
+        assert!(validate_relative_path("a?b.excalidraw").is_err());
+        assert!(validate_relative_path("folder//x.excalidraw").is_err());
+        assert!(validate_relative_path("").is_err());
+    }
+
+    // ──────────────────────────────────────────────
+    // reorder_entry のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn reorder_entry_中間のアイテムを上に移動して保存する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        let folder = make_dir(&base, "folder");
+        make_file(&folder, "a.excalidraw");
+        make_file(&folder, "b.excalidraw");
+        make_file(&folder, "c.excalidraw");
+
+        let order = reorder_entry(&base, "folder", "b.excalidraw", "up").unwrap();
+        assert_eq!(order, vec!["b.excalidraw", "a.excalidraw", "c.excalidraw"]);
+
+        let stored: Vec<String> =
+            serde_json::from_str(&fs::read_to_string(folder.join(ORDER_FILE)).unwrap()).unwrap();
+        assert_eq!(stored, order);
+    }
+
+    #[test]
+    fn reorder_entry_先頭のアイテムを上に移動しても変化しない() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        make_file(&base, "a.excalidraw");
+        make_file(&base, "b.excalidraw");
+
+        let order = reorder_entry(&base, "", "a.excalidraw", "up").unwrap();
+        assert_eq!(order, vec!["a.excalidraw", "b.excalidraw"]);
+        assert!(!base.join(ORDER_FILE).exists());
+    }
+
+    #[test]
+    fn rename_entry_並び順の位置を保つ() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        let folder = make_dir(&base, "folder");
+        make_file(&folder, "a.excalidraw");
+        make_file(&folder, "b.excalidraw");
+        make_file(&folder, "c.excalidraw");
+        reorder_entry(&base, "folder", "c.excalidraw", "up").unwrap();
+
+        rename_entry(&base, "folder/c.excalidraw", "folder/z.excalidraw").unwrap();
+
+        assert_eq!(
+            load_order(&folder).unwrap(),
+            vec!["a.excalidraw", "z.excalidraw", "b.excalidraw"]
+        );
+    }
+
+    #[test]
+    fn move_item_into_移動元の並び順から外す() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        let folder = make_dir(&base, "folder");
+        make_dir(&base, "dest");
+        make_file(&folder, "a.excalidraw");
+        make_file(&folder, "b.excalidraw");
+        reorder_entry(&base, "folder", "b.excalidraw", "up").unwrap();
+
+        move_item_into(&base, "folder/b.excalidraw", "dest", false).unwrap();
+
+        assert_eq!(read_stored_order(&folder), vec!["a.excalidraw"]);
+    }
+
+    #[test]
+    fn reorder_entry_不正な方向はエラーになる() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        make_file(&base, "a.excalidraw");
+
+        assert!(reorder_entry(&base, "", "a.excalidraw", "left").is_err());
+        assert!(reorder_entry(&base, "", "missing.excalidraw", "up").is_err());
+    }
+
+    // ──────────────────────────────────────────────
+    // write_vault_archive のテスト
+    // ──────────────────────────────────────────────
+
+    fn zip_entry_names(archive_path: &std::path::Path) -> Vec<String> {
+        let archive = zip::ZipArchive::new(fs::File::open(archive_path).unwrap()).unwrap();
+        archive.file_names().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn write_vault_archive_キャンバスを含むアーカイブを作成する() {
+        let vault = TempDir::new().unwrap();
+        let out = TempDir::new().unwrap();
+        let base = vault.path().to_path_buf();
+        make_file(&base, "root.excalidraw");
+        let sub = make_dir(&base, "folder");
+        make_file(&sub, "nested.excalidraw");
+        let meta = make_dir(&base, ".meta");
+        make_file(&meta, "root.excalidraw.icon");
+
+        let archive = out.path().join("vault.zip");
+        write_vault_archive(&base, &archive, false).unwrap();
+
+        assert!(archive.exists());
+        let names = zip_entry_names(&archive);
+        assert!(names.contains(&"root.excalidraw".to_string()));
+        assert!(names.contains(&"folder/nested.excalidraw".to_string()));
+        assert!(!names.iter().any(|n| n.starts_with(".meta")));
+    }
+
+    #[test]
+    fn write_vault_archive_隠しファイルを含めるオプション() {
+        let vault = TempDir::new().unwrap();
+        let out = TempDir::new().unwrap();
+        let base = vault.path().to_path_buf();
+        let meta = make_dir(&base, ".meta");
+        make_file(&meta, "root.excalidraw.icon");
+
+        let archive = out.path().join("vault.zip");
+        write_vault_archive(&base, &archive, true).unwrap();
+
+        let names = zip_entry_names(&archive);
+        assert!(names.contains(&".meta/root.excalidraw.icon".to_string()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_vault_archive_シンボリックリンクはたどらない() {
+        let vault = TempDir::new().unwrap();
+        let out = TempDir::new().unwrap();
+        let base = vault.path().to_path_buf();
+        make_file(&base, "note.excalidraw");
+        add_to_collection_in(&base, "note.excalidraw", "Pinned").unwrap();
+
+        let archive = out.path().join("vault.zip");
+        write_vault_archive(&base, &archive, true).unwrap();
+
+        let names = zip_entry_names(&archive);
+        assert!(names.contains(&"note.excalidraw".to_string()));
+        assert!(!names.contains(&".collections/Pinned/note.excalidraw".to_string()));
+    }
+
+    // ──────────────────────────────────────────────
+    // extract_vault_archive のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn extract_vault_archive_空の保管庫に復元する() {
+        let vault = TempDir::new().unwrap();
+        let out = TempDir::new().unwrap();
+        let restored = TempDir::new().unwrap();
+        let base = vault.path().to_path_buf();
+        make_file(&base, "root.excalidraw");
+        let sub = make_dir(&base, "folder");
+        make_file(&sub, "nested.excalidraw");
+
+        let archive = out.path().join("vault.zip");
+        write_vault_archive(&base, &archive, false).unwrap();
+
+        let count = extract_vault_archive(restored.path(), &archive, false).unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(
+            fs::read_to_string(restored.path().join("folder/nested.excalidraw")).unwrap(),
+            "dummy"
+        );
+        assert!(restored.path().join("root.excalidraw").exists());
+    }
+
+    #[test]
+    fn extract_vault_archive_空でない保管庫はforceなしで拒否する() {
+        let vault = TempDir::new().unwrap();
+        let out = TempDir::new().unwrap();
+        make_file(vault.path(), "root.excalidraw");
+        let archive = out.path().join("vault.zip");
+        write_vault_archive(vault.path(), &archive, false).unwrap();
+
+        let target = TempDir::new().unwrap();
+        make_file(target.path(), "existing.excalidraw");
+
+        assert!(extract_vault_archive(target.path(), &archive, false).is_err());
+        assert!(!target.path().join("root.excalidraw").exists());
+
+        extract_vault_archive(target.path(), &archive, true).unwrap();
+        assert!(target.path().join("root.excalidraw").exists());
+    }
+
+    #[test]
+    fn extract_vault_archive_保管庫外へのエントリを拒否する() {
+        use std::io::Write;
+
+        let out = TempDir::new().unwrap();
+        let archive = out.path().join("evil.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&archive).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        zip.start_file("ok.excalidraw", options).unwrap();
+        zip.write_all(b"{}").unwrap();
+        zip.start_file("../evil.excalidraw", options).unwrap();
+        zip.write_all(b"{}").unwrap();
+        zip.finish().unwrap();
+
+        let target = TempDir::new().unwrap();
+        let base = target.path().join("vault");
+        assert!(extract_vault_archive(&base, &archive, false).is_err());
+        assert!(!target.path().join("evil.excalidraw").exists());
+        assert!(!base.join("ok.excalidraw").exists());
+    }
+
+    // ──────────────────────────────────────────────
+    // element_counts_in のテスト
+    // ──────────────────────────────────────────────
+
+    fn write_canvas(dir: &std::path::Path, name: &str, elements: serde_json::Value) {
+        let doc = serde_json::json!({
+            "type": "excalidraw",
+            "version": 2,
+            "source": "excalidrauri",
+            "elements": elements,
+            "appState": { "gridSize": null, "viewBackgroundColor": "#ffffff" },
+            "files": {}
+        });
+        fs::write(dir.join(name), doc.to_string()).unwrap();
+    }
+
+    #[test]
+    fn element_counts_in_削除済みを除いた要素数を返す() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        write_canvas(
+            &base,
+            "one.excalidraw",
+            serde_json::json!([{ "id": "a", "type": "rectangle" }]),
+        );
+        write_canvas(
+            &base,
+            "three.excalidraw",
+            serde_json::json!([
+                { "id": "a", "type": "rectangle" },
+                { "id": "b", "type": "ellipse" },
+                { "id": "c", "type": "text", "isDeleted": false },
+                { "id": "d", "type": "arrow", "isDeleted": true }
+            ]),
+        );
+        make_file(&base, "broken.excalidraw");
+
+        let counts = element_counts_in(
+            &base,
+            &[
+                "one.excalidraw".to_string(),
+                "three.excalidraw".to_string(),
+                "broken.excalidraw".to_string(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            counts,
+            vec![
+                ("one.excalidraw".to_string(), 1),
+                ("three.excalidraw".to_string(), 3),
+                ("broken.excalidraw".to_string(), 0),
+            ]
+        );
+    }
+
+    // ──────────────────────────────────────────────
+    // organize_by_date_in のテスト
+    // ──────────────────────────────────────────────
+
+    fn set_mtime(path: &std::path::Path, secs: u64) {
+        let file = fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(UNIX_EPOCH + std::time::Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    // 2023-03-15T12:00:00Z
+    const MARCH_2023: u64 = 1_678_881_600;
+
+    #[test]
+    fn organize_by_date_in_ドライランは移動先を報告するだけ() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        make_file(&base, "note.excalidraw");
+        set_mtime(&base.join("note.excalidraw"), MARCH_2023);
+
+        let moves = organize_by_date_in(&base, "", true).unwrap();
+        assert_eq!(
+            moves,
+            vec![(
+                "note.excalidraw".to_string(),
+                "2023/03/note.excalidraw".to_string()
+            )]
+        );
+        assert!(base.join("note.excalidraw").exists());
+        assert!(!base.join("2023").exists());
+    }
+
+    #[test]
+    fn organize_by_date_in_日付フォルダへ移動し衝突時は番号を付ける() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        let inbox = make_dir(&base, "inbox");
+        make_file(&inbox, "note.excalidraw");
+        set_mtime(&inbox.join("note.excalidraw"), MARCH_2023);
+        let dated = make_dir(&inbox, "2023/03");
+        make_file(&dated, "note.excalidraw");
+
+        let moves = organize_by_date_in(&base, "inbox", false).unwrap();
+        assert_eq!(moves[0].1, "inbox/2023/03/note 2.excalidraw");
+        assert!(!inbox.join("note.excalidraw").exists());
+        assert!(inbox.join("2023/03/note 2.excalidraw").exists());
+    }
+
+    // ──────────────────────────────────────────────
+    // find_canvases_with_element_type_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn find_canvases_with_element_type_in_矢印を含むキャンバスだけ返す() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        let sub = make_dir(&base, "folder");
+        write_canvas(
+            &sub,
+            "flow.excalidraw",
+            serde_json::json!([{ "id": "a", "type": "rectangle" }, { "id": "b", "type": "arrow" }]),
+        );
+        write_canvas(
+            &base,
+            "shapes.excalidraw",
+            serde_json::json!([
+                { "id": "a", "type": "rectangle" },
+                { "id": "b", "type": "arrow", "isDeleted": true }
+            ]),
+        );
+        make_file(&base, "broken.excalidraw");
+
+        let found = find_canvases_with_element_type_in(&base, "arrow").unwrap();
+        assert_eq!(found, vec!["folder/flow.excalidraw"]);
+    }
+
+    // ──────────────────────────────────────────────
+    // retarget_trash_metas のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn retarget_trash_metas_リネーム後のスナップショットの復元先を更新する() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = tmp.path().join("trash");
+        let folder = make_dir(&base, "folder");
+        make_file(&base, "old.excalidraw");
+        make_file(&folder, "inner.excalidraw");
+        make_file(&base, "other.excalidraw");
+        move_to_trash(&base, &trash, "old.excalidraw").unwrap();
+        move_to_trash(&base, &trash, "folder/inner.excalidraw").unwrap();
+        move_to_trash(&base, &trash, "other.excalidraw").unwrap();
+
+        // A new canvas took the old name and was then renamed
+        make_file(&base, "old.excalidraw");
+        rename_entry(&base, "old.excalidraw", "new.excalidraw").unwrap();
+        assert_eq!(
+            retarget_trash_metas(&trash, "old.excalidraw", "new.excalidraw").unwrap(),
+            1
+        );
+        rename_entry(&base, "folder", "renamed").unwrap();
+        assert_eq!(
+            retarget_trash_metas(&trash, "folder", "renamed").unwrap(),
+            1
+        );
+
+        let mut targets: Vec<String> = read_trash_items(&trash)
+            .unwrap()
+            .into_iter()
+            .map(|item| item.original_path)
+            .collect();
+        targets.sort();
+        assert_eq!(
+            targets,
+            vec![
+                "new.excalidraw",
+                "other.excalidraw",
+                "renamed/inner.excalidraw"
+            ]
+        );
+    }
+
+    // ──────────────────────────────────────────────
+    // folder_color のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn folder_color_同じパスは常に同じ色になる() {
+        let a = folder_color("projects/design".to_string());
+        let b = folder_color("projects/design".to_string());
+        assert_eq!(a, b);
+        assert_eq!(a.len(), 7);
+        assert!(a.starts_with('#'));
+        assert!(a[1..].chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
+    #[test]
+    fn folder_color_異なるパスは概ね異なる色になる() {
+        let colors: std::collections::HashSet<String> = (0..20)
+            .map(|i| folder_color(format!("folder-{}", i)))
+            .collect();
+        assert!(colors.len() >= 18, "too many collisions: {}", colors.len());
+    }
+
+    #[test]
+    fn hsl_to_hex_基本色を変換する() {
+        assert_eq!(hsl_to_hex(0.0, 1.0, 0.5), "#ff0000");
+        assert_eq!(hsl_to_hex(120.0, 1.0, 0.5), "#00ff00");
+        assert_eq!(hsl_to_hex(240.0, 1.0, 0.5), "#0000ff");
+    }
+
+    // ──────────────────────────────────────────────
+    // trash_matching_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn trash_matching_in_空のキャンバスだけゴミ箱へ移す() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = tmp.path().join("trash");
+        let sub = make_dir(&base, "folder");
+        write_canvas(&base, "empty.excalidraw", serde_json::json!([]));
+        write_canvas(
+            &sub,
+            "deleted-only.excalidraw",
+            serde_json::json!([{ "id": "a", "type": "rectangle", "isDeleted": true }]),
+        );
+        write_canvas(
+            &base,
+            "full.excalidraw",
+            serde_json::json!([{ "id": "a", "type": "rectangle" }]),
+        );
+
+        let trashed = trash_matching_in(
+            &base,
+            &trash,
+            &TrashFilter::EmptyCanvases,
+            SystemTime::now(),
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            trashed,
+            vec!["empty.excalidraw", "folder/deleted-only.excalidraw"]
+        );
+        assert!(base.join("full.excalidraw").exists());
+        assert!(!base.join("empty.excalidraw").exists());
+        assert_eq!(read_trash_items(&trash).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn trash_matching_in_指定日数より古いキャンバスだけゴミ箱へ移す() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = tmp.path().join("trash");
+        make_file(&base, "old.excalidraw");
+        make_file(&base, "new.excalidraw");
+        set_mtime(&base.join("old.excalidraw"), MARCH_2023);
+
+        let now = UNIX_EPOCH + std::time::Duration::from_secs(MARCH_2023 + 40 * 24 * 60 * 60);
+        set_mtime(&base.join("new.excalidraw"), MARCH_2023 + 35 * 24 * 60 * 60);
+
+        let trashed =
+            trash_matching_in(&base, &trash, &TrashFilter::OlderThanDays(30), now, false).unwrap();
+        assert_eq!(trashed, vec!["old.excalidraw"]);
+        assert!(base.join("new.excalidraw").exists());
+    }
+
+    #[test]
+    fn trash_matching_in_同名のキャンバスも別々にゴミ箱へ移す() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = tmp.path().join("trash");
+        let a = make_dir(&base, "a");
+        let b = make_dir(&base, "b");
+        write_canvas(&a, "x.excalidraw", serde_json::json!([]));
+        write_canvas(&b, "x.excalidraw", serde_json::json!([]));
+
+        let trashed = trash_matching_in(
+            &base,
+            &trash,
+            &TrashFilter::EmptyCanvases,
+            SystemTime::now(),
+            false,
+        )
+        .unwrap();
+        assert_eq!(trashed, vec!["a/x.excalidraw", "b/x.excalidraw"]);
+
+        let items = read_trash_items(&trash).unwrap();
+        assert_eq!(items.len(), 2);
+        for item in &items {
+            restore_from_trash(&base, &trash, &item.trash_path).unwrap();
+        }
+        assert!(a.join("x.excalidraw").is_file());
+        assert!(b.join("x.excalidraw").is_file());
+    }
+
+    #[test]
+    fn trash_matching_in_日数が大きすぎても失敗しない() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = tmp.path().join("trash");
+        make_file(&base, "a.excalidraw");
+
+        let trashed = trash_matching_in(
+            &base,
+            &trash,
+            &TrashFilter::OlderThanDays(u32::MAX),
+            UNIX_EPOCH,
+            false,
+        )
+        .unwrap();
+        assert!(trashed.is_empty());
+    }
+
+    #[test]
+    fn trash_matching_in_設定に従いフォルダごとのゴミ箱へ移す() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = tmp.path().join("trash");
+        let work = make_dir(&base, "work");
+        write_canvas(&work, "a.excalidraw", serde_json::json!([]));
+        write_canvas(&base, "b.excalidraw", serde_json::json!([]));
+
+        trash_matching_in(
+            &base,
+            &trash,
+            &TrashFilter::EmptyCanvases,
+            SystemTime::now(),
+            true,
+        )
+        .unwrap();
+
+        let items = read_trash_items(&trash).unwrap();
+        let work_item = items.iter().find(|i| i.name == "a").unwrap();
+        assert_eq!(work_item.namespace.as_deref(), Some("work"));
+        let root_item = items.iter().find(|i| i.name == "b").unwrap();
+        assert_eq!(root_item.namespace, None);
+    }
+
+    #[test]
+    fn trash_filter_タグ付きjsonからデシリアライズできる() {
+        let filter: TrashFilter =
+            serde_json::from_str(r#"{"type":"olderThanDays","value":7}"#).unwrap();
+        assert!(matches!(filter, TrashFilter::OlderThanDays(7)));
+        let filter: TrashFilter = serde_json::from_str(r#"{"type":"emptyCanvases"}"#).unwrap();
+        assert!(matches!(filter, TrashFilter::EmptyCanvases));
+    }
+
+    // ──────────────────────────────────────────────
+    // build_index_canvas のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn build_index_canvas_キャンバスごとにテキスト要素を作る() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        let sub = make_dir(&base, "folder");
+        make_file(&base, "alpha.excalidraw");
+        make_file(&base, "beta.excalidraw");
+        make_file(&sub, "gamma.excalidraw");
+        make_file(&base, "index.excalidraw");
+
+        let doc = build_index_canvas(&base, "index.excalidraw").unwrap();
+        let elements = doc["elements"].as_array().unwrap();
+        assert_eq!(elements.len(), 3);
+        assert!(elements.iter().all(|el| el["type"] == "text"));
+
+        let labels: Vec<&str> = elements
+            .iter()
+            .map(|el| el["text"].as_str().unwrap())
+            .collect();
+        assert_eq!(labels, vec!["alpha", "beta", "gamma"]);
+
+        // 2 columns for 3 items: the third wraps onto a new row
+        assert_eq!(elements[2]["x"], 0.0);
+        assert!(elements[2]["y"].as_f64().unwrap() > 0.0);
+        assert_eq!(doc["type"], "excalidraw");
+    }
+
+    #[test]
+    fn generate_index_canvas_in_既存のファイルは上書きせず番号を付ける() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        make_file(base, "alpha.excalidraw");
+        make_file(base, "index.excalidraw");
+
+        let written = generate_index_canvas_in(base, "index.excalidraw").unwrap();
+
+        assert_eq!(written, "index 2.excalidraw");
+        assert_eq!(
+            fs::read_to_string(base.join("index.excalidraw")).unwrap(),
+            "dummy"
+        );
+        let doc = read_canvas_json(&base.join(&written)).unwrap();
+        let labels: Vec<&str> = doc["elements"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|el| el["text"].as_str().unwrap())
+            .collect();
+        assert_eq!(labels, vec!["alpha", "index"]);
+        assert!(generate_index_canvas_in(base, "").is_err());
+    }
+
+    // ──────────────────────────────────────────────
+    // fix_missing_files_key_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn fix_missing_files_key_in_filesがないキャンバスだけ修復する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        fs::write(
+            base.join("missing.excalidraw"),
+            r#"{"type":"excalidraw","version":2,"elements":[]}"#,
+        )
+        .unwrap();
+        fs::write(
+            base.join("invalid.excalidraw"),
+            r#"{"type":"excalidraw","version":2,"elements":[],"files":[]}"#,
+        )
+        .unwrap();
+        write_canvas(&base, "normal.excalidraw", serde_json::json!([]));
+        let normal_before = fs::read_to_string(base.join("normal.excalidraw")).unwrap();
+
+        assert_eq!(fix_missing_files_key_in(&base).unwrap(), 2);
+
+        let repaired = read_canvas_json(&base.join("missing.excalidraw")).unwrap();
+        assert_eq!(repaired["files"], serde_json::json!({}));
+        let repaired = read_canvas_json(&base.join("invalid.excalidraw")).unwrap();
+        assert_eq!(repaired["files"], serde_json::json!({}));
+        assert_eq!(
+            fs::read_to_string(base.join("normal.excalidraw")).unwrap(),
+            normal_before
+        );
+        assert!(!base.join(".missing.excalidraw.tmp").exists());
+    }
+
+    // ──────────────────────────────────────────────
+    // group_by_root_folder_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn group_by_root_folder_in_ルートフォルダごとにまとめる() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        let work = make_dir(&base, "work");
+        let nested = make_dir(&work, "deep");
+        let home = make_dir(&base, "home");
+        make_file(&base, "loose.excalidraw");
+        make_file(&work, "a.excalidraw");
+        make_file(&nested, "b.excalidraw");
+        make_file(&home, "c.excalidraw");
+
+        let groups = group_by_root_folder_in(&base).unwrap();
+        let keys: Vec<&str> = groups.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, vec!["", "home", "work"]);
+
+        assert_eq!(groups[0].1[0].path, "loose.excalidraw");
+        let work_paths: Vec<&str> = groups[2].1.iter().map(|i| i.path.as_str()).collect();
+        assert_eq!(
+            work_paths,
+            vec!["work/a.excalidraw", "work/deep/b.excalidraw"]
+        );
+        assert_eq!(groups[2].1[1].name, "b.excalidraw");
+    }
+
+    // ──────────────────────────────────────────────
+    // repair_zero_byte_canvases_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn repair_zero_byte_canvases_in_空ファイルだけテンプレートで書き直す() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        let sub = make_dir(&base, "folder");
+        fs::write(sub.join("empty.excalidraw"), "").unwrap();
+        write_canvas(&base, "normal.excalidraw", serde_json::json!([]));
+        let normal_before = fs::read_to_string(base.join("normal.excalidraw")).unwrap();
+
+        let repaired = repair_zero_byte_canvases_in(&base).unwrap();
+        assert_eq!(repaired, vec!["folder/empty.excalidraw"]);
+        assert_eq!(
+            fs::read_to_string(sub.join("empty.excalidraw")).unwrap(),
+            DEFAULT_CANVAS_CONTENT
+        );
+        assert_eq!(
+            fs::read_to_string(base.join("normal.excalidraw")).unwrap(),
+            normal_before
+        );
+    }
+
+    // ──────────────────────────────────────────────
+    // 最近開いたキャンバスのテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn record_open_in_最近開いた順に重複なく記録する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+
+        record_open_in(&base, "a.excalidraw").unwrap();
+        record_open_in(&base, "b.excalidraw").unwrap();
+        record_open_in(&base, "a.excalidraw").unwrap();
+
+        assert_eq!(load_recent(&base), vec!["a.excalidraw", "b.excalidraw"]);
+    }
+
+    #[test]
+    fn find_never_opened_in_一度も開いていないキャンバスを返す() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        let sub = make_dir(&base, "folder");
+        make_file(&base, "opened.excalidraw");
+        make_file(&base, "forgotten.excalidraw");
+        make_file(&sub, "buried.excalidraw");
+
+        record_open_in(&base, "opened.excalidraw").unwrap();
+
+        let never = find_never_opened_in(&base).unwrap();
+        assert_eq!(
+            never,
+            vec!["folder/buried.excalidraw", "forgotten.excalidraw"]
+        );
+    }
+
+    #[test]
+    fn list_recent_in_削除されたパスを除外して保存し直す() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        make_file(&base, "kept.excalidraw");
+        make_file(&base, "deleted.excalidraw");
+        record_open_in(&base, "kept.excalidraw").unwrap();
+        record_open_in(&base, "deleted.excalidraw").unwrap();
+        fs::remove_file(base.join("deleted.excalidraw")).unwrap();
+
+        assert_eq!(list_recent_in(&base).unwrap(), vec!["kept.excalidraw"]);
+        assert_eq!(load_recent(&base), vec!["kept.excalidraw"]);
+    }
+
+    #[test]
+    fn prune_recent_区切り文字違いの重複をまとめる() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        let sub = make_dir(&base, "folder");
+        make_file(&sub, "a.excalidraw");
+
+        let recent = vec![
+            "folder\\a.excalidraw".to_string(),
+            "folder/a.excalidraw".to_string(),
+            "./folder/a.excalidraw".to_string(),
+            "../outside.excalidraw".to_string(),
+        ];
+        let (pruned, changed) = prune_recent(&base, &recent);
+        assert_eq!(pruned, vec!["folder/a.excalidraw"]);
+        assert!(changed);
+
+        let (_, changed) = prune_recent(&base, &pruned);
+        assert!(!changed);
+    }
+
+    // ──────────────────────────────────────────────
+    // affix_names_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn affix_names_in_接頭辞を付けてリネームする() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        let sub = make_dir(&base, "folder");
+        make_file(&base, "plan.excalidraw");
+        make_file(&sub, "draft.excalidraw");
+
+        let results = affix_names_in(
+            &base,
+            &[
+                "plan.excalidraw".to_string(),
+                "folder/draft.excalidraw".to_string(),
+            ],
+            "[DONE] ",
+            "",
+        );
+        assert_eq!(
+            results[0].new_path.as_deref(),
+            Some("[DONE] plan.excalidraw")
+        );
+        assert_eq!(
+            results[1].new_path.as_deref(),
+            Some("folder/[DONE] draft.excalidraw")
+        );
+        assert!(base.join("[DONE] plan.excalidraw").exists());
+        assert!(!base.join("plan.excalidraw").exists());
+    }
+
+    #[test]
+    fn affix_names_in_衝突時は番号を付け失敗は個別に報告する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        make_file(&base, "plan.excalidraw");
+        make_file(&base, "plan v2.excalidraw");
+
+        let results = affix_names_in(
+            &base,
+            &[
+                "plan.excalidraw".to_string(),
+                "missing.excalidraw".to_string(),
+            ],
+            "",
+            " v2",
+        );
+        assert_eq!(results[0].new_path.as_deref(), Some("plan v2 2.excalidraw"));
+        assert!(results[0].error.is_none());
+        assert!(results[1].new_path.is_none());
+        assert!(results[1].error.is_some());
+    }
+
+    // ──────────────────────────────────────────────
+    // canvas_palette_of のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn canvas_palette_of_使用頻度順に色を返しtransparentを除く() {
+        let doc = canvas_document(
+            vec![
+                serde_json::json!({ "id": "a", "strokeColor": "#1e1e1e", "backgroundColor": "transparent" }),
+                serde_json::json!({ "id": "b", "strokeColor": "#e03131", "backgroundColor": "transparent" }),
+                serde_json::json!({ "id": "c", "strokeColor": "#e03131", "backgroundColor": "transparent" }),
+                serde_json::json!({ "id": "d", "strokeColor": "#ffffff", "isDeleted": true }),
+            ],
+            serde_json::json!({}),
+        );
+
+        assert_eq!(canvas_palette_of(&doc), vec!["#e03131", "#1e1e1e"]);
+    }
+
+    // ──────────────────────────────────────────────
+    // find_broken_image_refs_in のテスト
+    // ──────────────────────────────────────────────
+
+    fn image_file_entry(id: &str) -> serde_json::Value {
+        serde_json::json!({
+            "id": id,
+            "mimeType": "image/png",
+            "dataURL": "data:image/png;base64,iVBORw0KGgo=",
+            "created": 1
+        })
+    }
+
+    #[test]
+    fn find_broken_image_refs_in_filesにないファイルidを報告する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        let doc = canvas_document(
+            vec![
+                serde_json::json!({ "id": "a", "type": "image", "fileId": "present" }),
+                serde_json::json!({ "id": "b", "type": "image", "fileId": "missing" }),
+            ],
+            serde_json::json!({ "present": image_file_entry("present") }),
+        );
+        fs::write(base.join("broken.excalidraw"), doc.to_string()).unwrap();
+        let doc = canvas_document(
+            vec![serde_json::json!({ "id": "a", "type": "image", "fileId": "present" })],
+            serde_json::json!({ "present": image_file_entry("present") }),
+        );
+        fs::write(base.join("fine.excalidraw"), doc.to_string()).unwrap();
+
+        let broken = find_broken_image_refs_in(&base).unwrap();
+        assert_eq!(
+            broken,
+            vec![("broken.excalidraw".to_string(), vec!["missing".to_string()])]
+        );
+    }
+
+    // ──────────────────────────────────────────────
+    // import_files_between のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn import_files_between_提供元の画像データを取り込む() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        let donor = canvas_document(
+            vec![],
+            serde_json::json!({
+                "img1": image_file_entry("img1"),
+                "img2": image_file_entry("img2")
+            }),
+        );
+        fs::write(base.join("donor.excalidraw"), donor.to_string()).unwrap();
+        let target = canvas_document(
+            vec![serde_json::json!({ "id": "a", "type": "image", "fileId": "img1" })],
+            serde_json::json!({}),
+        );
+        fs::write(base.join("target.excalidraw"), target.to_string()).unwrap();
+
+        let added = import_files_between(
+            &base.join("target.excalidraw"),
+            &base.join("donor.excalidraw"),
+            &["img1".to_string()],
+        )
+        .unwrap();
+        assert_eq!(added, 1);
+
+        let doc = read_canvas_json(&base.join("target.excalidraw")).unwrap();
+        let files = doc["files"].as_object().unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files["img1"], image_file_entry("img1"));
+        assert!(broken_file_refs(&doc).is_empty());
+    }
+
+    #[test]
+    fn import_files_between_提供元にないidはエラーにする() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        write_canvas(&base, "donor.excalidraw", serde_json::json!([]));
+        write_canvas(&base, "target.excalidraw", serde_json::json!([]));
+
+        let err = import_files_between(
+            &base.join("target.excalidraw"),
+            &base.join("donor.excalidraw"),
+            &["nope".to_string()],
+        )
+        .unwrap_err();
+        assert!(err.contains("nope"));
+    }
+
+    // ──────────────────────────────────────────────
+    // contains_canvas のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn contains_canvas_深い階層のキャンバスを見つける() {
+        let tmp = TempDir::new().unwrap();
+        let deep = make_dir(tmp.path(), "a/b/c/d");
+        make_file(&deep, "deep.excalidraw");
+        make_dir(tmp.path(), "a/empty");
+
+        assert!(contains_canvas(&tmp.path().join("a")).unwrap());
+    }
+
+    #[test]
+    fn contains_canvas_キャンバスのないフォルダはfalse() {
+        let tmp = TempDir::new().unwrap();
+        let folder = make_dir(tmp.path(), "folder");
+        make_dir(&folder, "sub/subsub");
+        make_file(&folder, "notes.txt");
+        make_file(&folder, ".hidden.excalidraw");
+
+        assert!(!contains_canvas(&folder).unwrap());
+    }
+
+    // ──────────────────────────────────────────────
+    // set_grid_size_at のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn set_grid_size_at_グリッドサイズを設定して解除できる() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("canvas.excalidraw");
+        fs::write(&path, DEFAULT_CANVAS_CONTENT).unwrap();
+
+        set_grid_size_at(&path, Some(20)).unwrap();
+        let doc = read_canvas_json(&path).unwrap();
+        assert_eq!(doc["appState"]["gridSize"], 20);
+        assert_eq!(doc["appState"]["viewBackgroundColor"], "#ffffff");
+
+        set_grid_size_at(&path, None).unwrap();
+        let doc = read_canvas_json(&path).unwrap();
+        assert!(doc["appState"]["gridSize"].is_null());
+    }
+
+    #[test]
+    fn set_grid_size_at_範囲外のサイズを拒否する() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("canvas.excalidraw");
+        fs::write(&path, DEFAULT_CANVAS_CONTENT).unwrap();
+
+        assert!(set_grid_size_at(&path, Some(0)).is_err());
+        assert!(set_grid_size_at(&path, Some(1000)).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), DEFAULT_CANVAS_CONTENT);
+    }
+
+    // ──────────────────────────────────────────────
+    // clipboard_payload のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn clipboard_payload_クリップボード形式で要素と画像を持つ() {
+        let doc = canvas_document(
+            vec![
+                serde_json::json!({ "id": "a", "type": "rectangle" }),
+                serde_json::json!({ "id": "b", "type": "image", "fileId": "img1" }),
+                serde_json::json!({ "id": "c", "type": "ellipse", "isDeleted": true }),
+            ],
+            serde_json::json!({
+                "img1": image_file_entry("img1"),
+                "unused": image_file_entry("unused")
+            }),
+        );
+
+        let payload = clipboard_payload(&doc);
+        assert_eq!(payload["type"], "excalidraw/clipboard");
+        let ids: Vec<&str> = payload["elements"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|el| el["id"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, vec!["a", "b"]);
+        let files = payload["files"].as_object().unwrap();
+        assert_eq!(files.len(), 1);
+        assert!(files.contains_key("img1"));
+    }
+
+    // ──────────────────────────────────────────────
+    // import_clipboard_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn import_clipboard_in_クリップボードから新しいキャンバスを作る() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        make_file(&base, "pasted.excalidraw");
+        let clipboard = serde_json::json!({
+            "type": "excalidraw/clipboard",
+            "elements": [{ "id": "a", "type": "rectangle" }],
+            "files": {}
+        });
+
+        let path = import_clipboard_in(&base, &clipboard.to_string(), "pasted.excalidraw").unwrap();
+        assert_eq!(path, "pasted 2.excalidraw");
+
+        let doc = read_canvas_json(&base.join(&path)).unwrap();
+        assert_eq!(doc["type"], "excalidraw");
+        assert_eq!(doc["elements"][0]["id"], "a");
+        assert_eq!(doc["appState"]["viewBackgroundColor"], "#ffffff");
+        assert_eq!(doc["files"], serde_json::json!({}));
+    }
+
+    #[test]
+    fn import_clipboard_in_クリップボード形式以外を拒否する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+
+        let err = import_clipboard_in(&base, DEFAULT_CANVAS_CONTENT, "x.excalidraw").unwrap_err();
+        assert!(err.contains("clipboard"));
+        assert!(import_clipboard_in(&base, "not json", "x.excalidraw").is_err());
+        assert!(!base.join("x.excalidraw").exists());
+    }
+
+    #[test]
+    fn import_clipboard_in_保存先が空やルートなら拒否する() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let clipboard = serde_json::json!({
+            "type": "excalidraw/clipboard",
+            "elements": [],
+            "files": {}
+        });
+
+        for dest in ["", "."] {
+            assert!(import_clipboard_in(&base, &clipboard.to_string(), dest).is_err());
+        }
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 1);
+        assert_eq!(fs::read_dir(&base).unwrap().count(), 0);
+    }
+
+    // ──────────────────────────────────────────────
+    // find_foreign_source_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn find_foreign_source_in_外部で編集されたキャンバスだけ返す() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        fs::write(
+            base.join("web.excalidraw"),
+            r#"{"type":"excalidraw","version":2,"source":"https://excalidraw.com","elements":[]}"#,
+        )
+        .unwrap();
+        write_canvas(&base, "native.excalidraw", serde_json::json!([]));
+
+        let foreign = find_foreign_source_in(&base).unwrap();
+        assert_eq!(
+            foreign,
+            vec![(
+                "web.excalidraw".to_string(),
+                "https://excalidraw.com".to_string()
+            )]
+        );
+    }
+
+    // ──────────────────────────────────────────────
+    // set_canvas_source_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn set_canvas_source_in_外部のsourceをこのアプリに書き換える() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        fs::write(
+            base.join("web.excalidraw"),
+            r#"{"type":"excalidraw","version":2,"source":"https://excalidraw.com","elements":[{"id":"a"}]}"#,
+        )
+        .unwrap();
+
+        let results = set_canvas_source_in(
+            &base,
+            &[
+                "web.excalidraw".to_string(),
+                "missing.excalidraw".to_string(),
+            ],
+        );
+        assert!(results[0].error.is_none());
+        assert!(results[1].error.is_some());
+
+        let doc = read_canvas_json(&base.join("web.excalidraw")).unwrap();
+        assert_eq!(doc["source"], "excalidrauri");
+        assert_eq!(doc["elements"][0]["id"], "a");
+        assert!(find_foreign_source_in(&base).unwrap().is_empty());
+    }
+
+    // ──────────────────────────────────────────────
+    // delete_if_empty_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn delete_if_empty_in_空のキャンバスは完全に削除する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        write_canvas(
+            &base,
+            "blank.excalidraw",
+            serde_json::json!([{ "id": "a", "type": "rectangle", "isDeleted": true }]),
+        );
+
+        delete_if_empty_in(&base, "blank.excalidraw").unwrap();
+        assert!(!base.join("blank.excalidraw").exists());
+    }
+
+    #[test]
+    fn delete_if_empty_in_要素があるキャンバスは拒否する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        write_canvas(
+            &base,
+            "drawing.excalidraw",
+            serde_json::json!([{ "id": "a", "type": "rectangle" }]),
+        );
+
+        let err = delete_if_empty_in(&base, "drawing.excalidraw").unwrap_err();
+        assert!(err.contains("trash"), "expected trash hint, got: {err}");
+        assert!(base.join("drawing.excalidraw").exists());
+    }
+
+    // ──────────────────────────────────────────────
+    // embedded_image_stats_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn data_url_decoded_len_パディングを考慮してサイズを推定する() {
+        // "hello" → aGVsbG8=
+        assert_eq!(data_url_decoded_len("data:text/plain;base64,aGVsbG8="), 5);
+        // "hi!" → aGkh
+        assert_eq!(data_url_decoded_len("data:text/plain;base64,aGkh"), 3);
+        assert_eq!(data_url_decoded_len("not a data url"), 0);
+    }
+
+    #[test]
+    fn embedded_image_stats_in_複数キャンバスの画像を集計する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        let sub = make_dir(&base, "folder");
+        let doc = canvas_document(
+            vec![],
+            serde_json::json!({ "a": image_file_entry("a"), "b": image_file_entry("b") }),
+        );
+        fs::write(base.join("two.excalidraw"), doc.to_string()).unwrap();
+        let doc = canvas_document(vec![], serde_json::json!({ "c": image_file_entry("c") }));
+        fs::write(sub.join("one.excalidraw"), doc.to_string()).unwrap();
+        write_canvas(&base, "none.excalidraw", serde_json::json!([]));
+
+        let stats = embedded_image_stats_in(&base).unwrap();
+        assert_eq!(stats.image_count, 3);
+        // "iVBORw0KGgo=" decodes to 8 bytes
+        assert_eq!(stats.total_bytes, 3 * 8);
+    }
+
+    // ──────────────────────────────────────────────
+    // descendant_remappings のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn descendant_remappings_配下のキャンバスの新旧パスを返す() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        let project = make_dir(&base, "work/project");
+        let nested = make_dir(&project, "sub");
+        make_file(&project, "a.excalidraw");
+        make_file(&nested, "b.excalidraw");
+        make_file(&base, "work/outside.excalidraw");
+
+        let remapped = descendant_remappings(&base, "work/project", "archive/project").unwrap();
+        assert_eq!(
+            remapped,
+            vec![
+                PathRemap {
+                    from: "work/project/a.excalidraw".to_string(),
+                    to: "archive/project/a.excalidraw".to_string(),
+                },
+                PathRemap {
+                    from: "work/project/sub/b.excalidraw".to_string(),
+                    to: "archive/project/sub/b.excalidraw".to_string(),
+                },
+            ]
+        );
+    }
+
+    // ──────────────────────────────────────────────
+    // バージョン履歴のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn create_version_in_新しい順に一覧できる() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        let sub = make_dir(&base, "folder");
+        make_file(&sub, "canvas.excalidraw");
+
+        let first = create_version_in(&base, "folder/canvas.excalidraw").unwrap();
+        let second = create_version_in(&base, "folder/canvas.excalidraw").unwrap();
+        assert!(second > first);
+
+        assert_eq!(
+            list_versions_in(&base, "folder/canvas.excalidraw").unwrap(),
+            vec![second, first]
+        );
+        assert!(base
+            .join(".versions/folder%2Fcanvas.excalidraw")
+            .join(format!("{}.excalidraw", first))
+            .exists());
+    }
+
+    #[test]
+    fn clear_versions_in_バックアップを削除して件数を返す() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        make_file(&base, "canvas.excalidraw");
+        make_file(&base, "other.excalidraw");
+        create_version_in(&base, "canvas.excalidraw").unwrap();
+        create_version_in(&base, "canvas.excalidraw").unwrap();
+        create_version_in(&base, "other.excalidraw").unwrap();
+
+        assert_eq!(clear_versions_in(&base, "canvas.excalidraw").unwrap(), 2);
+        assert!(list_versions_in(&base, "canvas.excalidraw")
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            list_versions_in(&base, "other.excalidraw").unwrap().len(),
+            1
+        );
+        assert_eq!(clear_versions_in(&base, "canvas.excalidraw").unwrap(), 0);
+    }
+
+    #[test]
+    fn clear_versions_in_区切りが下線になる別のキャンバスの履歴を消さない() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        let sub = make_dir(&base, "a");
+        make_file(&sub, "b.excalidraw");
+        make_file(&base, "a_b.excalidraw");
+        create_version_in(&base, "a/b.excalidraw").unwrap();
+        create_version_in(&base, "a_b.excalidraw").unwrap();
+
+        assert_eq!(clear_versions_in(&base, "a/b.excalidraw").unwrap(), 1);
+        assert_eq!(list_versions_in(&base, "a_b.excalidraw").unwrap().len(), 1);
+    }
+
+    // ──────────────────────────────────────────────
+    // list_dir_filtered_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn list_dir_filtered_in_拡張子パターンで全キャンバスを返す() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        let sub = make_dir(&base, "folder");
+        make_dir(&base, "empty");
+        make_file(&sub, "inner.excalidraw");
+        make_file(&base, "a.excalidraw");
+        make_file(&base, "notes.md");
+
+        let items = list_dir_filtered_in(&base, "", "*.excalidraw").unwrap();
+        let names: Vec<&str> = items.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(names, vec!["folder", "a.excalidraw"]);
+        assert!(items[0].children.is_none());
+    }
+
+    #[test]
+    fn list_dir_filtered_in_名前の接頭辞で絞り込む() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        let with_match = make_dir(&base, "with");
+        let without = make_dir(&base, "without");
+        make_file(&with_match, "draft-2.excalidraw");
+        make_file(&without, "final.excalidraw");
+        make_file(&base, "draft-1.excalidraw");
+        make_file(&base, "final.excalidraw");
+
+        let items = list_dir_filtered_in(&base, "", "draft-*").unwrap();
+        let paths: Vec<&str> = items.iter().map(|i| i.path.as_str()).collect();
+        assert_eq!(paths, vec!["with", "draft-1.excalidraw"]);
+    }
+
+    #[test]
+    fn list_dir_filtered_in_不正なパターンはエラーにする() {
+        let tmp = TempDir::new().unwrap();
+        let err = list_dir_filtered_in(tmp.path(), "", "[unclosed").unwrap_err();
+        assert!(err.contains("Invalid glob"), "unexpected error: {err}");
+    }
+
+    // ──────────────────────────────────────────────
+    // save_canvas_guarded_at のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn content_hash_sha256の16進表記を返す() {
+        assert_eq!(
+            content_hash(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            content_hash(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn save_canvas_guarded_at_ハッシュが一致すれば保存する() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("canvas.excalidraw");
+        fs::write(&path, "original").unwrap();
+        let baseline = content_hash(b"original");
+
+        save_canvas_guarded_at(&path, "updated", &baseline).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "updated");
+    }
+
+    #[test]
+    fn save_canvas_guarded_at_外部で変更されていれば競合を返す() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("canvas.excalidraw");
+        fs::write(&path, "original").unwrap();
+        let baseline = content_hash(b"original");
+        fs::write(&path, "changed by sync").unwrap();
+
+        let err = save_canvas_guarded_at(&path, "updated", &baseline).unwrap_err();
+        assert_eq!(
+            err,
+            SaveError::Conflict {
+                current: Some("changed by sync".to_string()),
+                current_hash: Some(content_hash(b"changed by sync")),
+            }
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), "changed by sync");
+    }
+
+    #[test]
+    fn save_error_種類タグ付きでシリアライズされる() {
+        let err = SaveError::Io {
+            message: "boom".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_value(&err).unwrap(),
+            serde_json::json!({ "kind": "io", "message": "boom" })
+        );
+    }
+
+    // ──────────────────────────────────────────────
+    // new_untitled_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn new_untitled_in_呼ぶたびに番号が増える() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        make_dir(&base, "folder");
+
+        let names: Vec<String> = (0..3)
+            .map(|_| new_untitled_in(&base, "folder", true).unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "folder/Untitled.excalidraw",
+                "folder/Untitled 2.excalidraw",
+                "folder/Untitled 3.excalidraw",
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(base.join("folder/Untitled 3.excalidraw")).unwrap(),
+            DEFAULT_CANVAS_CONTENT
+        );
+    }
+
+    #[test]
+    fn new_untitled_in_作成しない場合は名前だけ返す() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+
+        assert_eq!(
+            new_untitled_in(&base, "", false).unwrap(),
+            "Untitled.excalidraw"
+        );
+        assert_eq!(
+            new_untitled_in(&base, "", false).unwrap(),
+            "Untitled.excalidraw"
+        );
+        assert!(!base.join("Untitled.excalidraw").exists());
+    }
+
+    // ──────────────────────────────────────────────
+    // normalize_relative_path のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn normalize_relative_path_バックスラッシュをスラッシュと同じに解決する() {
+        let base = PathBuf::from("/vault");
+        let backslash = normalize_relative_path("folder\\sub\\canvas.excalidraw").unwrap();
+        let slash = normalize_relative_path("folder/sub/canvas.excalidraw").unwrap();
+
+        assert_eq!(backslash, "folder/sub/canvas.excalidraw");
+        assert_eq!(base.join(&backslash), base.join(&slash));
+    }
+
+    #[test]
+    fn normalize_relative_path_バックスラッシュでもトラバーサルを拒否する() {
+        let err = normalize_relative_path("..\\secret").unwrap_err();
+        assert!(
+            err.contains("パストラバーサル"),
+            "expected traversal error, got: {err}"
+        );
+        assert!(normalize_relative_path("folder\\..\\..\\etc").is_err());
+    }
+
+    #[test]
+    fn validate_relative_path_バックスラッシュ区切りも検証する() {
+        assert!(validate_relative_path("folder\\canvas.excalidraw").is_ok());
+        assert!(validate_relative_path("folder\\CON").is_err());
+    }
+
+    // ──────────────────────────────────────────────
+    // created_millis のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn created_millis_対応環境では作成時刻を返す() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("canvas.excalidraw");
+        let before = now_millis();
+        make_file(tmp.path(), "canvas.excalidraw");
+
+        let created = created_millis(&path).unwrap();
+        if fs::metadata(&path).unwrap().created().is_ok() {
+            let created = created.expect("creation time should be reported");
+            // Allow for coarse filesystem timestamp granularity
+            assert!(
+                created + 2_000 >= before,
+                "created={created} before={before}"
+            );
+            assert!(created <= now_millis());
+        } else {
+            assert!(created.is_none());
+        }
+    }
+
+    #[test]
+    fn created_millis_存在しないファイルはエラー() {
+        let tmp = TempDir::new().unwrap();
+        assert!(created_millis(&tmp.path().join("missing.excalidraw")).is_err());
+    }
+
+    #[test]
+    fn history_stats_in_追記した件数とサイズを返す() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        assert_eq!(history_stats_in(&base).unwrap(), HistoryStats::default());
+
+        append_history(&base, "create", None, Some("a.excalidraw")).unwrap();
+        append_history(&base, "delete", Some("a.excalidraw"), None).unwrap();
+
+        let stats = history_stats_in(&base).unwrap();
+        assert_eq!(stats.entry_count, 2);
+        assert_eq!(
+            stats.byte_size,
+            fs::metadata(base.join(HISTORY_FILE)).unwrap().len()
+        );
+    }
+
+    #[test]
+    fn clear_history_in_件数をゼロに戻す() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().to_path_buf();
+        append_history(&base, "create", None, Some("a.excalidraw")).unwrap();
+
+        clear_history_in(&base).unwrap();
+
+        assert_eq!(history_stats_in(&base).unwrap(), HistoryStats::default());
+        assert!(read_history(&base, 10).unwrap().is_empty());
+    }
+
+    // ──────────────────────────────────────────────
+    // restore_from_trash のテスト
+    // ──────────────────────────────────────────────
+
+    fn tamper_trash_meta(trash: &std::path::Path, trash_name: &str, original_path: &str) {
+        let mut index = read_trash_index(trash);
+        index.insert(
+            trash_name.to_string(),
+            TrashMeta {
+                original_path: original_path.to_string(),
+                trashed_at: 1,
+            },
+        );
+        write_trash_index(trash, &index).unwrap();
+    }
+
+    #[test]
+    fn restore_from_trash_元の場所に戻す() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = tmp.path().join("trash");
+        let sub = make_dir(&base, "folder");
+        make_file(&sub, "canvas.excalidraw");
+        let trash_name = move_to_trash(&base, &trash, "folder/canvas.excalidraw").unwrap();
+        fs::remove_dir(&sub).unwrap();
+
+        restore_from_trash(&base, &trash, &trash_name).unwrap();
+        assert!(sub.join("canvas.excalidraw").exists());
+        assert!(!trash.join(format!("{}.meta", trash_name)).exists());
+    }
+
+    #[test]
+    fn restore_from_trash_改ざんされたトラバーサルを拒否する() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = tmp.path().join("trash");
+        make_file(&base, "canvas.excalidraw");
+        let trash_name = move_to_trash(&base, &trash, "canvas.excalidraw").unwrap();
+        tamper_trash_meta(&trash, &trash_name, "../escaped.excalidraw");
+
+        assert!(restore_from_trash(&base, &trash, &trash_name).is_err());
+        assert!(!tmp.path().join("escaped.excalidraw").exists());
+        assert!(trash.join(&trash_name).exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn restore_from_trash_シンボリックリンク経由の脱出を拒否する() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = tmp.path().join("trash");
+        let outside = make_dir(tmp.path(), "outside");
+        std::os::unix::fs::symlink(&outside, base.join("link")).unwrap();
+        make_file(&base, "canvas.excalidraw");
+        let trash_name = move_to_trash(&base, &trash, "canvas.excalidraw").unwrap();
+        tamper_trash_meta(&trash, &trash_name, "link/escaped.excalidraw");
+
+        let err = restore_from_trash(&base, &trash, &trash_name).unwrap_err();
+        assert!(err.contains("保管庫の外"), "unexpected error: {err}");
+        assert!(!outside.join("escaped.excalidraw").exists());
+    }
+
+    #[test]
+    fn restore_from_trash_ゴミ箱外のtrash_pathを拒否する() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = make_dir(tmp.path(), "trash");
+        make_file(&base, "canvas.excalidraw");
+
+        assert!(restore_from_trash(&base, &trash, "../canvases/canvas.excalidraw").is_err());
+        assert!(base.join("canvas.excalidraw").exists());
+    }
+
+    #[test]
+    fn restore_from_trash_リネーム後も生きているキャンバスを上書きしない() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = tmp.path().join("trash");
+        fs::write(base.join("old.excalidraw"), "snapshot").unwrap();
+        let trash_name = move_to_trash(&base, &trash, "old.excalidraw").unwrap();
+        fs::write(base.join("old.excalidraw"), "live").unwrap();
+        rename_entry(&base, "old.excalidraw", "new.excalidraw").unwrap();
+        retarget_trash_metas(&trash, "old.excalidraw", "new.excalidraw").unwrap();
+
+        let err = restore_from_trash(&base, &trash, &trash_name).unwrap_err();
+        assert!(err.contains("既に存在"), "unexpected error: {err}");
+        assert_eq!(
+            fs::read_to_string(base.join("new.excalidraw")).unwrap(),
+            "live"
+        );
+        assert!(trash.join(&trash_name).exists());
+    }
+
+    // ──────────────────────────────────────────────
+    // trash_items_since のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn trash_items_since_指定時刻より後のものだけを新しい順に返す() {
+        let tmp = TempDir::new().unwrap();
+        let trash = make_dir(tmp.path(), "trash");
+        for (name, trashed_at) in [
+            ("old", 1_000u64),
+            ("edge", 2_000),
+            ("new", 3_000),
+            ("newer", 4_000),
+        ] {
+            let trash_name = format!("{}.excalidraw", name);
+            make_file(&trash, &trash_name);
+            let meta = TrashMeta {
+                original_path: trash_name.clone(),
+                trashed_at,
+            };
+            fs::write(
+                trash.join(format!("{}.meta", trash_name)),
+                serde_json::to_string(&meta).unwrap(),
+            )
+            .unwrap();
+        }
+
+        let names: Vec<String> = trash_items_since(&trash, 2_000)
+            .unwrap()
+            .into_iter()
+            .map(|item| item.name)
+            .collect();
+        assert_eq!(names, vec!["newer", "new"]);
+    }
+
+    #[test]
+    fn trash_items_since_ゴミ箱がなければ空() {
+        let tmp = TempDir::new().unwrap();
+        assert!(trash_items_since(&tmp.path().join("trash"), 0)
+            .unwrap()
+            .is_empty());
+    }
+
+    // ──────────────────────────────────────────────
+    // elements_csv のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn elements_csv_ヘッダーと要素ごとの行を出力する() {
+        let doc = serde_json::json!({
+            "elements": [
+                {"id": "r1", "type": "rectangle", "x": 10, "y": 20.5, "width": 100, "height": 50},
+                {"id": "t1", "type": "text", "x": 0, "y": 0, "width": 40, "height": 20, "text": "a, \"b\""},
+                {"id": "gone", "type": "ellipse", "x": 0, "y": 0, "isDeleted": true}
+            ]
+        });
+
+        let csv = elements_csv(&doc);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "id,type,x,y,width,height,text");
+        assert_eq!(lines[1], "r1,rectangle,10,20.5,100,50,");
+        assert_eq!(lines[2], "t1,text,0,0,40,20,\"a, \"\"b\"\"\"");
+    }
+
+    #[test]
+    fn elements_csv_欠けたフィールドは空セルになる() {
+        let doc = serde_json::json!({"elements": [{"type": "line"}]});
+        assert_eq!(
+            elements_csv(&doc),
+            "id,type,x,y,width,height,text\n,line,,,,,\n"
+        );
+    }
+
+    // ──────────────────────────────────────────────
+    // find_offscreen_canvases_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn find_offscreen_canvases_in_負の座標を持つキャンバスだけを返す() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        write_canvas(
+            base,
+            "offscreen.excalidraw",
+            serde_json::json!([{"id": "a", "type": "rectangle", "x": -100, "y": -100, "width": 50, "height": 50}]),
+        );
+        write_canvas(
+            base,
+            "normal.excalidraw",
+            serde_json::json!([{"id": "a", "type": "rectangle", "x": 10, "y": 10, "width": 50, "height": 50}]),
+        );
+        write_canvas(base, "empty.excalidraw", serde_json::json!([]));
+
+        assert_eq!(
+            find_offscreen_canvases_in(base).unwrap(),
+            vec!["offscreen.excalidraw"]
+        );
+    }
+
+    #[test]
+    fn content_bounds_線のポイントと負のサイズを含める() {
+        let doc = serde_json::json!({"elements": [
+            {"type": "arrow", "x": 10, "y": 10, "width": 0, "height": 0, "points": [[0, 0], [-30, 5]]},
+            {"type": "rectangle", "x": 50, "y": 50, "width": -20, "height": 10},
+            {"type": "rectangle", "x": -500, "y": -500, "width": 10, "height": 10, "isDeleted": true}
+        ]});
+        assert_eq!(content_bounds(&doc), Some((-20.0, 10.0, 50.0, 60.0)));
+    }
+
+    // ──────────────────────────────────────────────
+    // text_preview のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn text_preview_上限を超えたら切り詰めて省略記号を付ける() {
+        let doc = serde_json::json!({"elements": [
+            {"type": "text", "text": "  こんにちは "},
+            {"type": "rectangle"},
+            {"type": "text", "text": "world", "isDeleted": true},
+            {"type": "text", "text": "excalidraw"}
+        ]});
+
+        assert_eq!(text_preview(&doc, 100), "こんにちは excalidraw");
+        assert_eq!(text_preview(&doc, 16), "こんにちは excalidraw");
+        assert_eq!(text_preview(&doc, 8), "こんにちは ex…");
+        assert_eq!(text_preview(&doc, 6), "こんにちは…");
+    }
+
+    #[test]
+    fn text_preview_空のキャンバスは空文字列() {
+        let doc = serde_json::json!({"elements": []});
+        assert_eq!(text_preview(&doc, 10), "");
+    }
+
+    // ──────────────────────────────────────────────
+    // コレクションのテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn add_to_collection_in_元のキャンバスに解決される() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        let a = make_dir(base, "a");
+        let b = make_dir(base, "b");
+        make_file(&a, "note.excalidraw");
+        make_file(&b, "note.excalidraw");
+
+        add_to_collection_in(base, "a/note.excalidraw", "Pinned").unwrap();
+        add_to_collection_in(base, "b/note.excalidraw", "Pinned").unwrap();
+        add_to_collection_in(base, "a/note.excalidraw", "Pinned").unwrap();
+
+        assert_eq!(
+            list_collection_in(base, "Pinned").unwrap(),
+            vec!["a/note.excalidraw", "b/note.excalidraw"]
+        );
+        assert!(list_collection_in(base, "Other").unwrap().is_empty());
+        // 通常の一覧には現れない
+        assert!(!walk_canvases(base, base)
+            .unwrap()
+            .iter()
+            .any(|p| p.starts_with(".")));
+    }
+
+    #[test]
+    fn remove_from_collection_in_エントリだけを削除する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        make_file(base, "note.excalidraw");
+        add_to_collection_in(base, "note.excalidraw", "Pinned").unwrap();
+
+        remove_from_collection_in(base, "note.excalidraw", "Pinned").unwrap();
+        assert!(list_collection_in(base, "Pinned").unwrap().is_empty());
+        assert!(base.join("note.excalidraw").exists());
+        assert!(remove_from_collection_in(base, "note.excalidraw", "Pinned").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn add_to_collection_in_保管庫を移動してもリンクが解決できる() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().join("vault");
+        make_dir(&base, "a");
+        make_file(&base, "a/note.excalidraw");
+        add_to_collection_in(&base, "a/note.excalidraw", "Pinned").unwrap();
+
+        let link = base.join(COLLECTIONS_DIR).join("Pinned/note.excalidraw");
+        assert_eq!(fs::read_to_string(&link).unwrap(), "dummy");
+
+        let moved = tmp.path().join("moved");
+        fs::rename(&base, &moved).unwrap();
+        assert_eq!(
+            list_collection_in(&moved, "Pinned").unwrap(),
+            vec!["a/note.excalidraw"]
+        );
+    }
+
+    #[test]
+    fn add_to_collection_in_不正なコレクション名を拒否する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        make_file(base, "note.excalidraw");
+
+        assert!(add_to_collection_in(base, "note.excalidraw", "../escape").is_err());
+        assert!(add_to_collection_in(base, "note.excalidraw", ".hidden").is_err());
+        assert!(add_to_collection_in(base, "missing.excalidraw", "Pinned").is_err());
+    }
+
+    // ──────────────────────────────────────────────
+    // diff_versions_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn diff_versions_in_追加削除変更された要素を返す() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        write_canvas(
+            base,
+            "canvas.excalidraw",
+            serde_json::json!([
+                {"id": "keep", "type": "rectangle", "x": 0},
+                {"id": "move", "type": "rectangle", "x": 0},
+                {"id": "drop", "type": "ellipse", "x": 0}
+            ]),
+        );
+        let ts_a = create_version_in(base, "canvas.excalidraw").unwrap();
+        write_canvas(
+            base,
+            "canvas.excalidraw",
+            serde_json::json!([
+                {"id": "keep", "type": "rectangle", "x": 0},
+                {"id": "move", "type": "rectangle", "x": 40},
+                {"id": "drop", "type": "ellipse", "x": 0, "isDeleted": true},
+                {"id": "new", "type": "text", "x": 0}
+            ]),
+        );
+        let ts_b = create_version_in(base, "canvas.excalidraw").unwrap();
+
+        let diff = diff_versions_in(base, "canvas.excalidraw", ts_a, ts_b).unwrap();
+        assert_eq!(
+            diff,
+            CanvasDiff {
+                added: vec!["new".to_string()],
+                removed: vec!["drop".to_string()],
+                modified: vec!["move".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn diff_versions_in_存在しないバージョンはエラー() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        make_file(base, "canvas.excalidraw");
+        let ts = create_version_in(base, "canvas.excalidraw").unwrap();
+
+        assert!(diff_versions_in(base, "canvas.excalidraw", ts, ts + 1).is_err());
+    }
+
+    // ──────────────────────────────────────────────
+    // ゴミ箱インデックスのテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn move_to_trash_インデックスに記録してサイドカーを作らない() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = tmp.path().join("trash");
+        make_file(&base, "canvas.excalidraw");
+
+        let trash_name = move_to_trash(&base, &trash, "canvas.excalidraw").unwrap();
+
+        assert!(!trash.join(format!("{}.meta", trash_name)).exists());
+        let index = read_trash_index(&trash);
+        assert_eq!(index[&trash_name].original_path, "canvas.excalidraw");
+
+        let items = read_trash_items(&trash).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].trash_path, trash_name);
+        assert_eq!(items[0].name, "canvas");
+    }
+
+    #[test]
+    fn read_trash_items_インデックスと旧形式のメタを併用する() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = tmp.path().join("trash");
+        make_file(&base, "new.excalidraw");
+        move_to_trash(&base, &trash, "new.excalidraw").unwrap();
+        make_file(&trash, "1_legacy.excalidraw");
+        fs::write(
+            trash.join("1_legacy.excalidraw.meta"),
+            r#"{"original_path":"legacy.excalidraw","trashed_at":1}"#,
+        )
+        .unwrap();
+
+        let mut names: Vec<String> = read_trash_items(&trash)
+            .unwrap()
+            .into_iter()
+            .map(|item| item.name)
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["legacy", "new"]);
+    }
+
+    #[test]
+    fn migrate_trash_index_in_旧形式のメタを取り込む() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = tmp.path().join("trash");
+        make_file(&base, "new.excalidraw");
+        let indexed = move_to_trash(&base, &trash, "new.excalidraw").unwrap();
+        for (name, original) in [("1_a.excalidraw", "a.excalidraw"), ("2_b", "folder/b")] {
+            make_file(&trash, name);
+            fs::write(
+                trash.join(format!("{}.meta", name)),
+                format!(r#"{{"original_path":"{}","trashed_at":1}}"#, original),
+            )
+            .unwrap();
+        }
+
+        assert_eq!(migrate_trash_index_in(&trash).unwrap(), 2);
+        assert!(!trash.join("1_a.excalidraw.meta").exists());
+        assert!(!trash.join("2_b.meta").exists());
+
+        let index = read_trash_index(&trash);
+        assert_eq!(index.len(), 3);
+        assert_eq!(index["2_b"].original_path, "folder/b");
+        assert_eq!(index[&indexed].original_path, "new.excalidraw");
+        assert_eq!(read_trash_items(&trash).unwrap().len(), 3);
+        assert_eq!(migrate_trash_index_in(&trash).unwrap(), 0);
+    }
+
+    #[test]
+    fn read_trash_index_壊れたインデックスは退避してから書き直す() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = tmp.path().join("trash");
+        fs::create_dir_all(&trash).unwrap();
+        fs::write(trash.join(TRASH_INDEX), "{ not json").unwrap();
+        make_file(&base, "a.excalidraw");
+
+        let trash_name = move_to_trash(&base, &trash, "a.excalidraw").unwrap();
+
+        assert_eq!(
+            fs::read_to_string(trash.join("index.json.bak")).unwrap(),
+            "{ not json"
+        );
+        let index = read_trash_index(&trash);
+        assert_eq!(index.len(), 1);
+        assert_eq!(index[&trash_name].original_path, "a.excalidraw");
+        assert_eq!(read_trash_items(&trash).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn retarget_trash_metas_インデックスの元パスも書き換える() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = tmp.path().join("trash");
+        let sub = make_dir(&base, "old");
+        make_file(&sub, "canvas.excalidraw");
+        let trash_name = move_to_trash(&base, &trash, "old/canvas.excalidraw").unwrap();
+
+        assert_eq!(retarget_trash_metas(&trash, "old", "new").unwrap(), 1);
+        assert_eq!(
+            read_trash_index(&trash)[&trash_name].original_path,
+            "new/canvas.excalidraw"
+        );
+    }
+
+    // ──────────────────────────────────────────────
+    // find_similar_canvases_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn find_similar_canvases_in_要素の大半が共通なら類似とみなす() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        let shared = |extra: serde_json::Value| {
+            let mut elements = vec![
+                serde_json::json!({"id": "a", "type": "rectangle"}),
+                serde_json::json!({"id": "b", "type": "ellipse"}),
+                serde_json::json!({"id": "c", "type": "text"}),
+                serde_json::json!({"id": "d", "type": "arrow"}),
+            ];
+            elements.push(extra);
+            serde_json::Value::Array(elements)
+        };
+        write_canvas(
+            base,
+            "one.excalidraw",
+            shared(serde_json::json!({"id": "e", "type": "line"})),
+        );
+        write_canvas(
+            base,
+            "two.excalidraw",
+            shared(serde_json::json!({"id": "f", "type": "line"})),
+        );
+        write_canvas(
+            base,
+            "other.excalidraw",
+            serde_json::json!([{"id": "x", "type": "rectangle"}]),
+        );
+
+        let pairs = find_similar_canvases_in(base, 0.6).unwrap();
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].0, "one.excalidraw");
+        assert_eq!(pairs[0].1, "two.excalidraw");
+        assert!((pairs[0].2 - 4.0 / 6.0).abs() < 1e-9);
+
+        assert!(find_similar_canvases_in(base, 0.9).unwrap().is_empty());
+    }
+
+    // ──────────────────────────────────────────────
+    // save_canvas_at のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn save_canvas_at_整形保存は複数行で同じ文書に戻る() {
+        let tmp = TempDir::new().unwrap();
+        let full_path = tmp.path().join("folder/canvas.excalidraw");
+
+        save_canvas_at(&full_path, DEFAULT_CANVAS_CONTENT, true).unwrap();
+
+        let written = fs::read_to_string(&full_path).unwrap();
+        assert!(written.lines().count() > 1);
+        let reparsed: serde_json::Value = serde_json::from_str(&written).unwrap();
+        let original: serde_json::Value = serde_json::from_str(DEFAULT_CANVAS_CONTENT).unwrap();
+        assert_eq!(reparsed, original);
+    }
+
+    #[test]
+    fn save_canvas_at_通常保存は内容をそのまま書く() {
+        let tmp = TempDir::new().unwrap();
+        let full_path = tmp.path().join("canvas.excalidraw");
+
+        save_canvas_at(&full_path, DEFAULT_CANVAS_CONTENT, false).unwrap();
+        assert_eq!(
+            fs::read_to_string(&full_path).unwrap(),
+            DEFAULT_CANVAS_CONTENT
+        );
+        assert!(save_canvas_at(&full_path, "not json", true).is_err());
+    }
+
+    // ──────────────────────────────────────────────
+    // フォルダのピン留めのテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn pin_folder_in_ピン留めした順に一覧できる() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        make_dir(base, "zeta");
+        make_dir(base, "alpha");
+        make_dir(&base.join("alpha"), "nested");
+
+        pin_folder_in(base, "zeta").unwrap();
+        pin_folder_in(base, "alpha/nested").unwrap();
+        pin_folder_in(base, "alpha").unwrap();
+        pin_folder_in(base, "zeta").unwrap();
+
+        assert_eq!(
+            list_pinned_folders_in(base),
+            vec!["zeta", "alpha/nested", "alpha"]
+        );
+
+        unpin_folder_in(base, "alpha/nested").unwrap();
+        assert_eq!(list_pinned_folders_in(base), vec!["zeta", "alpha"]);
+    }
+
+    #[test]
+    fn pin_folder_in_ファイルのパスは拒否する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        make_file(base, "canvas.excalidraw");
+
+        assert!(pin_folder_in(base, "canvas.excalidraw").is_err());
+        assert!(pin_folder_in(base, "missing").is_err());
+        assert!(pin_folder_in(base, "").is_err());
+        assert!(list_pinned_folders_in(base).is_empty());
+    }
+
+    #[test]
+    fn list_pinned_folders_in_消えたフォルダは除外する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        let gone = make_dir(base, "gone");
+        make_dir(base, "kept");
+        pin_folder_in(base, "gone").unwrap();
+        pin_folder_in(base, "kept").unwrap();
+        fs::remove_dir(gone).unwrap();
+
+        assert_eq!(list_pinned_folders_in(base), vec!["kept"]);
+    }
+
+    // ──────────────────────────────────────────────
+    // rename_transactional_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn rename_transactional_in_アイコンのサイドカーも移動する() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = tmp.path().join("trash");
+        let old = make_dir(&base, "old");
+        make_file(&old, "canvas.excalidraw");
+        let meta = make_dir(&base, ".meta");
+        fs::write(meta.join("old.icon"), "{}").unwrap();
+        fs::write(meta.join("old_canvas.excalidraw.icon"), "{}").unwrap();
+
+        rename_transactional_in(&base, &trash, "old", "new").unwrap();
+
+        assert!(base.join("new/canvas.excalidraw").exists());
+        assert!(meta.join("new.icon").exists());
+        assert!(meta.join("new_canvas.excalidraw.icon").exists());
+        assert!(!meta.join("old.icon").exists());
+    }
+
+    #[test]
+    fn rename_transactional_in_途中で失敗したら元に戻す() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let old = make_dir(&base, "old");
+        make_file(&old, "canvas.excalidraw");
+        let meta = make_dir(&base, ".meta");
+        fs::write(meta.join("old.icon"), "{}").unwrap();
+        // ゴミ箱がファイルだとメタの書き換えが失敗する
+        let trash = tmp.path().join("trash");
+        fs::write(&trash, "not a directory").unwrap();
+
+        assert!(rename_transactional_in(&base, &trash, "old", "new").is_err());
+
+        assert!(base.join("old/canvas.excalidraw").exists());
+        assert!(!base.join("new").exists());
+        assert!(meta.join("old.icon").exists());
+        assert!(!meta.join("new.icon").exists());
+        assert!(!base.join(HISTORY_FILE).exists());
+    }
+
+    #[test]
+    fn rename_transactional_in_既存のアイテムを上書きしない() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = tmp.path().join("trash");
+        fs::write(base.join("a.excalidraw"), "a").unwrap();
+        fs::write(base.join("b.excalidraw"), "b").unwrap();
+
+        let err =
+            rename_transactional_in(&base, &trash, "a.excalidraw", "b.excalidraw").unwrap_err();
+
+        assert_eq!(err, "移動先に同名のアイテムが既に存在します");
+        assert_eq!(fs::read_to_string(base.join("a.excalidraw")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(base.join("b.excalidraw")).unwrap(), "b");
+    }
+
+    #[test]
+    fn rename_transactional_in_バージョン履歴も移動する() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = tmp.path().join("trash");
+        make_file(&base, "old.excalidraw");
+        create_version_in(&base, "old.excalidraw").unwrap();
+
+        rename_transactional_in(&base, &trash, "old.excalidraw", "new.excalidraw").unwrap();
+
+        assert_eq!(list_versions_in(&base, "new.excalidraw").unwrap().len(), 1);
+        assert!(!get_versions_dir(&base, "old.excalidraw").exists());
+    }
+
+    // ──────────────────────────────────────────────
+    // group_count のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn group_count_異なるグループの数を返す() {
+        let doc = serde_json::json!({"elements": [
+            {"type": "rectangle", "groupIds": ["g1"]},
+            {"type": "text", "groupIds": ["g1"]},
+            {"type": "ellipse", "groupIds": ["g2", "g1"]},
+            {"type": "line", "groupIds": []},
+            {"type": "arrow", "groupIds": ["g3"], "isDeleted": true}
+        ]});
+        assert_eq!(group_count(&doc), 2);
+        assert_eq!(group_count(&serde_json::json!({"elements": []})), 0);
+    }
+
+    // ──────────────────────────────────────────────
+    // find_stale_canvases_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn find_stale_canvases_in_古いものだけを古い順に返す() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        let sub = make_dir(base, "archive");
+        make_file(base, "older.excalidraw");
+        make_file(&sub, "old.excalidraw");
+        make_file(base, "recent.excalidraw");
+        set_mtime(&base.join("older.excalidraw"), MARCH_2023 - 86_400);
+        set_mtime(&sub.join("old.excalidraw"), MARCH_2023);
+        set_mtime(&base.join("recent.excalidraw"), MARCH_2023 + 360 * 86_400);
+
+        let now = UNIX_EPOCH + std::time::Duration::from_secs(MARCH_2023 + 365 * 86_400);
+        let paths: Vec<String> = find_stale_canvases_in(base, 30, now)
+            .unwrap()
+            .into_iter()
+            .map(|item| item.path)
+            .collect();
+        assert_eq!(paths, vec!["older.excalidraw", "archive/old.excalidraw"]);
+    }
+
+    #[test]
+    fn find_stale_canvases_in_極端な日数でもパニックしない() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        make_file(base, "old.excalidraw");
+        set_mtime(&base.join("old.excalidraw"), MARCH_2023);
+
+        let now = UNIX_EPOCH + std::time::Duration::from_secs(MARCH_2023 + 86_400);
+        assert!(find_stale_canvases_in(base, u32::MAX, now)
+            .unwrap()
+            .is_empty());
+    }
+
+    // ──────────────────────────────────────────────
+    // lint_folder_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn lint_folder_in_問題のあるキャンバスだけを報告する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        let project = make_dir(base, "project");
+        write_canvas(
+            &project,
+            "clean.excalidraw",
+            serde_json::json!([{"id": "a", "type": "rectangle", "x": 0, "y": 0}]),
+        );
+        fs::write(
+            project.join("messy.excalidraw"),
+            serde_json::json!({
+                "elements": [
+                    {"id": "a", "type": "image", "x": -10, "y": 5, "fileId": "gone"}
+                ]
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let before = fs::read_to_string(project.join("messy.excalidraw")).unwrap();
+
+        let results = lint_folder_in(base, "project").unwrap();
+        assert_eq!(
+            results,
+            vec![LintResult {
+                path: "project/messy.excalidraw".to_string(),
+                issues: vec![
+                    LintIssue::MissingFilesKey,
+                    LintIssue::Offscreen,
+                    LintIssue::BrokenImageRefs {
+                        file_ids: vec!["gone".to_string()]
+                    },
+                ],
+            }]
+        );
+        assert_eq!(
+            fs::read_to_string(project.join("messy.excalidraw")).unwrap(),
+            before
+        );
+    }
+
+    #[test]
+    fn lint_folder_in_空ファイルと壊れたjsonを報告する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        fs::write(base.join("empty.excalidraw"), "").unwrap();
+        fs::write(base.join("broken.excalidraw"), "{not json").unwrap();
+
+        let results = lint_folder_in(base, "").unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].path, "broken.excalidraw");
+        assert!(matches!(
+            results[0].issues[..],
+            [LintIssue::Unparseable { .. }]
+        ));
+        assert_eq!(results[1].issues, vec![LintIssue::ZeroByte]);
+        assert!(lint_folder_in(base, "missing").is_err());
+    }
+
+    // ──────────────────────────────────────────────
+    // autofix_folder_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn autofix_folder_in_修復できる問題を直して報告する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        let project = make_dir(base, "project");
+        fs::write(project.join("empty.excalidraw"), "").unwrap();
+        fs::write(
+            project.join("messy.excalidraw"),
+            serde_json::json!({
+                "elements": [
+                    {"id": "a", "type": "rectangle", "x": -10, "y": 5, "width": 20, "height": 20},
+                    {"id": "b", "type": "image", "x": 0, "y": -5, "fileId": "gone"}
+                ]
+            })
+            .to_string(),
+        )
+        .unwrap();
+        fs::write(project.join("broken.excalidraw"), "{not json").unwrap();
+        write_canvas(&project, "clean.excalidraw", serde_json::json!([]));
+
+        let results = autofix_folder_in(base, "project").unwrap();
+        assert_eq!(results.len(), 3);
+
+        assert_eq!(results[0].path, "project/broken.excalidraw");
+        assert!(results[0].fixed.is_empty());
+        assert_eq!(results[0].remaining.len(), 1);
+        assert_eq!(
+            fs::read_to_string(project.join("broken.excalidraw")).unwrap(),
+            "{not json"
+        );
+
+        assert_eq!(results[1].path, "project/empty.excalidraw");
+        assert_eq!(results[1].fixed, vec![LintIssue::ZeroByte]);
+
+        assert_eq!(results[2].path, "project/messy.excalidraw");
+        assert_eq!(
+            results[2].fixed,
+            vec![LintIssue::MissingFilesKey, LintIssue::Offscreen]
+        );
+        assert_eq!(
+            results[2].remaining,
+            vec![LintIssue::BrokenImageRefs {
+                file_ids: vec!["gone".to_string()]
+            }]
+        );
+        let doc = read_canvas_json(&project.join("messy.excalidraw")).unwrap();
+        assert_eq!(content_bounds(&doc).unwrap().0, 0.0);
+        assert_eq!(content_bounds(&doc).unwrap().1, 0.0);
+
+        let after = lint_folder_in(base, "project").unwrap();
+        assert_eq!(after.len(), 2);
+    }
+
+    // ──────────────────────────────────────────────
+    // walk_discovered のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn walk_discovered_隠しファイル以外の項目ごとに1件通知する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        let project = make_dir(base, "Project");
+        make_file(&project, "inner.excalidraw");
+        make_dir(base, "archive");
+        make_file(base, "b.excalidraw");
+        make_file(base, "A.excalidraw");
+        make_file(base, "notes.txt");
+        make_dir(base, ".versions");
+        make_file(base, ".recent.json");
+
+        let mut events = Vec::new();
+        let count = walk_discovered(base, base, &mut |item| events.push(item)).unwrap();
+
+        let item = |path: &str, is_folder: bool, parent: &str| DiscoveredItem {
+            path: path.to_string(),
+            is_folder,
+            parent: parent.to_string(),
+        };
+        assert_eq!(
+            events,
+            vec![
+                item("archive", true, ""),
+                item("Project", true, ""),
+                item("Project/inner.excalidraw", false, "Project"),
+                item("A.excalidraw", false, ""),
+                item("b.excalidraw", false, ""),
+            ]
+        );
+        assert_eq!(count, 5);
+    }
+
+    // ──────────────────────────────────────────────
+    // set_canvas_icon_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn set_canvas_icon_in_設定して一覧に反映される() {
+        let tmp = TempDir::new().unwrap();
+        let base = &tmp.path().to_path_buf();
+        make_file(base, "canvas.excalidraw");
+
+        set_canvas_icon_in(base, "canvas.excalidraw", Some("🎨")).unwrap();
+
+        assert_eq!(
+            load_item_icon(base, "canvas.excalidraw").0.as_deref(),
+            Some("🎨")
+        );
+        let items = collect_items(base, base).unwrap();
+        assert_eq!(items[0].icon.as_deref(), Some("🎨"));
+    }
+
+    #[test]
+    fn set_canvas_icon_in_色を保ったまま差し替えて消去できる() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        make_file(base, "canvas.excalidraw");
+        let meta = make_dir(base, ".meta");
+        fs::write(meta.join("canvas.excalidraw.icon"), "star:#ff0000").unwrap();
+
+        set_canvas_icon_in(base, "canvas.excalidraw", Some("📝")).unwrap();
+        assert_eq!(
+            load_item_icon(base, "canvas.excalidraw"),
+            (Some("📝".to_string()), Some("#ff0000".to_string()))
+        );
+
+        set_canvas_icon_in(base, "canvas.excalidraw", None).unwrap();
+        assert_eq!(load_item_icon(base, "canvas.excalidraw"), (None, None));
+    }
+
+    #[test]
+    fn set_canvas_icon_in_長すぎるアイコンを拒否する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        make_file(base, "canvas.excalidraw");
+
+        assert!(set_canvas_icon_in(base, "canvas.excalidraw", Some("")).is_err());
+        assert!(set_canvas_icon_in(base, "canvas.excalidraw", Some(&"x".repeat(17))).is_err());
+        assert!(set_canvas_icon_in(base, "canvas.excalidraw", Some("a:b")).is_err());
+        assert!(set_canvas_icon_in(base, "missing.excalidraw", Some("🎨")).is_err());
+    }
+
+    // ──────────────────────────────────────────────
+    // canvas_etag_at のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn canvas_etag_at_変更がなければ同じで変更すると変わる() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("canvas.excalidraw");
+        fs::write(&path, DEFAULT_CANVAS_CONTENT).unwrap();
+        set_mtime(&path, MARCH_2023);
+
+        let first = canvas_etag_at(&path, false).unwrap();
+        assert!(first.starts_with("W/\"") && first.ends_with('"'));
+        assert_eq!(canvas_etag_at(&path, false).unwrap(), first);
+
+        set_mtime(&path, MARCH_2023 + 1);
+        assert_ne!(canvas_etag_at(&path, false).unwrap(), first);
+    }
+
+    #[test]
+    fn canvas_etag_at_強いetagは内容だけに依存する() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("canvas.excalidraw");
+        fs::write(&path, DEFAULT_CANVAS_CONTENT).unwrap();
+
+        let strong = canvas_etag_at(&path, true).unwrap();
+        assert_eq!(
+            strong,
+            format!("\"{}\"", content_hash(DEFAULT_CANVAS_CONTENT.as_bytes()))
+        );
+        set_mtime(&path, MARCH_2023);
+        assert_eq!(canvas_etag_at(&path, true).unwrap(), strong);
+
+        fs::write(&path, "{}").unwrap();
+        assert_ne!(canvas_etag_at(&path, true).unwrap(), strong);
+    }
+
+    // ──────────────────────────────────────────────
+    // find_heavy_canvases_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn find_heavy_canvases_in_しきい値を超えるものを多い順に返す() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        let elements = |n: usize| {
+            serde_json::Value::Array(
+                (0..n)
+                    .map(|i| serde_json::json!({"id": format!("e{}", i), "type": "rectangle"}))
+                    .collect(),
+            )
+        };
+        write_canvas(base, "big.excalidraw", elements(100));
+        write_canvas(base, "medium.excalidraw", elements(60));
+        write_canvas(base, "small.excalidraw", elements(2));
+
+        assert_eq!(
+            find_heavy_canvases_in(base, 50).unwrap(),
+            vec![
+                ("big.excalidraw".to_string(), 100),
+                ("medium.excalidraw".to_string(), 60)
+            ]
+        );
+        assert!(find_heavy_canvases_in(base, 100).unwrap().is_empty());
+    }
+
+    // ──────────────────────────────────────────────
+    // 大文字小文字だけのリネームのテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn rename_plan_大文字小文字だけの変更は一時名を経由する() {
+        let from = Path::new("/vault/notes.excalidraw");
+        let to = Path::new("/vault/Notes.excalidraw");
+
+        let plan = rename_plan(from, to);
+        assert_eq!(plan.len(), 2);
+        assert_eq!(plan[0].0, from);
+        assert_eq!(plan[0].1, plan[1].0);
+        assert!(plan[0]
+            .1
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with('.'));
+        assert_eq!(plan[1].1, to);
+    }
+
+    #[test]
+    fn rename_plan_通常のリネームは一段階() {
+        let plan = rename_plan(
+            Path::new("/vault/notes.excalidraw"),
+            Path::new("/vault/ideas.excalidraw"),
+        );
+        assert_eq!(plan.len(), 1);
+    }
+
+    #[test]
+    fn rename_entry_大文字小文字だけのリネームを反映する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        make_file(base, "notes.excalidraw");
+
+        rename_entry(base, "notes.excalidraw", "Notes.excalidraw").unwrap();
+
+        let names: Vec<String> = fs::read_dir(base)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|n| n.ends_with(".excalidraw"))
+            .collect();
+        assert_eq!(names, vec!["Notes.excalidraw"]);
+    }
+
+    // ──────────────────────────────────────────────
+    // scene_size のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn scene_size_内容の範囲とページ数を返す() {
+        let doc = serde_json::json!({"elements": [
+            {"type": "rectangle", "x": -100, "y": 0, "width": 100, "height": 50},
+            {"type": "rectangle", "x": 1000, "y": 0, "width": 500, "height": 500}
+        ]});
+
+        let size = scene_size(&doc, 96.0);
+        assert_eq!(size.width, 1600.0);
+        assert_eq!(size.height, 500.0);
+        // A4 at 96 DPI is about 794 x 1123 px
+        assert_eq!(size.a4_pages_portrait, 3);
+        assert_eq!(size.a4_pages_landscape, 2);
+    }
+
+    #[test]
+    fn scene_size_空のキャンバスはゼロ() {
+        let size = scene_size(&serde_json::json!({"elements": []}), 96.0);
+        assert_eq!(size.width, 0.0);
+        assert_eq!(size.height, 0.0);
+        assert_eq!(size.a4_pages_portrait, 0);
+    }
+
+    // ──────────────────────────────────────────────
+    // rekey_embedded_files_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn rekey_embedded_files_in_内容のハッシュで付け替えて参照も更新する() {
+        // "iVBORw0KGgo=" は PNG シグネチャ 8 バイト
+        let hash = data_url_sha1("data:image/png;base64,iVBORw0KGgo=").unwrap();
+        assert_eq!(hash, "4caece539b039b16e16206ea2478f8c5ffb2ca05");
+
+        let mut doc = canvas_document(
+            vec![
+                serde_json::json!({ "id": "a", "type": "image", "fileId": "custom" }),
+                serde_json::json!({ "id": "b", "type": "image", "fileId": hash.clone() }),
+            ],
+            serde_json::json!({
+                "custom": image_file_entry("custom"),
+                hash.clone(): image_file_entry(&hash),
+            }),
+        );
+
+        assert_eq!(rekey_embedded_files_in(&mut doc), 1);
+
+        let files = doc["files"].as_object().unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[&hash]["id"], hash.as_str());
+        assert_eq!(doc["elements"][0]["fileId"], hash.as_str());
+        assert_eq!(doc["elements"][1]["fileId"], hash.as_str());
+        assert_eq!(rekey_embedded_files_in(&mut doc), 0);
+    }
+
+    // ──────────────────────────────────────────────
+    // write_subtree_archive のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn write_subtree_archive_フォルダのキャンバスとマニフェストを含む() {
+        let vault = TempDir::new().unwrap();
+        let out = TempDir::new().unwrap();
+        let base = vault.path();
+        let project = make_dir(base, "work/project");
+        make_file(&project, "a.excalidraw");
+        make_file(&make_dir(&project, "sub"), "b.excalidraw");
+        make_file(base, "outside.excalidraw");
+        let archive_path = out.path().join("project.zip");
+
+        let manifest = write_subtree_archive(base, "work/project", &archive_path).unwrap();
+        assert_eq!(manifest.folder, "work/project");
+        assert_eq!(manifest.canvases, vec!["a.excalidraw", "sub/b.excalidraw"]);
+
+        let mut names = zip_entry_names(&archive_path);
+        names.sort();
+        assert_eq!(
+            names,
+            vec!["a.excalidraw", "manifest.json", "sub/b.excalidraw"]
+        );
+
+        let mut archive = zip::ZipArchive::new(fs::File::open(&archive_path).unwrap()).unwrap();
+        let stored: SubtreeManifest =
+            serde_json::from_reader(archive.by_name(SUBTREE_MANIFEST).unwrap()).unwrap();
+        assert_eq!(stored, manifest);
+    }
+
+    #[test]
+    fn write_subtree_archive_フォルダでなければエラー() {
+        let vault = TempDir::new().unwrap();
+        let out = TempDir::new().unwrap();
+        make_file(vault.path(), "canvas.excalidraw");
+        let archive_path = out.path().join("x.zip");
+
+        assert!(write_subtree_archive(vault.path(), "canvas.excalidraw", &archive_path).is_err());
+        assert!(write_subtree_archive(vault.path(), "", &archive_path).is_err());
+        assert!(!archive_path.exists());
+    }
+
+    // ──────────────────────────────────────────────
+    // find_id_collisions_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn find_id_collisions_in_共通の要素idを持つ組を報告する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        write_canvas(
+            base,
+            "a.excalidraw",
+            serde_json::json!([{"id": "shared"}, {"id": "also"}, {"id": "only-a"}]),
+        );
+        write_canvas(
+            base,
+            "b.excalidraw",
+            serde_json::json!([{"id": "also"}, {"id": "shared"}, {"id": "only-b"}]),
+        );
+        write_canvas(
+            base,
+            "c.excalidraw",
+            serde_json::json!([{"id": "only-a", "isDeleted": true}, {"id": "unique"}]),
+        );
+
+        assert_eq!(
+            find_id_collisions_in(base).unwrap(),
+            vec![(
+                "a.excalidraw".to_string(),
+                "b.excalidraw".to_string(),
+                vec!["also".to_string(), "shared".to_string()]
+            )]
+        );
+    }
+
+    // ──────────────────────────────────────────────
+    // natural_cmp のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn natural_cmp_数字を数値として比較する() {
+        use std::cmp::Ordering;
+        assert_eq!(natural_cmp("canvas2", "canvas10"), Ordering::Less);
+        assert_eq!(natural_cmp("canvas10", "canvas2"), Ordering::Greater);
+        assert_eq!(natural_cmp("Canvas2", "canvas02"), Ordering::Less);
+        assert_eq!(natural_cmp("a", "B"), Ordering::Less);
+        assert_eq!(natural_cmp("file", "file1"), Ordering::Less);
+    }
+
+    #[test]
+    fn collect_items_sorted_自然順で並べる() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        make_file(base, "canvas10.excalidraw");
+        make_file(base, "canvas2.excalidraw");
+        make_file(base, "canvas1.excalidraw");
+        make_dir(base, "folder10");
+        make_dir(base, "folder9");
+
+        let names = |natural| -> Vec<String> {
+            collect_items_sorted(base, base, natural)
+                .unwrap()
+                .into_iter()
+                .map(|item| item.name)
+                .collect()
+        };
+        assert_eq!(
+            names(true),
+            vec![
+                "folder9",
+                "folder10",
+                "canvas1.excalidraw",
+                "canvas2.excalidraw",
+                "canvas10.excalidraw"
+            ]
+        );
+        assert_eq!(
+            names(false),
+            vec![
+                "folder10",
+                "folder9",
+                "canvas1.excalidraw",
+                "canvas10.excalidraw",
+                "canvas2.excalidraw"
+            ]
+        );
+    }
+
+    // ──────────────────────────────────────────────
+    // UI 状態のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn load_ui_state_in_保存した展開状態を読み戻す() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        make_dir(&base, "work/project");
+
+        save_ui_state_in(
+            tmp.path(),
+            vec!["work".to_string(), "work/project".to_string()],
+        )
+        .unwrap();
+
+        assert_eq!(
+            load_ui_state_in(tmp.path(), &base),
+            vec!["work", "work/project"]
+        );
+    }
+
+    #[test]
+    fn load_ui_state_in_存在しないパスを除外する() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let gone = make_dir(&base, "gone");
+        make_dir(&base, "kept");
+        make_file(&base, "canvas.excalidraw");
+        save_ui_state_in(
+            tmp.path(),
+            vec![
+                "gone".to_string(),
+                "kept".to_string(),
+                "canvas.excalidraw".to_string(),
+                "../canvases".to_string(),
+            ],
+        )
+        .unwrap();
+        fs::remove_dir(gone).unwrap();
+
+        assert_eq!(load_ui_state_in(tmp.path(), &base), vec!["kept"]);
+    }
+
+    #[test]
+    fn load_ui_state_in_ファイルがなければ空() {
+        let tmp = TempDir::new().unwrap();
+        assert!(load_ui_state_in(tmp.path(), tmp.path()).is_empty());
+    }
+
+    // ──────────────────────────────────────────────
+    // move_items_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn move_items_in_移動して自身の配下への移動は拒否する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        let target = make_dir(base, "parent/target");
+        make_file(base, "a.excalidraw");
+        make_file(base, "b.excalidraw");
+        make_file(&target, "b.excalidraw");
+
+        let results = move_items_in(
+            base,
+            &[
+                "a.excalidraw".to_string(),
+                "b.excalidraw".to_string(),
+                "parent".to_string(),
+            ],
+            "parent/target",
+        )
+        .unwrap();
+
+        assert_eq!(
+            results[0].new_path.as_deref(),
+            Some("parent/target/a.excalidraw")
+        );
+        assert_eq!(
+            results[1].new_path.as_deref(),
+            Some("parent/target/b 2.excalidraw")
+        );
+        assert!(results[2].error.is_some());
+        assert!(target.join("a.excalidraw").exists());
+        assert!(target.join("b 2.excalidraw").exists());
+        assert!(!base.join("a.excalidraw").exists());
+        assert!(base.join("parent").is_dir());
+    }
+
+    #[test]
+    fn move_items_in_移動先がフォルダでなければエラー() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        make_file(base, "a.excalidraw");
+
+        assert!(move_items_in(base, &["a.excalidraw".to_string()], "missing").is_err());
+        let results = move_items_in(base, &["missing.excalidraw".to_string()], "").unwrap();
+        assert!(results[0].error.is_some());
+    }
+
+    // ──────────────────────────────────────────────
+    // compare_snapshots_at のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn compare_snapshots_at_追加削除変更に振り分ける() {
+        let vault = TempDir::new().unwrap();
+        let out = TempDir::new().unwrap();
+        let base = vault.path();
+        let sub = make_dir(base, "folder");
+        fs::write(base.join("same.excalidraw"), "same").unwrap();
+        fs::write(sub.join("edited.excalidraw"), "before").unwrap();
+        fs::write(base.join("removed.excalidraw"), "gone").unwrap();
+        let archive_a = out.path().join("a.zip");
+        write_vault_archive(base, &archive_a, false).unwrap();
+
+        fs::write(sub.join("edited.excalidraw"), "after").unwrap();
+        fs::remove_file(base.join("removed.excalidraw")).unwrap();
+        fs::write(base.join("added.excalidraw"), "new").unwrap();
+        let archive_b = out.path().join("b.zip");
+        write_vault_archive(base, &archive_b, false).unwrap();
+
+        assert_eq!(
+            compare_snapshots_at(&archive_a, &archive_b).unwrap(),
+            SnapshotDiff {
+                added: vec!["added.excalidraw".to_string()],
+                removed: vec!["removed.excalidraw".to_string()],
+                changed: vec!["folder/edited.excalidraw".to_string()],
+            }
+        );
+    }
+
+    // ──────────────────────────────────────────────
+    // 容量上限のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn save_canvas_within_quota_上限内なら保存する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        fs::write(base.join("other.excalidraw"), "x".repeat(100)).unwrap();
+        fs::write(base.join("canvas.excalidraw"), "y".repeat(50)).unwrap();
+        let cache = VaultSizeCache::default();
+
+        save_canvas_within_quota(
+            base,
+            "canvas.excalidraw",
+            &"z".repeat(100),
+            false,
+            Some(200),
+            &cache,
+        )
+        .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(base.join("canvas.excalidraw")).unwrap(),
+            "z".repeat(100)
+        );
+        assert_eq!(cache.total(base).unwrap(), 200);
+    }
+
+    #[test]
+    fn save_canvas_within_quota_上限を超えるなら拒否する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        fs::write(base.join("other.excalidraw"), "x".repeat(100)).unwrap();
+        fs::write(base.join("canvas.excalidraw"), "y".repeat(50)).unwrap();
+        let cache = VaultSizeCache::default();
+
+        let err = save_canvas_within_quota(
+            base,
+            "canvas.excalidraw",
+            &"z".repeat(101),
+            false,
+            Some(200),
+            &cache,
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            err,
+            SaveError::QuotaExceeded {
+                limit: 200,
+                projected: 201
+            }
+        );
+        assert_eq!(
+            fs::read_to_string(base.join("canvas.excalidraw")).unwrap(),
+            "y".repeat(50)
+        );
+    }
+
+    #[test]
+    fn save_canvas_within_quota_圧縮キャンバスは圧縮後の大きさで判定する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        let cache = VaultSizeCache::default();
+        let content = format!(r#"{{"elements":[],"note":"{}"}}"#, "a".repeat(4_000));
+
+        save_canvas_within_quota(
+            base,
+            "big.excalidraw.gz",
+            &content,
+            false,
+            Some(1_000),
+            &cache,
+        )
+        .unwrap();
+
+        let on_disk = fs::metadata(base.join("big.excalidraw.gz")).unwrap().len();
+        assert!(on_disk < 1_000);
+        assert_eq!(cache.total(base).unwrap(), on_disk);
+        assert_eq!(
+            read_canvas_text(&base.join("big.excalidraw.gz")).unwrap(),
+            content
+        );
+    }
+
+    #[test]
+    fn save_canvas_within_quota_上限がなければそのまま保存する() {
+        let tmp = TempDir::new().unwrap();
+        let cache = VaultSizeCache::default();
+
+        save_canvas_within_quota(tmp.path(), "a/new.excalidraw", "{}", false, None, &cache)
+            .unwrap();
+        assert!(tmp.path().join("a/new.excalidraw").exists());
+    }
+
+    #[test]
+    fn load_settings_保存した設定を読み戻す() {
+        let tmp = TempDir::new().unwrap();
+        assert_eq!(load_settings(tmp.path()), Settings::default());
+
+        let settings = Settings {
+            max_vault_bytes: Some(1024),
+            max_concurrent_ops: Some(2),
+            namespaced_trash: true,
+        };
+        save_settings_in(tmp.path(), &settings).unwrap();
+        assert_eq!(load_settings(tmp.path()), settings);
+    }
+
+    // ──────────────────────────────────────────────
+    // find_remnants_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn find_remnants_in_中断された操作の残骸を報告する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        let project = make_dir(base, "project");
+        make_file(&project, "canvas.excalidraw");
+        make_file(&project, ".canvas.excalidraw.tmp");
+        fs::write(project.join("empty.excalidraw"), "").unwrap();
+        make_dir(base, "leftover");
+        let meta = make_dir(base, ".meta");
+        fs::write(meta.join("blank.icon"), "").unwrap();
+        make_file(&meta, ".x.icon.tmp");
+        make_dir(base, ".versions");
+
+        assert_eq!(
+            find_remnants_in(base).unwrap(),
+            vec![
+                ".meta/.x.icon.tmp",
+                "leftover",
+                "project/.canvas.excalidraw.tmp",
+                "project/empty.excalidraw",
+            ]
+        );
+    }
+
+    // ──────────────────────────────────────────────
+    // folder_element_histogram_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn folder_element_histogram_in_フォルダ内の種類別件数を合算する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        let project = make_dir(base, "project");
+        write_canvas(
+            &project,
+            "a.excalidraw",
+            serde_json::json!([{"type": "rectangle"}, {"type": "text"}, {"type": "rectangle"}]),
+        );
+        write_canvas(
+            &make_dir(&project, "sub"),
+            "b.excalidraw",
+            serde_json::json!([{"type": "text"}, {"type": "arrow", "isDeleted": true}]),
+        );
+        fs::write(project.join("broken.excalidraw"), "{").unwrap();
+        write_canvas(
+            base,
+            "outside.excalidraw",
+            serde_json::json!([{"type": "ellipse"}]),
+        );
+
+        let histogram = folder_element_histogram_in(base, "project").unwrap();
+        assert_eq!(histogram.len(), 2);
+        assert_eq!(histogram["rectangle"], 2);
+        assert_eq!(histogram["text"], 2);
+    }
+
+    // ──────────────────────────────────────────────
+    // compat_report のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn compat_report_対応バージョンなら互換と判定する() {
+        let report = compat_report(&serde_json::from_str(DEFAULT_CANVAS_CONTENT).unwrap());
+        assert_eq!(report.version, Some(CANVAS_VERSION));
+        assert!(report.is_supported);
+        assert_eq!(report.max_supported, CANVAS_VERSION);
+    }
+
+    #[test]
+    fn compat_report_範囲外やバージョンなしは非互換() {
+        let newer = compat_report(&serde_json::json!({"version": CANVAS_VERSION + 1}));
+        assert_eq!(newer.version, Some(CANVAS_VERSION + 1));
+        assert!(!newer.is_supported);
+
+        let missing = compat_report(&serde_json::json!({"elements": []}));
+        assert_eq!(missing.version, None);
+        assert!(!missing.is_supported);
+    }
+
+    // ──────────────────────────────────────────────
+    // swap_canvases_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn swap_canvases_in_内容を入れ替える() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        let sub = make_dir(base, "sub");
+        fs::write(base.join("a.excalidraw"), "content a").unwrap();
+        fs::write(sub.join("b.excalidraw"), "content b").unwrap();
+
+        swap_canvases_in(base, "a.excalidraw", "sub/b.excalidraw").unwrap();
+
+        assert_eq!(
+            fs::read_to_string(base.join("a.excalidraw")).unwrap(),
+            "content b"
+        );
+        assert_eq!(
+            fs::read_to_string(sub.join("b.excalidraw")).unwrap(),
+            "content a"
+        );
+        assert!(find_remnants_in(base).unwrap().is_empty());
+    }
+
+    #[test]
+    fn swap_canvases_in_片方がなければ何も変えない() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        fs::write(base.join("a.excalidraw"), "content a").unwrap();
+
+        assert!(swap_canvases_in(base, "a.excalidraw", "missing.excalidraw").is_err());
+        assert!(swap_canvases_in(base, "a.excalidraw", "a.excalidraw").is_err());
+        assert_eq!(
+            fs::read_to_string(base.join("a.excalidraw")).unwrap(),
+            "content a"
+        );
+    }
+
+    // ──────────────────────────────────────────────
+    // extract_elements_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn extract_elements_in_指定した要素だけを新しいidで書き出す() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        fs::write(
+            base.join("source.excalidraw"),
+            canvas_document(
+                vec![
+                    serde_json::json!({"id": "img", "type": "image", "fileId": "f1",
+                        "boundElements": [{"id": "arrow", "type": "arrow"}]}),
+                    serde_json::json!({"id": "arrow", "type": "arrow",
+                        "startBinding": {"elementId": "img", "focus": 0, "gap": 1}}),
+                    serde_json::json!({"id": "other", "type": "rectangle"}),
+                ],
+                serde_json::json!({
+                    "f1": image_file_entry("f1"),
+                    "f2": image_file_entry("f2"),
+                }),
+            )
+            .to_string(),
+        )
+        .unwrap();
+
+        let count = extract_elements_in(
+            base,
+            "source.excalidraw",
+            &["img".to_string()],
+            "out/extracted.excalidraw",
+        )
+        .unwrap();
+        assert_eq!(count, 1);
+
+        let doc = read_canvas_json(&base.join("out/extracted.excalidraw")).unwrap();
+        let elements = doc["elements"].as_array().unwrap();
+        assert_eq!(elements.len(), 1);
+        assert_eq!(elements[0]["type"], "image");
+        assert_ne!(elements[0]["id"], "img");
+        assert_eq!(elements[0]["boundElements"], serde_json::json!([]));
+        assert_eq!(
+            doc["files"].as_object().unwrap().keys().collect::<Vec<_>>(),
+            vec!["f1"]
+        );
+    }
+
+    #[test]
+    fn extract_elements_in_コンテナ内のテキストも含めて参照を付け替える() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        write_canvas(
+            base,
+            "source.excalidraw",
+            serde_json::json!([
+                {"id": "box", "type": "rectangle", "boundElements": [{"id": "label", "type": "text"}]},
+                {"id": "label", "type": "text", "containerId": "box", "text": "hi"}
+            ]),
+        );
+
+        extract_elements_in(
+            base,
+            "source.excalidraw",
+            &["box".to_string()],
+            "out.excalidraw",
+        )
+        .unwrap();
+
+        let doc = read_canvas_json(&base.join("out.excalidraw")).unwrap();
+        let elements = doc["elements"].as_array().unwrap();
+        assert_eq!(elements.len(), 2);
+        assert_eq!(elements[1]["containerId"], elements[0]["id"]);
+        assert_eq!(elements[0]["boundElements"][0]["id"], elements[1]["id"]);
+        assert!(extract_elements_in(
+            base,
+            "source.excalidraw",
+            &["box".to_string()],
+            "out.excalidraw"
+        )
+        .is_err());
+        assert!(extract_elements_in(
+            base,
+            "source.excalidraw",
+            &["nope".to_string()],
+            "none.excalidraw"
+        )
+        .is_err());
+    }
+
+    // ──────────────────────────────────────────────
+    // depth_histogram_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn depth_histogram_in_深さごとのフォルダとキャンバス数を返す() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        let work = make_dir(base, "work");
+        make_dir(base, "empty");
+        make_file(base, "root.excalidraw");
+        make_file(&work, "a.excalidraw");
+        make_file(&work, "b.excalidraw");
+        make_dir(&work, "nested");
+        make_file(&work, "notes.txt");
+        make_dir(base, ".versions");
+
+        assert_eq!(
+            depth_histogram_in(base).unwrap(),
+            vec![(0, 2, 1), (1, 1, 2)]
+        );
+    }
+
+    // ──────────────────────────────────────────────
+    // 孤立したバージョン履歴のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn delete_entry_キャンバスのバージョン履歴も削除する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        let folder = make_dir(base, "folder");
+        make_file(&folder, "inner.excalidraw");
+        make_file(base, "canvas.excalidraw");
+        make_file(base, "kept.excalidraw");
+        for path in [
+            "folder/inner.excalidraw",
+            "canvas.excalidraw",
+            "kept.excalidraw",
+        ] {
+            create_version_in(base, path).unwrap();
+        }
+
+        delete_entry(base, "canvas.excalidraw").unwrap();
+        delete_entry(base, "folder").unwrap();
+
+        assert!(!get_versions_dir(base, "canvas.excalidraw").exists());
+        assert!(!get_versions_dir(base, "folder/inner.excalidraw").exists());
+        assert_eq!(list_versions_in(base, "kept.excalidraw").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn delete_from_trash_完全削除でバージョン履歴も削除する() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = tmp.path().join("trash");
+        make_file(&base, "canvas.excalidraw");
+        create_version_in(&base, "canvas.excalidraw").unwrap();
+        let trash_name = move_to_trash(&base, &trash, "canvas.excalidraw").unwrap();
+        // ゴミ箱にある間は履歴を残す
+        assert!(get_versions_dir(&base, "canvas.excalidraw").exists());
+
+        delete_from_trash(&base, &trash, &trash_name).unwrap();
+
+        assert!(!trash.join(&trash_name).exists());
+        assert!(!get_versions_dir(&base, "canvas.excalidraw").exists());
+    }
+
+    #[test]
+    fn prune_orphan_versions_in_実体のない履歴フォルダだけ削除する() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = tmp.path().join("trash");
+        make_file(&base, "live.excalidraw");
+        make_file(&base, "gone.excalidraw");
+        create_version_in(&base, "live.excalidraw").unwrap();
+        create_version_in(&base, "gone.excalidraw").unwrap();
+        fs::remove_file(base.join("gone.excalidraw")).unwrap();
+
+        assert_eq!(prune_orphan_versions_in(&base, &trash).unwrap(), 1);
+        assert!(get_versions_dir(&base, "live.excalidraw").exists());
+        assert!(!get_versions_dir(&base, "gone.excalidraw").exists());
+        assert_eq!(prune_orphan_versions_in(&base, &trash).unwrap(), 0);
+    }
+
+    #[test]
+    fn prune_orphan_versions_in_ゴミ箱にあるキャンバスの履歴は残す() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = tmp.path().join("trash");
+        let folder = make_dir(&base, "proj/sub");
+        make_file(&folder, "inner.excalidraw");
+        make_file(&base, "canvas.excalidraw");
+        create_version_in(&base, "proj/sub/inner.excalidraw").unwrap();
+        create_version_in(&base, "canvas.excalidraw").unwrap();
+        let canvas_trash_name = move_to_trash(&base, &trash, "canvas.excalidraw").unwrap();
+        // フォルダは名前空間付きのゴミ箱へ入れる
+        let folder_trash = trash_for(&trash, "proj/sub", true);
+        let folder_trash_name = move_to_trash(&base, &folder_trash, "proj/sub").unwrap();
+
+        assert_eq!(prune_orphan_versions_in(&base, &trash).unwrap(), 0);
+
+        restore_from_trash(&base, &trash, &canvas_trash_name).unwrap();
+        restore_from_trash(&base, &trash, &format!("proj/{}", folder_trash_name)).unwrap();
+        assert_eq!(
+            list_versions_in(&base, "canvas.excalidraw").unwrap().len(),
+            1
+        );
+        assert_eq!(
+            list_versions_in(&base, "proj/sub/inner.excalidraw")
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    // ──────────────────────────────────────────────
+    // 開いた回数のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn record_open_in_開くたびに回数を加算する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        make_file(base, "a.excalidraw");
+
+        record_open_in(base, "a.excalidraw").unwrap();
+        record_open_in(base, "a.excalidraw").unwrap();
+
+        assert_eq!(load_open_counts(base)["a.excalidraw"], 2);
+    }
+
+    #[test]
+    fn list_most_opened_in_回数の多い順に上位を返す() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        for (name, opens) in [("a", 1), ("b", 3), ("c", 2), ("gone", 5)] {
+            let path = format!("{}.excalidraw", name);
+            make_file(base, &path);
+            for _ in 0..opens {
+                record_open_in(base, &path).unwrap();
+            }
+        }
+        fs::remove_file(base.join("gone.excalidraw")).unwrap();
+
+        assert_eq!(
+            list_most_opened_in(base, 2),
+            vec![
+                ("b.excalidraw".to_string(), 3),
+                ("c.excalidraw".to_string(), 2)
+            ]
+        );
+    }
+
+    // ──────────────────────────────────────────────
+    // リネーム時の履歴移動のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn rename_entry_バージョン履歴も新しい名前に移る() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        make_file(base, "old.excalidraw");
+        create_version_in(base, "old.excalidraw").unwrap();
+        create_version_in(base, "old.excalidraw").unwrap();
+
+        rename_entry(base, "old.excalidraw", "new.excalidraw").unwrap();
+
+        assert_eq!(list_versions_in(base, "new.excalidraw").unwrap().len(), 2);
+        assert!(list_versions_in(base, "old.excalidraw").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rename_entry_フォルダ内のキャンバスの履歴も移る() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        make_dir(base, "folder/sub");
+        make_file(base, "folder/sub/a.excalidraw");
+        create_version_in(base, "folder/sub/a.excalidraw").unwrap();
+
+        rename_entry(base, "folder", "renamed").unwrap();
+
+        assert_eq!(
+            list_versions_in(base, "renamed/sub/a.excalidraw")
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    // ──────────────────────────────────────────────
+    // export_path_list のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn export_path_list_to_パスを1行ずつ整列して書き出す() {
+        let vault = TempDir::new().unwrap();
+        let out = TempDir::new().unwrap();
+        let base = vault.path();
+        make_dir(base, "b");
+        make_file(base, "b/z.excalidraw");
+        make_file(base, "c.excalidraw");
+        make_file(base, "a.excalidraw");
+        make_file(base, "notes.txt");
+
+        let dest = out.path().join("paths.txt");
+        assert_eq!(export_path_list_to(base, &dest).unwrap(), 3);
+
+        assert_eq!(
+            fs::read_to_string(&dest).unwrap(),
+            "a.excalidraw\nb/z.excalidraw\nc.excalidraw\n"
+        );
+    }
+
+    // ──────────────────────────────────────────────
+    // find_title_mismatches のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn find_title_mismatches_in_ファイル名と異なるタイトルだけを返す() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        write_canvas(
+            base,
+            "plan.excalidraw",
+            serde_json::json!([
+                {"id": "r", "type": "rectangle"},
+                {"id": "t", "type": "text", "text": " plan \nsecond line"}
+            ]),
+        );
+        write_canvas(
+            base,
+            "draft.excalidraw",
+            serde_json::json!([
+                {"id": "old", "type": "text", "text": "draft", "isDeleted": true},
+                {"id": "t", "type": "text", "text": "Roadmap"}
+            ]),
+        );
+        write_canvas(base, "untitled.excalidraw", serde_json::json!([]));
+
+        assert_eq!(
+            find_title_mismatches_in(base).unwrap(),
+            vec![(
+                "draft.excalidraw".to_string(),
+                "Roadmap".to_string(),
+                "draft".to_string()
+            )]
+        );
+    }
+
+    // ──────────────────────────────────────────────
+    // 圧縮保存のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn compress_vault_in_往復で内容を保つ() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        make_dir(base, "work");
+        write_canvas(
+            base,
+            "a.excalidraw",
+            serde_json::json!([{"id": "a", "type": "rectangle"}]),
+        );
+        write_canvas(
+            base,
+            "work/b.excalidraw",
+            serde_json::json!([{"id": "b", "type": "text", "text": "日本語"}]),
+        );
+        let before: Vec<String> = ["a.excalidraw", "work/b.excalidraw"]
+            .iter()
+            .map(|p| fs::read_to_string(base.join(p)).unwrap())
+            .collect();
+
+        assert_eq!(compress_vault_in(base).unwrap(), 2);
+        assert_eq!(
+            walk_canvases(base, base).unwrap(),
+            vec!["a.excalidraw.gz", "work/b.excalidraw.gz"]
+        );
+
+        assert_eq!(decompress_vault_in(base).unwrap(), 2);
+        let after: Vec<String> = ["a.excalidraw", "work/b.excalidraw"]
+            .iter()
+            .map(|p| fs::read_to_string(base.join(p)).unwrap())
+            .collect();
+        assert_eq!(after, before);
+        assert!(!base.join("a.excalidraw.gz").exists());
+    }
+
+    #[test]
+    fn compress_vault_in_圧縮後も読み書きでき履歴と付随情報を保つ() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        let trash = tmp.path().join(".trash");
+        write_canvas(base, "a.excalidraw", serde_json::json!([{"id": "a"}]));
+        create_version_in(base, "a.excalidraw").unwrap();
+        make_dir(base, ".meta");
+        fs::write(get_icon_meta_path(base, "a.excalidraw"), "Star").unwrap();
+        record_open_in(base, "a.excalidraw").unwrap();
+
+        compress_vault_in(base).unwrap();
+        let full_path = base.join("a.excalidraw.gz");
+        let doc: serde_json::Value =
+            serde_json::from_str(&read_canvas_text(&full_path).unwrap()).unwrap();
+        assert_eq!(doc["elements"][0]["id"], "a");
+
+        let mut doc = doc;
+        doc["elements"][0]["id"] = "b".into();
+        save_canvas_at(&full_path, &doc.to_string(), false).unwrap();
+        assert_ne!(fs::read(&full_path).unwrap(), doc.to_string().into_bytes());
+        assert_eq!(
+            read_canvas_json(&full_path).unwrap()["elements"][0]["id"],
+            "b"
+        );
+
+        assert_eq!(prune_orphan_versions_in(base, &trash).unwrap(), 0);
+        assert_eq!(list_versions_in(base, "a.excalidraw.gz").unwrap().len(), 1);
+        assert_eq!(
+            load_item_icon(base, "a.excalidraw.gz").0.as_deref(),
+            Some("Star")
+        );
+        assert_eq!(load_open_counts(base)["a.excalidraw.gz"], 1);
+        assert_eq!(load_recent(base), vec!["a.excalidraw.gz".to_string()]);
+    }
+
+    #[test]
+    fn collect_items_sorted_圧縮されたキャンバスも一覧に含める() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        write_canvas(base, "a.excalidraw", serde_json::json!([]));
+        compress_vault_in(base).unwrap();
+
+        let items = collect_items_sorted(base, base, false).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].path, "a.excalidraw.gz");
+    }
+
+    // ──────────────────────────────────────────────
+    // find_giant_elements のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn find_giant_elements_in_上限を超える要素を報告する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        write_canvas(
+            base,
+            "broken.excalidraw",
+            serde_json::json!([
+                {"id": "huge", "type": "rectangle", "width": 1e9, "height": 10},
+                {"id": "tall", "type": "line", "width": 0, "height": -5e6},
+                {"id": "ok", "type": "rectangle", "width": 100, "height": 100},
+                {"id": "gone", "type": "rectangle", "width": 1e9, "height": 1, "isDeleted": true}
+            ]),
+        );
+        write_canvas(
+            base,
+            "fine.excalidraw",
+            serde_json::json!([{"id": "a", "type": "rectangle", "width": 500, "height": 500}]),
+        );
+
+        assert_eq!(
+            find_giant_elements_in(base, 100_000.0).unwrap(),
+            vec![
+                ("broken.excalidraw".to_string(), "huge".to_string()),
+                ("broken.excalidraw".to_string(), "tall".to_string())
+            ]
+        );
+    }
+
+    // ──────────────────────────────────────────────
+    // read_canvas_light のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn strip_file_data_データだけを置き換えてキーを保つ() {
+        let mut doc = serde_json::json!({
+            "elements": [{"id": "img", "type": "image", "fileId": "f1"}],
+            "files": {
+                "f1": {"id": "f1", "mimeType": "image/png", "dataURL": "data:image/png;base64,AAAA"},
+                "f2": {"id": "f2", "mimeType": "image/svg+xml", "dataURL": "data:image/svg+xml;base64,BBBB"}
+            }
+        });
+
+        strip_file_data(&mut doc);
+
+        let files = doc["files"].as_object().unwrap();
+        assert_eq!(files.keys().collect::<Vec<_>>(), vec!["f1", "f2"]);
+        assert_eq!(files["f1"]["dataURL"], LIGHT_DATA_URL_PLACEHOLDER);
+        assert_eq!(files["f1"]["mimeType"], "image/png");
+        assert_eq!(files["f2"]["dataURL"], LIGHT_DATA_URL_PLACEHOLDER);
+        assert_eq!(files["f2"]["mimeType"], "image/svg+xml");
+        assert_eq!(doc["elements"][0]["fileId"], "f1");
+    }
+
+    // ──────────────────────────────────────────────
+    // trash_needs_repair_in のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn trash_needs_repair_in_整合していれば不要() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = make_dir(tmp.path(), "trash");
+        make_file(&base, "a.excalidraw");
+        move_to_trash(&base, &trash, "a.excalidraw").unwrap();
+
+        assert!(!trash_needs_repair_in(&trash).unwrap());
+    }
+
+    #[test]
+    fn trash_needs_repair_in_メタのない項目を検出する() {
+        let tmp = TempDir::new().unwrap();
+        let trash = make_dir(tmp.path(), "trash");
+        make_file(&trash, "orphan.excalidraw");
+
+        assert!(trash_needs_repair_in(&trash).unwrap());
+    }
+
+    #[test]
+    fn trash_needs_repair_in_実体のないメタを検出する() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = make_dir(tmp.path(), "trash");
+        make_file(&base, "a.excalidraw");
+        let name = move_to_trash(&base, &trash, "a.excalidraw").unwrap();
+        fs::remove_file(trash.join(name)).unwrap();
+
+        assert!(trash_needs_repair_in(&trash).unwrap());
+    }
+
+    #[test]
+    fn repair_trash_in_孤立した項目だけを整理し他は残す() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = make_dir(tmp.path(), "trash");
+        make_file(&base, "kept.excalidraw");
+        make_file(&base, "gone.excalidraw");
+        let kept = move_to_trash(&base, &trash, "kept.excalidraw").unwrap();
+        let gone = move_to_trash(&base, &trash, "gone.excalidraw").unwrap();
+        fs::remove_file(trash.join(gone)).unwrap();
+        make_file(&trash, "1700000000000_orphan.excalidraw");
+        fs::write(
+            trash.join("missing.excalidraw.meta"),
+            r#"{"original_path":"missing.excalidraw","trashed_at":1}"#,
+        )
+        .unwrap();
+
+        assert_eq!(repair_trash_in(&trash, None).unwrap(), 3);
+
+        assert!(!trash_needs_repair_in(&trash).unwrap());
+        assert!(trash.join(&kept).exists());
+        assert!(!trash.join("missing.excalidraw.meta").exists());
+        let mut items: Vec<(String, u64)> = read_trash_items(&trash)
+            .unwrap()
+            .into_iter()
+            .map(|item| (item.original_path, item.trashed_at))
+            .collect();
+        items.sort();
+        assert_eq!(items.len(), 2);
+        assert_eq!(
+            items[1],
+            ("orphan.excalidraw".to_string(), 1_700_000_000_000)
+        );
+        assert_eq!(items[0].0, "kept.excalidraw");
+    }
+
+    #[test]
+    fn repair_trash_in_名前空間内の孤立項目はフォルダ配下に戻す() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = make_dir(tmp.path(), "trash");
+        make_dir(&base, "work");
+        make_file(&base, "work/a.excalidraw");
+        trash_namespaced(&base, &trash, "work/a.excalidraw");
+        make_file(&trash.join("work"), "5_2_b.excalidraw");
+
+        assert_eq!(repair_trash_in(&trash, None).unwrap(), 1);
+
+        let index = read_trash_index(&trash.join("work"));
+        assert_eq!(index["5_2_b.excalidraw"].original_path, "work/b.excalidraw");
+        assert_eq!(index["5_2_b.excalidraw"].trashed_at, 5);
+    }
+
+    // ──────────────────────────────────────────────
+    // group_by_modified_week のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn iso_week_key_年をまたぐ週はiso年で数える() {
+        // 2021-01-01 は 2020 年の第 53 週
+        assert_eq!(iso_week_key(1_609_459_200), "2020-W53");
+        assert_eq!(iso_week_key(MARCH_2023), "2023-W11");
+    }
+
+    #[test]
+    fn group_by_modified_week_in_週ごとに新しい順でまとめる() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        make_file(base, "old.excalidraw");
+        make_file(base, "new.excalidraw");
+        set_mtime(&base.join("old.excalidraw"), MARCH_2023);
+        set_mtime(&base.join("new.excalidraw"), MARCH_2023 + 7 * 86_400);
+
+        let groups = group_by_modified_week_in(base).unwrap();
+
+        let summary: Vec<(&str, Vec<&str>)> = groups
+            .iter()
+            .map(|(week, items)| {
+                (
+                    week.as_str(),
+                    items.iter().map(|item| item.path.as_str()).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("2023-W12", vec!["new.excalidraw"]),
+                ("2023-W11", vec!["old.excalidraw"])
+            ]
+        );
+    }
+
+    // ──────────────────────────────────────────────
+    // replace_text_in_canvases のテスト
+    // ──────────────────────────────────────────────
+
+    fn write_product_canvases(base: &std::path::Path) {
+        write_canvas(
+            base,
+            "a.excalidraw",
+            serde_json::json!([
+                {"id": "t1", "type": "text", "text": "Acme Acme", "originalText": "Acme Acme"},
+                {"id": "t2", "type": "text", "text": "About Acme"},
+                {"id": "old", "type": "text", "text": "Acme", "isDeleted": true}
+            ]),
+        );
+        write_canvas(
+            base,
+            "b.excalidraw",
+            serde_json::json!([{"id": "t", "type": "text", "text": "unrelated"}]),
+        );
+    }
+
+    #[test]
+    fn replace_text_in_canvases_in_ドライランは件数だけ返す() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        write_product_canvases(base);
+        let before = fs::read_to_string(base.join("a.excalidraw")).unwrap();
+
+        assert_eq!(
+            replace_text_in_canvases_in(base, "Acme", "Globex", true).unwrap(),
+            vec![("a.excalidraw".to_string(), 3)]
+        );
+        assert_eq!(
+            fs::read_to_string(base.join("a.excalidraw")).unwrap(),
+            before
+        );
+    }
+
+    #[test]
+    fn replace_text_in_canvases_in_保存されたテキストを置き換える() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        write_product_canvases(base);
+
+        replace_text_in_canvases_in(base, "Acme", "Globex", false).unwrap();
+
+        let doc = read_canvas_json(&base.join("a.excalidraw")).unwrap();
+        assert_eq!(doc["elements"][0]["text"], "Globex Globex");
+        assert_eq!(doc["elements"][0]["originalText"], "Globex Globex");
+        assert_eq!(doc["elements"][1]["text"], "About Globex");
+        assert_eq!(doc["elements"][2]["text"], "Acme");
+    }
+
+    // ──────────────────────────────────────────────
+    // FsSemaphore のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn fs_semaphore_同時実行数を上限までに抑える() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let semaphore = FsSemaphore::new(2);
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    semaphore.run(|| {
+                        let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                        peak.fetch_max(now, Ordering::SeqCst);
+                        std::thread::sleep(std::time::Duration::from_millis(20));
+                        running.fetch_sub(1, Ordering::SeqCst);
+                    })
+                });
+            }
+        });
+
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn fs_semaphore_成功でも失敗でも許可を返す() {
+        let semaphore = FsSemaphore::new(1);
+
+        let failed: Result<(), String> = semaphore.run(|| Err("boom".to_string()));
+        assert!(failed.is_err());
+        let succeeded: Result<u8, String> = semaphore.run(|| Ok(1));
+        assert_eq!(succeeded, Ok(1));
+        assert_eq!(*semaphore.state.lock().unwrap(), (0, 1));
+    }
+
+    // ──────────────────────────────────────────────
+    // vault_storage_type のテスト
+    // ──────────────────────────────────────────────
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn storage_type_from_mounts_最も深いマウントポイントで判定する() {
+        let mounts = "\
+/dev/sda1 / ext4 rw,relatime 0 0
+server:/export /mnt/nas nfs4 rw 0 0
+//host/share /mnt/smb\\040share cifs rw 0 0
+/dev/sdb1 /run/media/user/USB vfat rw 0 0
+/dev/sdc1 /mnt/nas/local ext4 rw 0 0
+";
+        let detect = |path: &str| storage_type_from_mounts(mounts, Path::new(path));
+
+        assert_eq!(detect("/home/user/canvases"), StorageType::Local);
+        assert_eq!(detect("/mnt/nas/vault"), StorageType::Network);
+        assert_eq!(detect("/mnt/smb share/vault"), StorageType::Network);
+        assert_eq!(detect("/run/media/user/USB/vault"), StorageType::Removable);
+        assert_eq!(detect("/mnt/nas/local/vault"), StorageType::Local);
+        assert_eq!(
+            storage_type_from_mounts("", Path::new("/vault")),
+            StorageType::Unknown
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn storage_type_from_drive_type_ドライブ種別を対応付ける() {
+        assert_eq!(storage_type_from_drive_type(2), StorageType::Removable);
+        assert_eq!(storage_type_from_drive_type(3), StorageType::Local);
+        assert_eq!(storage_type_from_drive_type(4), StorageType::Network);
+        assert_eq!(storage_type_from_drive_type(0), StorageType::Unknown);
+    }
+
+    // ──────────────────────────────────────────────
+    // duplicate_item のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn duplicate_entry_既存の名前を避けて番号を振る() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        make_file(base, "note.excalidraw");
+        make_file(base, "note (copy 2).excalidraw");
+
+        assert_eq!(
+            duplicate_entry(base, "note.excalidraw").unwrap(),
+            "note (copy).excalidraw"
+        );
+        assert_eq!(
+            duplicate_entry(base, "note.excalidraw").unwrap(),
+            "note (copy 3).excalidraw"
+        );
+        assert_eq!(
+            fs::read_to_string(base.join("note (copy).excalidraw")).unwrap(),
+            fs::read_to_string(base.join("note.excalidraw")).unwrap()
+        );
+    }
+
+    #[test]
+    fn duplicate_entry_ルートは複製しない() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        make_file(&base, "a.excalidraw");
+
+        for path in ["", ".", "./"] {
+            assert!(duplicate_entry(&base, path).is_err());
+        }
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn duplicate_entry_フォルダは配下ごと複製する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        make_dir(base, "work/nested");
+        make_file(base, "work/a.excalidraw");
+        make_file(base, "work/nested/b.excalidraw");
+
+        assert_eq!(duplicate_entry(base, "work").unwrap(), "work (copy)");
+        assert_eq!(
+            walk_canvases(base, &base.join("work (copy)")).unwrap(),
+            vec![
+                "work (copy)/a.excalidraw",
+                "work (copy)/nested/b.excalidraw"
+            ]
+        );
+        assert!(base.join("work/nested/b.excalidraw").is_file());
+    }
+
+    // ──────────────────────────────────────────────
+    // Mermaid 取り込みのテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn create_canvas_from_mermaid_in_ノード2つと矢印1本を生成する() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+
+        create_canvas_from_mermaid_in(
+            base,
+            "flowchart TD\n    A[Start] --> B[End]\n",
+            "flow.excalidraw",
+        )
+        .unwrap();
+
+        let doc = read_canvas_json(&base.join("flow.excalidraw")).unwrap();
+        let of_type = |t: &str| -> Vec<&serde_json::Value> {
+            live_elements(&doc).filter(|el| el["type"] == t).collect()
+        };
+        assert_eq!(of_type("rectangle").len(), 2);
+        let arrows = of_type("arrow");
+        assert_eq!(arrows.len(), 1);
+        assert_eq!(arrows[0]["startBinding"]["elementId"], "mermaid-node-A");
+        assert_eq!(arrows[0]["endBinding"]["elementId"], "mermaid-node-B");
+        let labels: Vec<&str> = of_type("text")
+            .iter()
+            .filter_map(|el| el["text"].as_str())
+            .collect();
+        assert_eq!(labels, vec!["Start", "End"]);
     }
 
-    fn make_dir(dir: &std::path::Path, name: &str) -> PathBuf {
-        let p = dir.join(name);
-        fs::create_dir_all(&p).unwrap();
-        p
+    #[test]
+    fn parse_mermaid_flowchart_連鎖とラベルと形を読む() {
+        let graph =
+            parse_mermaid_flowchart("graph LR\n%% comment\nA(Begin) -->|go| B{Ok?} --- C; B --> A")
+                .unwrap();
+
+        assert!(!graph.vertical);
+        let ids: Vec<&str> = graph.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, vec!["A", "B", "C"]);
+        assert!(graph.nodes[0].rounded);
+        assert_eq!(graph.nodes[1].shape, "diamond");
+        assert_eq!(graph.nodes[2].label, "C");
+        assert_eq!(
+            graph.edges,
+            vec![
+                MermaidEdge {
+                    from: 0,
+                    to: 1,
+                    label: Some("go".to_string()),
+                    arrowhead: true
+                },
+                MermaidEdge {
+                    from: 1,
+                    to: 2,
+                    label: None,
+                    arrowhead: false
+                },
+                MermaidEdge {
+                    from: 1,
+                    to: 0,
+                    label: None,
+                    arrowhead: true
+                },
+            ]
+        );
     }
 
     #[test]
-    fn collect_items_空ディレクトリは空ベクタを返す() {
+    fn parse_mermaid_flowchart_未対応の構文を報告する() {
+        let err = parse_mermaid_flowchart("flowchart TD\nA --> B\nsubgraph one\nB -.-> C\nend")
+            .unwrap_err();
+
+        assert!(err.contains("line 3: subgraph one"), "{}", err);
+        assert!(err.contains("line 4: B -.-> C"), "{}", err);
+        assert!(err.contains("line 5: end"), "{}", err);
+        assert!(parse_mermaid_flowchart("sequenceDiagram\nA->>B: hi").is_err());
+    }
+
+    // ──────────────────────────────────────────────
+    // get_base_directory_canonical のテスト
+    // ──────────────────────────────────────────────
+
+    #[cfg(unix)]
+    #[test]
+    fn canonical_dir_string_シンボリックリンクを実体に解決する() {
         let tmp = TempDir::new().unwrap();
-        let base = tmp.path().to_path_buf();
-        let result = collect_items(&base, &base).unwrap();
-        assert!(result.is_empty());
+        let real = make_dir(tmp.path(), "real");
+        let link = tmp.path().join("link");
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+
+        assert_eq!(
+            canonical_dir_string(&link).unwrap(),
+            real.canonicalize().unwrap().to_string_lossy()
+        );
     }
 
     #[test]
-    fn collect_items_excalidrawファイルのみ収集する() {
+    fn canonical_dir_string_存在しなければエラー() {
         let tmp = TempDir::new().unwrap();
-        let base = tmp.path().to_path_buf();
-        make_file(&base, "canvas.excalidraw");
-        make_file(&base, "README.md");
-        make_file(&base, "image.png");
+        assert!(canonical_dir_string(&tmp.path().join("missing")).is_err());
+    }
 
-        let result = collect_items(&base, &base).unwrap();
-        assert_eq!(result.len(), 1);
-        assert_eq!(result[0].name, "canvas.excalidraw");
-        assert!(!result[0].is_folder);
+    // ──────────────────────────────────────────────
+    // リネーム時の上書き防止のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn rename_entry_既存のファイルを上書きしない() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        fs::write(base.join("a.excalidraw"), "a").unwrap();
+        fs::write(base.join("b.excalidraw"), "b").unwrap();
+
+        let err = rename_entry(base, "a.excalidraw", "b.excalidraw").unwrap_err();
+
+        assert_eq!(err, "移動先に同名のアイテムが既に存在します");
+        assert_eq!(fs::read_to_string(base.join("a.excalidraw")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(base.join("b.excalidraw")).unwrap(), "b");
     }
 
     #[test]
-    fn collect_items_ドットで始まるファイルをスキップする() {
+    fn rename_entry_既存のフォルダを上書きしない() {
         let tmp = TempDir::new().unwrap();
-        let base = tmp.path().to_path_buf();
-        make_file(&base, ".hidden.excalidraw");
-        make_file(&base, "visible.excalidraw");
+        let base = tmp.path();
+        make_dir(base, "from");
+        make_file(base, "from/a.excalidraw");
+        make_dir(base, "to");
 
-        let result = collect_items(&base, &base).unwrap();
-        assert_eq!(result.len(), 1);
-        assert_eq!(result[0].name, "visible.excalidraw");
+        let err = rename_entry(base, "from", "to").unwrap_err();
+
+        assert_eq!(err, "移動先に同名のアイテムが既に存在します");
+        assert!(base.join("from/a.excalidraw").is_file());
+        assert!(!base.join("to/a.excalidraw").exists());
     }
 
     #[test]
-    fn collect_items_ドットで始まるフォルダをスキップする() {
+    fn rename_entry_同じパスへのリネームは成功する() {
         let tmp = TempDir::new().unwrap();
-        let base = tmp.path().to_path_buf();
-        make_dir(&base, ".git");
-        make_dir(&base, "myFolder");
+        let base = tmp.path();
+        fs::write(base.join("a.excalidraw"), "a").unwrap();
 
-        let result = collect_items(&base, &base).unwrap();
-        assert_eq!(result.len(), 1);
-        assert_eq!(result[0].name, "myFolder");
-        assert!(result[0].is_folder);
+        rename_entry(base, "a.excalidraw", "a.excalidraw").unwrap();
+
+        assert_eq!(fs::read_to_string(base.join("a.excalidraw")).unwrap(), "a");
     }
 
+    // ──────────────────────────────────────────────
+    // move_item のテスト
+    // ──────────────────────────────────────────────
+
     #[test]
-    fn collect_items_フォルダはファイルより先に並ぶ() {
+    fn move_item_into_存在しない入れ子フォルダを作って移動する() {
         let tmp = TempDir::new().unwrap();
-        let base = tmp.path().to_path_buf();
-        make_file(&base, "zzz.excalidraw");
-        make_dir(&base, "aaa");
+        let base = tmp.path();
+        make_file(base, "note.excalidraw");
 
-        let result = collect_items(&base, &base).unwrap();
-        assert_eq!(result.len(), 2);
-        assert!(result[0].is_folder, "folder should come first");
-        assert!(!result[1].is_folder, "file should come second");
+        assert_eq!(
+            move_item_into(base, "note.excalidraw", "work/2024", false).unwrap(),
+            "work/2024/note.excalidraw"
+        );
+        assert!(base.join("work/2024/note.excalidraw").is_file());
+        assert!(!base.join("note.excalidraw").exists());
     }
 
     #[test]
-    fn collect_items_同種アイテムはアルファベット順で並ぶ() {
+    fn move_item_into_自身の配下へのフォルダ移動を拒否する() {
         let tmp = TempDir::new().unwrap();
-        let base = tmp.path().to_path_buf();
-        make_file(&base, "zzz.excalidraw");
-        make_file(&base, "aaa.excalidraw");
-        make_file(&base, "mmm.excalidraw");
+        let base = tmp.path();
+        make_dir(base, "project/sub");
 
-        let result = collect_items(&base, &base).unwrap();
-        assert_eq!(result.len(), 3);
-        assert_eq!(result[0].name, "aaa.excalidraw");
-        assert_eq!(result[1].name, "mmm.excalidraw");
-        assert_eq!(result[2].name, "zzz.excalidraw");
+        assert!(move_item_into(base, "project", "project/sub", false).is_err());
+        assert!(move_item_into(base, "project", "project", false).is_err());
+        assert!(base.join("project/sub").is_dir());
+        assert!(!base.join("project/sub/project").exists());
     }
 
     #[test]
-    fn collect_items_アルファベット順は大文字小文字を区別しない() {
+    fn move_item_into_移動先に同名があれば元の名前のままエラーにする() {
         let tmp = TempDir::new().unwrap();
-        let base = tmp.path().to_path_buf();
-        make_file(&base, "Banana.excalidraw");
-        make_file(&base, "apple.excalidraw");
+        let base = tmp.path();
+        let work = make_dir(base, "work");
+        fs::write(base.join("note.excalidraw"), "moving").unwrap();
+        fs::write(work.join("note.excalidraw"), "existing").unwrap();
 
-        let result = collect_items(&base, &base).unwrap();
-        assert_eq!(result.len(), 2);
-        assert_eq!(result[0].name.to_lowercase(), "apple.excalidraw");
-        assert_eq!(result[1].name.to_lowercase(), "banana.excalidraw");
+        let err = move_item_into(base, "note.excalidraw", "work", false).unwrap_err();
+
+        assert_eq!(err, "移動先に同名のアイテムが既に存在します");
+        assert_eq!(
+            fs::read_to_string(base.join("note.excalidraw")).unwrap(),
+            "moving"
+        );
+        assert_eq!(
+            fs::read_to_string(work.join("note.excalidraw")).unwrap(),
+            "existing"
+        );
+        assert!(!work.join("note 2.excalidraw").exists());
     }
 
     #[test]
-    fn collect_items_フォルダ内のファイルを再帰的に収集する() {
+    fn move_item_into_アイコンのサイドカーも移動する() {
         let tmp = TempDir::new().unwrap();
-        let base = tmp.path().to_path_buf();
-        let sub = make_dir(&base, "subFolder");
-        make_file(&sub, "child.excalidraw");
+        let base = tmp.path();
+        let project = make_dir(base, "project/sub");
+        make_file(&project, "canvas.excalidraw");
+        make_dir(base, "archive");
+        make_dir(base, ".meta");
+        for path in ["project", "project/sub", "project/sub/canvas.excalidraw"] {
+            fs::write(get_icon_meta_path(base, path), "Star").unwrap();
+        }
 
-        let result = collect_items(&base, &base).unwrap();
-        assert_eq!(result.len(), 1);
-        assert!(result[0].is_folder);
-        assert_eq!(result[0].name, "subFolder");
-        let children = result[0].children.as_ref().unwrap();
-        assert_eq!(children.len(), 1);
-        assert_eq!(children[0].name, "child.excalidraw");
+        move_item_into(base, "project", "archive", false).unwrap();
+
+        for path in [
+            "archive/project",
+            "archive/project/sub",
+            "archive/project/sub/canvas.excalidraw",
+        ] {
+            assert_eq!(
+                load_item_icon(base, path).0.as_deref(),
+                Some("Star"),
+                "{path}"
+            );
+        }
+        assert_eq!(
+            load_item_icon(base, "project/sub/canvas.excalidraw"),
+            (None, None)
+        );
     }
 
+    // ──────────────────────────────────────────────
+    // find_duplicate_folder_names のテスト
+    // ──────────────────────────────────────────────
+
     #[test]
-    fn collect_items_相対パスはスラッシュ区切りになる() {
+    fn find_duplicate_folder_names_in_同名フォルダをまとめる() {
         let tmp = TempDir::new().unwrap();
-        let base = tmp.path().to_path_buf();
-        let sub = make_dir(&base, "folder");
-        make_file(&sub, "canvas.excalidraw");
+        let base = tmp.path();
+        make_dir(base, "web/assets");
+        make_dir(base, "mobile/assets");
+        make_dir(base, "docs");
+        make_file(base, "assets.excalidraw");
 
-        let result = collect_items(&base, &base).unwrap();
-        let children = result[0].children.as_ref().unwrap();
-        assert_eq!(children[0].path, "folder/canvas.excalidraw");
+        assert_eq!(
+            find_duplicate_folder_names_in(base).unwrap(),
+            vec![(
+                "assets".to_string(),
+                vec!["mobile/assets".to_string(), "web/assets".to_string()]
+            )]
+        );
     }
 
+    // ──────────────────────────────────────────────
+    // 開いた時刻のテスト
+    // ──────────────────────────────────────────────
+
     #[test]
-    fn collect_items_空のフォルダもchildren空ベクタで収集する() {
+    fn mark_opened_in_ファイルに触れずに時刻を記録する() {
         let tmp = TempDir::new().unwrap();
-        let base = tmp.path().to_path_buf();
-        make_dir(&base, "emptyFolder");
+        let base = tmp.path();
+        make_file(base, "a.excalidraw");
+        set_mtime(&base.join("a.excalidraw"), MARCH_2023);
 
-        let result = collect_items(&base, &base).unwrap();
-        assert_eq!(result.len(), 1);
-        assert!(result[0].is_folder);
-        assert_eq!(result[0].name, "emptyFolder");
-        let children = result[0].children.as_ref().unwrap();
-        assert!(children.is_empty());
+        mark_opened_in(base, "a.excalidraw", 1_000).unwrap();
+        mark_opened_in(base, "a.excalidraw", 2_000).unwrap();
+
+        assert_eq!(load_opened(base)["a.excalidraw"], 2_000);
+        let modified = fs::metadata(base.join("a.excalidraw"))
+            .unwrap()
+            .modified()
+            .unwrap();
+        assert_eq!(
+            modified,
+            UNIX_EPOCH + std::time::Duration::from_secs(MARCH_2023)
+        );
+        assert!(mark_opened_in(base, "missing.excalidraw", 1_000).is_err());
     }
 
     #[test]
-    fn collect_items_複数階層の再帰が正しく動く() {
+    fn mark_opened_in_開いた回数と最近の一覧には触れない() {
         let tmp = TempDir::new().unwrap();
-        let base = tmp.path().to_path_buf();
-        let a = make_dir(&base, "a");
-        let b = make_dir(&a, "b");
-        make_file(&b, "deep.excalidraw");
+        let base = tmp.path();
+        make_file(base, "a.excalidraw");
 
-        let result = collect_items(&base, &base).unwrap();
-        assert_eq!(result.len(), 1); // a
-        let a_children = result[0].children.as_ref().unwrap();
-        assert_eq!(a_children.len(), 1); // b
-        let b_children = a_children[0].children.as_ref().unwrap();
-        assert_eq!(b_children.len(), 1); // deep.excalidraw
-        assert_eq!(b_children[0].name, "deep.excalidraw");
-        assert_eq!(b_children[0].path, "a/b/deep.excalidraw");
+        mark_opened_in(base, "a.excalidraw", 1_000).unwrap();
+
+        assert!(load_open_counts(base).is_empty());
+        assert!(load_recent(base).is_empty());
     }
 
     #[test]
-    fn collect_items_フォルダとファイルが混在するとき正しくソートする() {
+    fn rename_entry_開いた記録を移動先に付け替える() {
         let tmp = TempDir::new().unwrap();
-        let base = tmp.path().to_path_buf();
-        make_file(&base, "z-file.excalidraw");
-        make_dir(&base, "m-folder");
-        make_file(&base, "a-file.excalidraw");
-        make_dir(&base, "z-folder");
+        let base = tmp.path();
+        make_dir(base, "work");
+        make_file(base, "work/a.excalidraw");
+        record_open_in(base, "work/a.excalidraw").unwrap();
+        mark_opened_in(base, "work/a.excalidraw", 1_000).unwrap();
 
-        let result = collect_items(&base, &base).unwrap();
-        assert_eq!(result.len(), 4);
-        // フォルダが先、アルファベット順
-        assert_eq!(result[0].name, "m-folder");
-        assert!(result[0].is_folder);
-        assert_eq!(result[1].name, "z-folder");
-        assert!(result[1].is_folder);
-        // ファイルが後、アルファベット順
-        assert_eq!(result[2].name, "a-file.excalidraw");
-        assert!(!result[2].is_folder);
-        assert_eq!(result[3].name, "z-file.excalidraw");
-        assert!(!result[3].is_folder);
+        rename_entry(base, "work", "done").unwrap();
+
+        assert_eq!(load_recent(base), vec!["done/a.excalidraw".to_string()]);
+        let counts = load_open_counts(base);
+        assert!(!counts.contains_key("work/a.excalidraw"));
+        assert_eq!(counts["done/a.excalidraw"], 1);
+        assert_eq!(load_opened(base)["done/a.excalidraw"], 1_000);
+    }
+
+    #[test]
+    fn delete_entry_開いた記録を消す() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        make_file(base, "a.excalidraw");
+        make_file(base, "b.excalidraw");
+        record_open_in(base, "a.excalidraw").unwrap();
+        record_open_in(base, "b.excalidraw").unwrap();
+        mark_opened_in(base, "a.excalidraw", 1_000).unwrap();
+
+        delete_entry(base, "a.excalidraw").unwrap();
+
+        assert_eq!(load_recent(base), vec!["b.excalidraw".to_string()]);
+        let counts = load_open_counts(base);
+        assert!(!counts.contains_key("a.excalidraw"));
+        assert!(counts.contains_key("b.excalidraw"));
+        assert!(!load_opened(base).contains_key("a.excalidraw"));
+    }
+
+    #[test]
+    fn collect_items_一覧に開いた時刻を含める() {
+        let tmp = TempDir::new().unwrap();
+        let base = &tmp.path().to_path_buf();
+        make_dir(base, "work");
+        make_file(base, "work/a.excalidraw");
+        make_file(base, "b.excalidraw");
+        mark_opened_in(base, "work/a.excalidraw", 1_234).unwrap();
+
+        let items = collect_items(base, base).unwrap();
+
+        assert_eq!(items[0].opened_at, None);
+        let children = items[0].children.as_ref().unwrap();
+        assert_eq!(children[0].opened_at, Some(1_234));
+        assert_eq!(items[1].opened_at, None);
+        let json = serde_json::to_value(&children[0]).unwrap();
+        assert_eq!(json["openedAt"], 1_234);
+    }
+
+    // ──────────────────────────────────────────────
+    // FileItem の要素数のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn collect_items_キャンバスの要素数を含める() {
+        let tmp = TempDir::new().unwrap();
+        let base = &tmp.path().to_path_buf();
+        write_canvas(
+            base,
+            "three.excalidraw",
+            serde_json::json!([
+                {"id": "a", "type": "rectangle"},
+                {"id": "b", "type": "ellipse"},
+                {"id": "c", "type": "text", "text": "hi"}
+            ]),
+        );
+        fs::write(base.join("broken.excalidraw"), "{not json").unwrap();
+        make_dir(base, "folder");
+
+        let items = collect_items(base, base).unwrap();
+        let count = |name: &str| {
+            items
+                .iter()
+                .find(|item| item.name == name)
+                .unwrap()
+                .element_count
+        };
+
+        assert_eq!(count("three.excalidraw"), Some(3));
+        assert_eq!(count("broken.excalidraw"), None);
+        assert_eq!(count("folder"), None);
+    }
+
+    // ──────────────────────────────────────────────
+    // 名前空間付きゴミ箱のテスト
+    // ──────────────────────────────────────────────
+
+    fn trash_namespaced(base: &std::path::Path, trash: &std::path::Path, path: &str) -> String {
+        move_to_trash(base, &trash_for(trash, path, true), path).unwrap()
+    }
+
+    #[test]
+    fn trash_for_ルートフォルダごとのゴミ箱に分ける() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = make_dir(tmp.path(), "trash");
+        make_dir(&base, "work/deep");
+        make_dir(&base, "home");
+        make_file(&base, "work/deep/a.excalidraw");
+        make_file(&base, "home/b.excalidraw");
+        make_file(&base, "root.excalidraw");
+
+        let a = trash_namespaced(&base, &trash, "work/deep/a.excalidraw");
+        let b = trash_namespaced(&base, &trash, "home/b.excalidraw");
+        let root = trash_namespaced(&base, &trash, "root.excalidraw");
+
+        assert!(trash.join("work").join(&a).is_file());
+        assert!(trash.join("home").join(&b).is_file());
+        assert!(trash.join(&root).is_file());
+
+        let items = read_trash_items(&trash).unwrap();
+        let mut listed: Vec<(Option<&str>, &str, String)> = items
+            .iter()
+            .map(|item| {
+                (
+                    item.namespace.as_deref(),
+                    item.original_path.as_str(),
+                    item.trash_path.clone(),
+                )
+            })
+            .collect();
+        listed.sort();
+        assert_eq!(
+            listed,
+            vec![
+                (None, "root.excalidraw", root),
+                (Some("home"), "home/b.excalidraw", format!("home/{}", b)),
+                (
+                    Some("work"),
+                    "work/deep/a.excalidraw",
+                    format!("work/{}", a)
+                ),
+            ]
+        );
+        assert!(!trash_needs_repair_in(&trash).unwrap());
+    }
+
+    #[test]
+    fn restore_from_trash_名前空間付きの項目を復元できる() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = make_dir(tmp.path(), "trash");
+        make_dir(&base, "work");
+        make_file(&base, "work/a.excalidraw");
+        make_file(&base, "work/b.excalidraw");
+        let a = trash_namespaced(&base, &trash, "work/a.excalidraw");
+        let b = trash_namespaced(&base, &trash, "work/b.excalidraw");
+
+        restore_from_trash(&base, &trash, &format!("work/{}", a)).unwrap();
+        delete_from_trash(&base, &trash, &format!("work/{}", b)).unwrap();
+
+        assert!(base.join("work/a.excalidraw").is_file());
+        assert!(read_trash_items(&trash).unwrap().is_empty());
+    }
+
+    #[test]
+    fn read_trash_items_旧来のフラットな項目も読む() {
+        let tmp = TempDir::new().unwrap();
+        let base = make_dir(tmp.path(), "canvases");
+        let trash = make_dir(tmp.path(), "trash");
+        make_dir(&base, "work");
+        make_file(&base, "work/flat.excalidraw");
+        make_file(&base, "work/namespaced.excalidraw");
+        move_to_trash(&base, &trash, "work/flat.excalidraw").unwrap();
+        trash_namespaced(&base, &trash, "work/namespaced.excalidraw");
+
+        let mut originals: Vec<String> = read_trash_items(&trash)
+            .unwrap()
+            .into_iter()
+            .map(|item| item.original_path)
+            .collect();
+        originals.sort();
+        assert_eq!(
+            originals,
+            vec!["work/flat.excalidraw", "work/namespaced.excalidraw"]
+        );
+    }
+
+    // ──────────────────────────────────────────────
+    // search_canvases のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn search_canvases_in_全階層のファイル名だけを大小無視で探す() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        make_dir(base, "Roadmap/2024/q1");
+        make_dir(base, ".versions/roadmap");
+        make_file(base, "roadmap.excalidraw");
+        make_file(base, "Roadmap/2024/Product ROADMAP.excalidraw");
+        make_file(base, "Roadmap/2024/q1/kickoff.excalidraw");
+        make_file(base, "Roadmap/2024/q1/old-roadmap.excalidraw");
+        make_file(base, ".versions/roadmap/1.excalidraw");
+        make_file(base, "roadmap.txt");
+
+        let paths: Vec<String> = search_canvases_in(base, "RoadMap")
+            .unwrap()
+            .into_iter()
+            .map(|item| item.path)
+            .collect();
+
+        assert_eq!(
+            paths,
+            vec![
+                "Roadmap/2024/Product ROADMAP.excalidraw",
+                "Roadmap/2024/q1/old-roadmap.excalidraw",
+                "roadmap.excalidraw"
+            ]
+        );
+    }
+
+    // ──────────────────────────────────────────────
+    // export_pdf のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn canvas_pdf_図形1つのキャンバスをpdfにする() {
+        let doc = serde_json::json!({"elements": [{
+            "id": "r", "type": "rectangle", "x": 10, "y": 20, "width": 200, "height": 100,
+            "strokeColor": "#1e1e1e", "backgroundColor": "#ffc9c9", "strokeWidth": 2
+        }]});
+
+        for page_size in [PdfPageSize::Fit, PdfPageSize::A4, PdfPageSize::Letter] {
+            let (pdf, report) = canvas_pdf(&doc, page_size).unwrap();
+            assert!(pdf.starts_with(b"%PDF"));
+            assert!(pdf.len() > 500, "{} bytes", pdf.len());
+            assert_eq!(report, PdfExportReport::default());
+        }
+    }
+
+    #[test]
+    fn canvas_pdf_表示できない文字と画像を報告する() {
+        let doc = serde_json::json!({"elements": [
+            {"id": "latin", "type": "text", "x": 0, "y": 0, "text": "Café €5"},
+            {"id": "ja", "type": "text", "x": 0, "y": 40, "text": "日本語のメモ"},
+            {"id": "mixed", "type": "text", "x": 0, "y": 80, "text": "ok\n→ next"},
+            {"id": "img", "type": "image", "x": 0, "y": 120, "width": 50, "height": 50,
+             "fileId": "f"}
+        ]});
+
+        let (pdf, report) = canvas_pdf(&doc, PdfPageSize::Fit).unwrap();
+
+        assert!(pdf.starts_with(b"%PDF"));
+        assert_eq!(report.skipped_text, vec!["ja", "mixed"]);
+        assert_eq!(report.skipped_images, vec!["img"]);
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["skippedText"][0], "ja");
+    }
+
+    #[test]
+    fn parse_hex_color_短縮形と透明を扱う() {
+        assert_eq!(parse_hex_color("#ff0000"), Some((1.0, 0.0, 0.0)));
+        assert_eq!(parse_hex_color("#fff"), Some((1.0, 1.0, 1.0)));
+        assert_eq!(parse_hex_color("transparent"), None);
+        assert_eq!(parse_hex_color("#12345"), None);
+    }
+
+    #[test]
+    fn element_outline_回転と線のポイントを反映する() {
+        let rect = serde_json::json!({
+            "type": "rectangle", "x": 0, "y": 0, "width": 2, "height": 2,
+            "angle": std::f64::consts::PI
+        });
+        let (points, closed) = element_outline(&rect).unwrap();
+        assert!(closed);
+        assert!((points[0].0 - 2.0).abs() < 1e-9 && (points[0].1 - 2.0).abs() < 1e-9);
+
+        let line = serde_json::json!({
+            "type": "line", "x": 5, "y": 5, "width": 10, "height": 0, "points": [[0, 0], [10, 0]]
+        });
+        assert_eq!(
+            element_outline(&line).unwrap(),
+            (vec![(5.0, 5.0), (15.0, 5.0)], false)
+        );
+        assert!(element_outline(&serde_json::json!({"type": "text"})).is_none());
+    }
+
+    // ──────────────────────────────────────────────
+    // search_canvas_text のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn search_canvas_text_in_一致するテキスト要素を集める() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        make_dir(base, "team");
+        write_canvas(
+            base,
+            "team/arch.excalidraw",
+            serde_json::json!([
+                {"id": "a", "type": "text", "text": "Auth Service"},
+                {"id": "b", "type": "text", "text": "Billing"},
+                {"id": "c", "type": "rectangle", "text": "auth"},
+                {"id": "d", "type": "text", "text": "old AUTH", "isDeleted": true},
+                {"id": "e", "type": "text", "text": "OAuth flow"}
+            ]),
+        );
+        write_canvas(
+            base,
+            "other.excalidraw",
+            serde_json::json!([{"id": "a", "type": "text", "text": "Billing"}]),
+        );
+        fs::write(base.join("broken.excalidraw"), "{").unwrap();
+
+        assert_eq!(
+            search_canvas_text_in(base, "AUTH").unwrap(),
+            vec![SearchHit {
+                path: "team/arch.excalidraw".to_string(),
+                snippets: vec!["Auth Service".to_string(), "OAuth flow".to_string()],
+            }]
+        );
+    }
+
+    // ──────────────────────────────────────────────
+    // fix_dangling_bindings のテスト
+    // ──────────────────────────────────────────────
+
+    #[test]
+    fn fix_dangling_bindings_in_存在しない要素への参照を取り除く() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path();
+        write_canvas(
+            base,
+            "a.excalidraw",
+            serde_json::json!([
+                {"id": "box", "type": "rectangle", "boundElements": [
+                    {"type": "text", "id": "label"},
+                    {"type": "arrow", "id": "gone-arrow"}
+                ]},
+                {"id": "label", "type": "text", "text": "ok", "containerId": "box"},
+                {"id": "orphan", "type": "text", "text": "x", "containerId": "gone-box", "frameId": "gone-frame"},
+                {"id": "deleted", "type": "frame", "isDeleted": true},
+                {"id": "framed", "type": "ellipse", "frameId": "deleted"}
+            ]),
+        );
+
+        assert_eq!(fix_dangling_bindings_in(base, "a.excalidraw").unwrap(), 4);
+
+        let doc = read_canvas_json(&base.join("a.excalidraw")).unwrap();
+        let elements = doc["elements"].as_array().unwrap();
+        assert_eq!(
+            elements[0]["boundElements"],
+            serde_json::json!([{"type": "text", "id": "label"}])
+        );
+        assert_eq!(elements[1]["containerId"], "box");
+        assert!(elements[2]["containerId"].is_null());
+        assert!(elements[2]["frameId"].is_null());
+        assert!(elements[4]["frameId"].is_null());
+        assert_eq!(fix_dangling_bindings_in(base, "a.excalidraw").unwrap(), 0);
     }
 }
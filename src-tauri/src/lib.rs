@@ -5,6 +5,8 @@ use tauri::Manager;
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
+        .manage(commands::VaultSizeCache::default())
+        .manage(commands::FsSemaphore::default())
         .setup(|app| {
             // Create base canvas directory on startup
             let base_dir = commands::resolve_base_dir(app.handle())?;
@@ -12,6 +14,7 @@ pub fn run() {
             // Create trash directory on startup
             let trash_dir = commands::resolve_trash_dir(app.handle())?;
             std::fs::create_dir_all(&trash_dir)?;
+            commands::configure_fs_semaphore(app.handle());
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -24,12 +27,115 @@ pub fn run() {
             commands::save_canvas,
             commands::copy_canvas,
             commands::get_base_directory,
+            commands::get_base_directory_canonical,
             commands::trash_item,
             commands::list_trash,
+            commands::list_trash_since,
+            commands::trash_needs_repair,
+            commands::repair_trash,
             commands::restore_item,
             commands::delete_permanently,
             commands::empty_trash,
             commands::set_item_icon,
+            commands::canvas_format_info,
+            commands::get_history,
+            commands::validate_path,
+            commands::reorder_item,
+            commands::backup_vault,
+            commands::restore_vault,
+            commands::element_counts,
+            commands::organize_by_date,
+            commands::find_canvases_with_element_type,
+            commands::list_canvas_snapshots,
+            commands::folder_color,
+            commands::trash_matching,
+            commands::generate_index_canvas,
+            commands::fix_missing_files_key,
+            commands::group_by_root_folder,
+            commands::repair_zero_byte_canvases,
+            commands::record_open,
+            commands::mark_opened,
+            commands::find_never_opened,
+            commands::list_recent,
+            commands::affix_names,
+            commands::canvas_palette,
+            commands::find_broken_image_refs,
+            commands::import_files_from,
+            commands::folder_has_canvases,
+            commands::set_grid_size,
+            commands::export_clipboard,
+            commands::import_clipboard,
+            commands::find_foreign_source,
+            commands::set_canvas_source,
+            commands::delete_if_empty,
+            commands::embedded_image_stats,
+            commands::create_version,
+            commands::list_versions,
+            commands::clear_versions,
+            commands::list_dir_filtered,
+            commands::canvas_hash,
+            commands::save_canvas_guarded,
+            commands::new_untitled,
+            commands::canvas_created_time,
+            commands::history_stats,
+            commands::clear_history,
+            commands::export_elements_csv,
+            commands::find_offscreen_canvases,
+            commands::canvas_text_preview,
+            commands::add_to_collection,
+            commands::remove_from_collection,
+            commands::list_collection,
+            commands::diff_versions,
+            commands::migrate_trash_index,
+            commands::find_similar_canvases,
+            commands::pin_folder,
+            commands::unpin_folder,
+            commands::list_pinned_folders,
+            commands::rename_item_transactional,
+            commands::canvas_group_count,
+            commands::find_stale_canvases,
+            commands::lint_folder,
+            commands::autofix_folder,
+            commands::list_dir_streaming,
+            commands::set_canvas_icon,
+            commands::get_canvas_icon,
+            commands::canvas_etag,
+            commands::find_heavy_canvases,
+            commands::canvas_scene_size,
+            commands::rekey_embedded_files,
+            commands::export_subtree,
+            commands::find_id_collisions,
+            commands::save_ui_state,
+            commands::load_ui_state,
+            commands::move_items,
+            commands::move_item,
+            commands::compare_snapshots,
+            commands::get_settings,
+            commands::save_settings,
+            commands::find_remnants,
+            commands::folder_element_histogram,
+            commands::check_import_compatibility,
+            commands::swap_canvases,
+            commands::extract_elements,
+            commands::depth_histogram,
+            commands::prune_orphan_versions,
+            commands::list_most_opened,
+            commands::export_path_list,
+            commands::find_title_mismatches,
+            commands::compress_vault,
+            commands::decompress_vault,
+            commands::find_giant_elements,
+            commands::read_canvas_light,
+            commands::group_by_modified_week,
+            commands::replace_text_in_canvases,
+            commands::vault_storage_type,
+            commands::duplicate_item,
+            commands::create_canvas_from_mermaid,
+            commands::find_duplicate_folder_names,
+            commands::search_canvases,
+            commands::export_pdf,
+            commands::search_canvas_text,
+            commands::fix_dangling_bindings,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
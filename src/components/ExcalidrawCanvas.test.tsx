@@ -281,6 +281,38 @@ describe("ExcalidrawCanvas", () => {
       expect(saved.files).toEqual({ file1: { mimeType: "image/png" } });
     });
 
+    it("容量上限で保存に失敗したらエラーを表示し、次の変更で再保存する", async () => {
+      mockSaveCanvas.mockRejectedValueOnce({
+        kind: "quotaExceeded",
+        limit: 1024,
+        projected: 2048,
+      });
+
+      render(<ExcalidrawCanvas selectedFile={sampleFile} />);
+      await act(async () => {});
+
+      act(() => {
+        capturedOnChange?.([{ id: "elem1" }], { viewBackgroundColor: "#fff" }, {});
+      });
+      await act(async () => {
+        vi.advanceTimersByTime(1000);
+      });
+
+      expect(screen.getByRole("alert")).toHaveTextContent(
+        "容量上限を超えるため保存できません (2.0 KB / 1.0 KB)"
+      );
+
+      act(() => {
+        capturedOnChange?.([{ id: "elem2" }], { viewBackgroundColor: "#fff" }, {});
+      });
+      await act(async () => {
+        vi.advanceTimersByTime(1000);
+      });
+
+      expect(mockSaveCanvas).toHaveBeenCalledTimes(2);
+      expect(screen.queryByRole("alert")).not.toBeInTheDocument();
+    });
+
     it("ファイル未選択時は onChange があっても保存しない", async () => {
       render(<ExcalidrawCanvas selectedFile={null} />);
       await act(async () => {});
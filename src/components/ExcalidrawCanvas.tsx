@@ -33,12 +33,37 @@ const DEFAULT_CANVAS_DATA: ExcalidrawInitialDataState = {
   files: {},
 };
 
+const formatFileSize = (bytes: number | undefined) => {
+  if (!bytes) return "0 B";
+  const units = ["B", "KB", "MB", "GB"];
+  let size = bytes;
+  let unitIndex = 0;
+  while (size >= 1024 && unitIndex < units.length - 1) {
+    size /= 1024;
+    unitIndex++;
+  }
+  return `${size.toFixed(unitIndex === 0 ? 0 : 1)} ${units[unitIndex]}`;
+};
+
+// save_canvas の SaveError（kind でタグ付け）を表示用の文言にする
+const describeSaveError = (err: unknown): string => {
+  if (typeof err === "object" && err !== null && "kind" in err) {
+    const saveError = err as { kind: string; limit?: number; projected?: number; message?: string };
+    if (saveError.kind === "quotaExceeded") {
+      return `容量上限を超えるため保存できません (${formatFileSize(saveError.projected)} / ${formatFileSize(saveError.limit)})`;
+    }
+    if (saveError.message) return saveError.message;
+  }
+  return String(err);
+};
+
 export function ExcalidrawCanvas({ selectedFile }: ExcalidrawCanvasProps) {
   const [initialData, setInitialData] =
     useState<ExcalidrawInitialDataState | null>(null);
   const [isLoading, setIsLoading] = useState(false);
   const [loadError, setLoadError] = useState<string | null>(null);
   const [isSaving, setIsSaving] = useState(false);
+  const [saveError, setSaveError] = useState<string | null>(null);
   const [canRender, setCanRender] = useState(false);
   const saveTimerRef = useRef<ReturnType<typeof setTimeout> | null>(null);
   const currentFileRef = useRef<string | null>(null);
@@ -47,10 +72,12 @@ export function ExcalidrawCanvas({ selectedFile }: ExcalidrawCanvasProps) {
     appState: AppState;
     files: BinaryFiles;
   } | null>(null);
+  // 最後の変更が保存済みかどうか。保存に失敗した間は true のまま残す
+  const dirtyRef = useRef(false);
   const { readCanvas, saveCanvas } = useTauriFS();
 
   const forceSave = useCallback(async () => {
-    if (!currentFileRef.current || !lastDataRef.current) return;
+    if (!currentFileRef.current || !lastDataRef.current || !dirtyRef.current) return;
 
     const { elements, appState, files } = lastDataRef.current;
     const data: ExcalidrawInitialDataState = {
@@ -69,8 +96,11 @@ export function ExcalidrawCanvas({ selectedFile }: ExcalidrawCanvasProps) {
 
     try {
       await saveCanvas(currentFileRef.current, JSON.stringify(data));
+      dirtyRef.current = false;
+      setSaveError(null);
     } catch (err) {
       console.error("保存に失敗しました:", err);
+      setSaveError(describeSaveError(err));
     }
   }, [saveCanvas]);
 
@@ -97,6 +127,9 @@ export function ExcalidrawCanvas({ selectedFile }: ExcalidrawCanvasProps) {
         setIsSaving(true);
         await forceSave();
         setIsSaving(false);
+        // 前のキャンバスの内容を新しいパスへ保存しないよう手放す（保存失敗時はエラー表示が残る）
+        lastDataRef.current = null;
+        dirtyRef.current = false;
       }
 
       currentFileRef.current = selectedFile.path;
@@ -150,6 +183,7 @@ export function ExcalidrawCanvas({ selectedFile }: ExcalidrawCanvasProps) {
       if (!selectedFile) return;
 
       lastDataRef.current = { elements, appState, files };
+      dirtyRef.current = true;
 
       if (saveTimerRef.current) {
         clearTimeout(saveTimerRef.current);
@@ -177,8 +211,13 @@ export function ExcalidrawCanvas({ selectedFile }: ExcalidrawCanvasProps) {
         setIsSaving(true);
         try {
           await saveCanvas(filePath, JSON.stringify(data));
+          if (lastDataRef.current?.elements === elements) {
+            dirtyRef.current = false;
+          }
+          setSaveError(null);
         } catch (err) {
           console.error("保存に失敗しました:", err);
+          setSaveError(describeSaveError(err));
         } finally {
           setIsSaving(false);
         }
@@ -243,18 +282,6 @@ export function ExcalidrawCanvas({ selectedFile }: ExcalidrawCanvasProps) {
   const IconComponent = selectedFile.icon && iconMap[selectedFile.icon] ? iconMap[selectedFile.icon] : File;
   const iconColor = selectedFile.iconColor && selectedFile.iconColor !== "default" ? selectedFile.iconColor : undefined;
 
-  const formatFileSize = (bytes: number | undefined) => {
-    if (!bytes) return "0 B";
-    const units = ["B", "KB", "MB", "GB"];
-    let size = bytes;
-    let unitIndex = 0;
-    while (size >= 1024 && unitIndex < units.length - 1) {
-      size /= 1024;
-      unitIndex++;
-    }
-    return `${size.toFixed(unitIndex === 0 ? 0 : 1)} ${units[unitIndex]}`;
-  };
-
   const formatDate = (timestamp: number | undefined) => {
     if (!timestamp) return "";
     const date = new Date(timestamp * 1000);
@@ -287,6 +314,11 @@ export function ExcalidrawCanvas({ selectedFile }: ExcalidrawCanvasProps) {
           {loadError && (
             <span className="canvas-error">読み込みエラー: {loadError}</span>
           )}
+          {saveError && (
+            <span className="canvas-error" role="alert">
+              未保存の変更があります: {saveError}
+            </span>
+          )}
           {isSaving && (
             <span className="canvas-saving">
               <Loader2 size={14} className="spinner-small" />
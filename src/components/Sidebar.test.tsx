@@ -8,6 +8,8 @@ const mockCreateCanvas = vi.fn();
 const mockCreateFolder = vi.fn();
 const mockDeleteItem = vi.fn();
 const mockRenameItem = vi.fn();
+const mockTrashNeedsRepair = vi.fn();
+const mockRepairTrash = vi.fn();
 
 vi.mock("../hooks/useTauriFS", () => ({
   useTauriFS: () => ({
@@ -15,6 +17,8 @@ vi.mock("../hooks/useTauriFS", () => ({
     createFolder: mockCreateFolder,
     deleteItem: mockDeleteItem,
     renameItem: mockRenameItem,
+    trashNeedsRepair: mockTrashNeedsRepair,
+    repairTrash: mockRepairTrash,
     listDir: vi.fn().mockResolvedValue([]),
     readCanvas: vi.fn().mockResolvedValue(""),
     saveCanvas: vi.fn().mockResolvedValue(undefined),
@@ -50,6 +54,8 @@ describe("Sidebar", () => {
     mockCreateFolder.mockResolvedValue(undefined);
     mockDeleteItem.mockResolvedValue(undefined);
     mockRenameItem.mockResolvedValue(undefined);
+    mockTrashNeedsRepair.mockResolvedValue(false);
+    mockRepairTrash.mockResolvedValue(0);
   });
 
   describe("空のファイルツリー", () => {
@@ -440,6 +446,44 @@ describe("Sidebar", () => {
     });
   });
 
+  describe("ゴミ箱の整合性チェック", () => {
+    it("表示時に確認し、不整合があれば案内を表示する", async () => {
+      mockTrashNeedsRepair.mockResolvedValue(true);
+      render(<Sidebar {...defaultProps} />);
+
+      expect(
+        await screen.findByText(/ゴミ箱に一覧に表示されない項目があります/)
+      ).toBeInTheDocument();
+      expect(mockTrashNeedsRepair).toHaveBeenCalledTimes(1);
+    });
+
+    it("「整理する」で孤立した項目だけを整理し、案内を消す", async () => {
+      mockTrashNeedsRepair.mockResolvedValueOnce(true).mockResolvedValueOnce(false);
+      render(<Sidebar {...defaultProps} />);
+
+      await userEvent.click(await screen.findByText("整理する"));
+
+      await waitFor(() => {
+        expect(
+          screen.queryByText(/ゴミ箱に一覧に表示されない項目があります/)
+        ).not.toBeInTheDocument();
+      });
+      expect(mockRepairTrash).toHaveBeenCalledTimes(1);
+      expect(mockTrashNeedsRepair).toHaveBeenCalledTimes(2);
+    });
+
+    it("整合していれば案内を表示しない", async () => {
+      render(<Sidebar {...defaultProps} />);
+
+      await waitFor(() => {
+        expect(mockTrashNeedsRepair).toHaveBeenCalled();
+      });
+      expect(
+        screen.queryByText(/ゴミ箱に一覧に表示されない項目があります/)
+      ).not.toBeInTheDocument();
+    });
+  });
+
   describe("ネストしたパスの構築", () => {
     it("フォルダ内でキャンバスを作成するとパスが正しく構築される", async () => {
       vi.spyOn(window, "prompt").mockReturnValue("child");
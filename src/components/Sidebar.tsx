@@ -408,6 +408,7 @@ export function Sidebar({
   const [contextMenu, setContextMenu] = useState<ContextMenuState | null>(null);
   const [trashItems, setTrashItems] = useState<TrashItem[]>([]);
   const [isTrashExpanded, setIsTrashExpanded] = useState(false);
+  const [needsTrashRepair, setNeedsTrashRepair] = useState(false);
   const [trashContextMenu, setTrashContextMenu] =
     useState<TrashContextMenuState | null>(null);
   const [searchQuery, setSearchQuery] = useState("");
@@ -450,6 +451,8 @@ export function Sidebar({
     restoreItem,
     deletePermanently,
     emptyTrash,
+    trashNeedsRepair,
+    repairTrash,
     setItemIcon,
     copyCanvas,
     getBaseDirectory,
@@ -464,10 +467,19 @@ export function Sidebar({
     }
   }, [listTrash]);
 
+  const checkTrash = useCallback(async () => {
+    try {
+      setNeedsTrashRepair(await trashNeedsRepair());
+    } catch {
+      // ignore
+    }
+  }, [trashNeedsRepair]);
+
   useEffect(() => {
     loadTrash();
+    checkTrash();
     loadBaseDirectory();
-  }, [loadTrash]);
+  }, [loadTrash, checkTrash]);
 
   const loadBaseDirectory = useCallback(async () => {
     try {
@@ -851,10 +863,21 @@ export function Sidebar({
     try {
       await emptyTrash();
       await loadTrash();
+      await checkTrash();
     } catch (err) {
       alert(`ゴミ箱の削除に失敗しました: ${err}`);
     }
-  }, [emptyTrash, loadTrash]);
+  }, [emptyTrash, loadTrash, checkTrash]);
+
+  const handleRepairTrash = useCallback(async () => {
+    try {
+      await repairTrash();
+      await loadTrash();
+      await checkTrash();
+    } catch (err) {
+      alert(`ゴミ箱の整理に失敗しました: ${err}`);
+    }
+  }, [repairTrash, loadTrash, checkTrash]);
 
   const handleIconClick = useCallback((item: FileItem) => {
     setIconPickerState({ isOpen: true, item });
@@ -1051,6 +1074,15 @@ export function Sidebar({
           )}
         </div>
 
+        {needsTrashRepair && (
+          <div className="trash-repair-msg">
+            ゴミ箱に一覧に表示されない項目があります。
+            <button className="trash-repair-button" onClick={handleRepairTrash}>
+              整理する
+            </button>
+          </div>
+        )}
+
         {isTrashExpanded && (
           <div className="trash-list">
             {trashItems.length === 0 ? (
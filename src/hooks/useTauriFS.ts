@@ -68,6 +68,14 @@ export function useTauriFS() {
     return invoke("empty_trash");
   }, []);
 
+  const trashNeedsRepair = useCallback((): Promise<boolean> => {
+    return invoke<boolean>("trash_needs_repair");
+  }, []);
+
+  const repairTrash = useCallback((): Promise<number> => {
+    return invoke<number>("repair_trash");
+  }, []);
+
   const setItemIcon = useCallback((path: string, icon: string, color?: string): Promise<void> => {
     return invoke("set_item_icon", { path, icon, color: color || null });
   }, []);
@@ -87,6 +95,8 @@ export function useTauriFS() {
     restoreItem,
     deletePermanently,
     emptyTrash,
+    trashNeedsRepair,
+    repairTrash,
     setItemIcon,
   };
 }
@@ -9,6 +9,8 @@ export interface FileItem {
   iconColor?: string;
   modified?: number;
   size?: number;
+  openedAt?: number;
+  elementCount?: number;
 }
 
 export interface TrashItem {
@@ -17,6 +19,7 @@ export interface TrashItem {
   originalPath: string;
   isFolder: boolean;
   trashedAt: number;
+  namespace?: string | null;
 }
 
 export interface ContextMenuState {